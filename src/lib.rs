@@ -0,0 +1,3 @@
+
+pub mod manipulators;
+mod utils;
@@ -1,12 +1,6 @@
-#![allow(unused_mut)]
-use std::fs;
+use htmlparser::manipulators::dom::Consumer;
 
-use manipulators::dom::Consumer;
-
-use crate::manipulators::dom::DOM;
-
-mod manipulators;
-mod utils;
+use htmlparser::manipulators::dom::DOM;
 
 
 fn main() {
@@ -15,7 +9,9 @@ fn main() {
 
     //println!("{:#?}", Json::parse("{\"firstName\" : \"John\",\"lastName\" : \"Doe\",\"age\" : 23,\"residency\" : {\"address\" : \"One Way 21\",\"zip\" : 123567,\"city\" : \"Big City\"},\"pets\" : [{\"animal\" : \"cat\", \"age\" : 2, \"name\" : \"Tom\"}, {\"animal\" : \"mouse\", \"age\" : 1, \"name\" : \"Jerry\"}],\"lastCoordinates\" : [[\"lat 84.45369\", \"long 12.5467\"], [\"lat 55.255657\", \"long 67.35677\"]]}"));
 
-    let mut consumer: Consumer = Consumer::from(&index);
-    let mut tokens = DOM::parse(consumer);
-    println!("{:#?}", tokens);
+    let consumer: Consumer = Consumer::from(index);
+    match DOM::parse(consumer) {
+        Ok(document) => println!("{:#?}", document.to_node(document.root())),
+        Err(error) => println!("{}", error),
+    }
 }
@@ -0,0 +1,128 @@
+#![allow(dead_code)]
+
+use std::rc::Rc;
+
+/* Scans a string one Unicode scalar value at a time.
+pos is the byte offset of the current character ch, it is always on a character boundary and pos == size means the input is exhausted */
+#[derive(Debug, Clone)]
+pub struct Consumer {
+    buf: Rc<str>,
+    pos: usize,
+    size: usize,
+    ch: char,
+}
+impl Consumer {
+    pub fn new(src: &str) -> Consumer {
+        Consumer {
+            buf: Rc::from(src),
+            pos: 0,
+            size: src.len(),
+            ch: src.chars().next().unwrap_or('\0'),
+        }
+    }
+
+    pub fn from(str: &str) -> Consumer {
+        Self::new(str)
+    }
+
+    pub fn eat(&mut self) -> char {
+        if let Some(ch) = self.rest().chars().next() {
+            self.pos += ch.len_utf8();
+        }
+        if let Some(ch) = self.rest().chars().next() {
+            self.ch = ch;
+        }
+        return self.ch;
+    }
+
+    /* Byte offset of the current character */
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /* Length of the input in bytes */
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.pos >= self.size
+    }
+
+    pub fn current(&self) -> Option<char> {
+        if self.is_eof() {
+            None
+        } else {
+            Some(self.ch)
+        }
+    }
+
+    /* The character after the current one */
+    pub fn peek(&self) -> Option<char> {
+        self.peek_nth(1)
+    }
+
+    /* The character n positions ahead, peek_nth(0) is the current character */
+    pub fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    /* The unconsumed input, starting with the current character */
+    pub fn rest(&self) -> &str {
+        &self.buf[self.pos.min(self.size)..]
+    }
+
+    pub fn slice(&self, from: usize, to: usize) -> &str {
+        &self.buf[from.min(self.size)..to.min(self.size)]
+    }
+
+    /* Checks if the input starting at the current character begins with str */
+    pub fn lookahead(&self, str: &str, ignore_case: bool) -> bool {
+        match self.rest().as_bytes().get(..str.len()) {
+            Some(bytes) if ignore_case => bytes.eq_ignore_ascii_case(str.as_bytes()),
+            Some(bytes) => bytes == str.as_bytes(),
+            None => false,
+        }
+    }
+
+    /* Advances n characters */
+    pub fn skip(&mut self, n: usize) {
+        for _ in 0..n {
+            self.eat();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    #[test]
+    fn non_ascii_input_is_read_by_character() {
+        let mut consumer = Consumer::new("Lägg");
+        assert_eq!((consumer.current(), consumer.peek(), consumer.peek_nth(3)), (Some('L'), Some('ä'), Some('g')));
+        assert_eq!(consumer.eat(), 'ä');
+        assert_eq!(consumer.eat(), 'g');
+        assert_eq!((consumer.pos(), consumer.rest()), (3, "gg"));
+        assert!(consumer.lookahead("GG", true) && !consumer.lookahead("GG", false));
+        consumer.skip(5);
+        assert_eq!((consumer.pos(), consumer.size(), consumer.current()), (5, 5, None));
+        assert_eq!(consumer.slice(1, 3), "ä");
+        let document = DOM::parse_str("<p title=Lägg>Lägg &auml;</p>").unwrap();
+        assert_eq!(document.inner_html(document.root()), "<html><head></head><body><p title=\"Lägg\">Lägg ä</p></body></html>");
+    }
+
+    #[test]
+    fn markup_declaration_at_the_end_of_the_input() {
+        let mut consumer = Consumer::new("<!");
+        consumer.skip(2);
+        assert!(consumer.is_eof());
+        assert!(!consumer.lookahead("--", false));
+        assert_eq!((consumer.peek(), consumer.rest()), (None, ""));
+        for html in ["<!", "a<!", "<!-", "<![CDATA"] {
+            let document = DOM::parse_str(html).unwrap();
+            assert!(!document.errors().is_empty(), "{}", html);
+        }
+    }
+}
@@ -1,45 +1,36 @@
 #![allow(dead_code, unused_variables, unused_assignments, unused_mut)]
 
-use std::{collections::HashMap, rc::Rc};
-use crate::utils::clean_string;
-
-#[derive(Debug)]
-pub struct Consumer {
-    buf: Rc<[u8]>,
-    pos: usize,
-    size: usize,
-    ch: char,
-}
-impl Consumer {
-    pub fn from(str: &str) -> Consumer {
-        Consumer {
-            buf: str.bytes().collect::<Rc<[u8]>>(),
-            pos: 0,
-            size: str.bytes().len(),
-            ch: str.chars().nth(0).unwrap(),
-        }
-    }
+use std::io::Read;
 
-    pub fn eat(&mut self) -> char {
-        if self.pos + 1 < self.size {
-            self.pos += 1;
-            self.ch = self.buf[self.pos] as char;
-        } else {
-            self.pos = self.size;
-        }
-        return self.ch;
-    }
-    pub fn peek(&self) -> char {
-        if self.pos + 1 < self.size {
-            return self.buf[self.pos + 1] as char;
-        } else {
-            return 0 as char;
-        }
-    }
-}
+pub use super::consumer::Consumer;
 
+mod doctype;
+mod document;
+mod encoding;
+mod entities;
+mod error;
+mod formatter;
+mod iter;
+mod selector;
+mod serializer;
+mod span;
+mod tokenizer;
+mod tree_builder;
+mod xpath;
 
+pub use doctype::{Doctype, QuirksMode};
+pub use document::{Document, NodeId};
+pub use encoding::Encoding;
+pub use error::{Error, ErrorCode, HierarchyError, ParseError};
+pub use iter::{Ancestors, Descendants, Edge, NodeDescendants, NodeTraverse, Siblings, Traverse};
+pub use selector::{Selector, SelectorError, SelectorErrorKind, Specificity};
+pub use span::{AttributeSpan, Position, Span};
+pub use xpath::{Value, XPath, XPathError, XPathErrorKind, XPathNode};
+use tokenizer::Tokenizer;
+use tree_builder::TreeBuilder;
 
+/* Named like the Tag variants */
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 enum TokenType {
     Open,
@@ -56,6 +47,21 @@ pub struct Token {
     tag: Tag,
     raw: String,
     tag_type: TokenType,
+    data: String,
+    /* None for tokens the tree builder makes up, like the implied <html> */
+    span: Option<Span>,
+    attributes: Vec<Attribute>,
+    /* Only set on doctype tokens */
+    doctype: Option<Doctype>,
+}
+impl Token {
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn doctype(&self) -> Option<&Doctype> {
+        self.doctype.as_ref()
+    }
 }
 
 
@@ -64,28 +70,134 @@ pub struct Token {
 pub enum Tag {
     Doctype,
     XML,
+    Comment,
+    CDATA,
+    /* Holds the target, the data is the content of the element */
+    ProcessingInstruction(String),
 
     Html,
     Head,
-    Body,
-    Meta,
     Title,
+    Base,
+    Link,
+    Meta,
+    Style,
+    Body,
 
-    Div,
-    Span,
-    P,
+    Article,
+    Section,
+    Nav,
+    Aside,
     H1,
     H2,
     H3,
     H4,
     H5,
     H6,
+    Hgroup,
+    Header,
+    Footer,
+    Address,
 
-    Ul,
+    P,
+    Hr,
+    Pre,
+    Blockquote,
     Ol,
+    Ul,
+    Menu,
     Li,
+    Dl,
+    Dt,
+    Dd,
+    Figure,
+    Figcaption,
+    Main,
+    Search,
+    Div,
 
+    A,
+    Em,
+    Strong,
+    Small,
+    S,
+    Cite,
+    Q,
+    Dfn,
+    Abbr,
+    Ruby,
+    Rt,
+    Rp,
+    Data,
+    Time,
+    Code,
+    Var,
+    Samp,
+    Kbd,
+    Sub,
+    Sup,
+    I,
+    B,
+    U,
+    Mark,
+    Bdi,
+    Bdo,
+    Span,
+    Br,
+    Wbr,
+
+    Ins,
+    Del,
+
+    Picture,
+    Source,
     Img,
+    Iframe,
+    Embed,
+    Object,
+    Video,
+    Audio,
+    Track,
+    Map,
+    Area,
+    Math,
+    Svg,
+
+    Table,
+    Caption,
+    Colgroup,
+    Col,
+    Tbody,
+    Thead,
+    Tfoot,
+    Tr,
+    Td,
+    Th,
+
+    Form,
+    Label,
+    Input,
+    Button,
+    Select,
+    Datalist,
+    Optgroup,
+    Option,
+    Textarea,
+    Output,
+    Progress,
+    Meter,
+    Fieldset,
+    Legend,
+
+    Details,
+    Summary,
+    Dialog,
+
+    Script,
+    Noscript,
+    Template,
+    Slot,
+    Canvas,
 
     PHP,
     
@@ -95,278 +207,921 @@ pub enum Tag {
     Root,
 }
 
-pub type Attributes = HashMap<String, String>;
+impl Tag {
+    /* Metadata below follows https://html.spec.whatwg.org/#elements-2 and #content-models,
+    obsolete elements that are parsed as Tag::Tag are included by name */
+
+    /* Elements that never have children or an end tag */
+    pub fn is_void(&self) -> bool {
+        matches!(self.name(),
+            "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" | "link" | "meta" | "source" | "track" | "wbr"
+            | "basefont" | "bgsound" | "frame" | "keygen" | "param")
+    }
+
+    /* Elements whose content is text that is never parsed as markup, see https://html.spec.whatwg.org/#raw-text-elements.
+    xmp, iframe, noembed and noframes are obsolete but parsed the same way */
+    pub fn is_raw_text(&self) -> bool {
+        matches!(self.name(), "script" | "style" | "xmp" | "iframe" | "noembed" | "noframes")
+    }
+
+    /* Elements whose content is text where character references are still decoded */
+    pub fn is_escapable_raw_text(&self) -> bool {
+        matches!(self.name(), "textarea" | "title")
+    }
+
+    /* Elements whose text is kept as is: raw text and preformatted text */
+    pub fn preserves_whitespace(&self) -> bool {
+        self.is_raw_text() || self.is_escapable_raw_text() || matches!(self.name(), "listing" | "plaintext" | "pre")
+    }
+
+    /* Elements from the MathML and SVG namespaces */
+    pub fn is_foreign(&self) -> bool {
+        matches!(self.name(), "math" | "svg")
+    }
+
+    pub fn is_metadata(&self) -> bool {
+        matches!(self.name(), "base" | "link" | "meta" | "noscript" | "script" | "style" | "template" | "title")
+    }
+
+    pub fn is_heading(&self) -> bool {
+        matches!(self.name(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+    }
+
+    pub fn is_sectioning(&self) -> bool {
+        matches!(self.name(), "article" | "aside" | "nav" | "section")
+    }
+
+    /* Elements rendered as blocks by the default user agent stylesheet */
+    pub fn is_block(&self) -> bool {
+        matches!(self.name(),
+            "html" | "body" | "address" | "article" | "aside" | "blockquote" | "center" | "details" | "dialog" | "dir"
+            | "div" | "dl" | "dd" | "dt" | "fieldset" | "figcaption" | "figure" | "footer" | "form" | "frameset"
+            | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "header" | "hgroup" | "hr" | "legend" | "li" | "listing"
+            | "main" | "menu" | "nav" | "ol" | "optgroup" | "option" | "p" | "plaintext" | "pre" | "search"
+            | "section" | "summary" | "ul" | "xmp" | "table" | "caption" | "colgroup" | "col" | "thead" | "tbody"
+            | "tfoot" | "tr" | "td" | "th")
+    }
+
+    /* Elements rendered inline, unknown and custom elements are inline as well */
+    pub fn is_inline(&self) -> bool {
+        self.is_element() && !self.is_block() && !self.is_metadata() && !matches!(self.name(), "head")
+    }
+
+    /* Elements that are phrasing content, i.e. allowed inside a paragraph */
+    pub fn is_phrasing(&self) -> bool {
+        matches!(self.name(),
+            "a" | "abbr" | "area" | "audio" | "b" | "bdi" | "bdo" | "br" | "button" | "canvas" | "cite" | "code"
+            | "data" | "datalist" | "del" | "dfn" | "em" | "embed" | "i" | "iframe" | "img" | "input" | "ins"
+            | "kbd" | "label" | "link" | "map" | "mark" | "math" | "meta" | "meter" | "noscript" | "object"
+            | "output" | "picture" | "progress" | "q" | "ruby" | "s" | "samp" | "script" | "select" | "slot"
+            | "small" | "span" | "strong" | "sub" | "sup" | "svg" | "template" | "textarea" | "time" | "u"
+            | "var" | "video" | "wbr")
+            || self.is_custom_element()
+    }
+
+    /* Elements in the list of active formatting elements of the tree builder */
+    pub fn is_formatting(&self) -> bool {
+        matches!(self.name(),
+            "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s" | "small" | "strike" | "strong" | "tt" | "u")
+    }
+
+    /* Elements in the "special" category of the tree builder */
+    pub fn is_special(&self) -> bool {
+        matches!(self.name(),
+            "address" | "applet" | "area" | "article" | "aside" | "base" | "basefont" | "bgsound" | "blockquote"
+            | "body" | "br" | "button" | "caption" | "center" | "col" | "colgroup" | "dd" | "details" | "dir" | "div"
+            | "dl" | "dt" | "embed" | "fieldset" | "figcaption" | "figure" | "footer" | "form" | "frame" | "frameset"
+            | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "head" | "header" | "hgroup" | "hr" | "html" | "iframe"
+            | "img" | "input" | "keygen" | "li" | "link" | "listing" | "main" | "marquee" | "menu" | "meta" | "nav"
+            | "noembed" | "noframes" | "noscript" | "object" | "ol" | "p" | "param" | "plaintext" | "pre" | "script"
+            | "search" | "section" | "select" | "source" | "style" | "summary" | "table" | "tbody" | "td"
+            | "template" | "textarea" | "tfoot" | "th" | "thead" | "title" | "tr" | "track" | "ul" | "wbr" | "xmp")
+    }
+
+    /* False for the pseudo tags (doctype, declarations, comments, text and the root) */
+    pub fn is_element(&self) -> bool {
+        !matches!(self,
+            Tag::Doctype | Tag::XML | Tag::Comment | Tag::CDATA | Tag::ProcessingInstruction(_) | Tag::PHP | Tag::None
+            | Tag::Unknown | Tag::Root)
+    }
+
+    /* Custom elements (web components) are kept as Tag::Tag with a valid custom element name */
+    pub fn is_custom_element(&self) -> bool {
+        match self {
+            Tag::Tag(name) => DOM::is_valid_custom_element_name(name),
+            _ => false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Tag::Doctype => "!DOCTYPE",
+            Tag::XML => "?xml",
+            Tag::Comment => "!--",
+            Tag::CDATA => "![CDATA[",
+            Tag::ProcessingInstruction(target) => target,
+            Tag::Html => "html",
+            Tag::Head => "head",
+            Tag::Title => "title",
+            Tag::Base => "base",
+            Tag::Link => "link",
+            Tag::Meta => "meta",
+            Tag::Style => "style",
+            Tag::Body => "body",
+            Tag::Article => "article",
+            Tag::Section => "section",
+            Tag::Nav => "nav",
+            Tag::Aside => "aside",
+            Tag::H1 => "h1",
+            Tag::H2 => "h2",
+            Tag::H3 => "h3",
+            Tag::H4 => "h4",
+            Tag::H5 => "h5",
+            Tag::H6 => "h6",
+            Tag::Hgroup => "hgroup",
+            Tag::Header => "header",
+            Tag::Footer => "footer",
+            Tag::Address => "address",
+            Tag::P => "p",
+            Tag::Hr => "hr",
+            Tag::Pre => "pre",
+            Tag::Blockquote => "blockquote",
+            Tag::Ol => "ol",
+            Tag::Ul => "ul",
+            Tag::Menu => "menu",
+            Tag::Li => "li",
+            Tag::Dl => "dl",
+            Tag::Dt => "dt",
+            Tag::Dd => "dd",
+            Tag::Figure => "figure",
+            Tag::Figcaption => "figcaption",
+            Tag::Main => "main",
+            Tag::Search => "search",
+            Tag::Div => "div",
+            Tag::A => "a",
+            Tag::Em => "em",
+            Tag::Strong => "strong",
+            Tag::Small => "small",
+            Tag::S => "s",
+            Tag::Cite => "cite",
+            Tag::Q => "q",
+            Tag::Dfn => "dfn",
+            Tag::Abbr => "abbr",
+            Tag::Ruby => "ruby",
+            Tag::Rt => "rt",
+            Tag::Rp => "rp",
+            Tag::Data => "data",
+            Tag::Time => "time",
+            Tag::Code => "code",
+            Tag::Var => "var",
+            Tag::Samp => "samp",
+            Tag::Kbd => "kbd",
+            Tag::Sub => "sub",
+            Tag::Sup => "sup",
+            Tag::I => "i",
+            Tag::B => "b",
+            Tag::U => "u",
+            Tag::Mark => "mark",
+            Tag::Bdi => "bdi",
+            Tag::Bdo => "bdo",
+            Tag::Span => "span",
+            Tag::Br => "br",
+            Tag::Wbr => "wbr",
+            Tag::Ins => "ins",
+            Tag::Del => "del",
+            Tag::Picture => "picture",
+            Tag::Source => "source",
+            Tag::Img => "img",
+            Tag::Iframe => "iframe",
+            Tag::Embed => "embed",
+            Tag::Object => "object",
+            Tag::Video => "video",
+            Tag::Audio => "audio",
+            Tag::Track => "track",
+            Tag::Map => "map",
+            Tag::Area => "area",
+            Tag::Math => "math",
+            Tag::Svg => "svg",
+            Tag::Table => "table",
+            Tag::Caption => "caption",
+            Tag::Colgroup => "colgroup",
+            Tag::Col => "col",
+            Tag::Tbody => "tbody",
+            Tag::Thead => "thead",
+            Tag::Tfoot => "tfoot",
+            Tag::Tr => "tr",
+            Tag::Td => "td",
+            Tag::Th => "th",
+            Tag::Form => "form",
+            Tag::Label => "label",
+            Tag::Input => "input",
+            Tag::Button => "button",
+            Tag::Select => "select",
+            Tag::Datalist => "datalist",
+            Tag::Optgroup => "optgroup",
+            Tag::Option => "option",
+            Tag::Textarea => "textarea",
+            Tag::Output => "output",
+            Tag::Progress => "progress",
+            Tag::Meter => "meter",
+            Tag::Fieldset => "fieldset",
+            Tag::Legend => "legend",
+            Tag::Details => "details",
+            Tag::Summary => "summary",
+            Tag::Dialog => "dialog",
+            Tag::Script => "script",
+            Tag::Noscript => "noscript",
+            Tag::Template => "template",
+            Tag::Slot => "slot",
+            Tag::Canvas => "canvas",
+            Tag::PHP => "?php",
+            Tag::Tag(name) => name,
+            Tag::None | Tag::Unknown | Tag::Root => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+    /* None for attributes that were not parsed from source */
+    pub span: Option<AttributeSpan>,
+}
+
+/* Attributes in source order, names are unique and lowercase when parsed from HTML */
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    list: Vec<Attribute>,
+}
+impl Attributes {
+    pub fn new() -> Attributes {
+        Attributes { list: Vec::new() }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attribute(name).map(|attribute| attribute.value.as_str())
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.list.iter().find(|attribute| attribute.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.attribute(name).is_some()
+    }
+
+    /* Replaces the value of an existing attribute in place, otherwise appends it. Returns the old value */
+    pub fn insert(&mut self, name: &str, value: &str) -> Option<String> {
+        match self.list.iter_mut().find(|attribute| attribute.name.eq_ignore_ascii_case(name)) {
+            Some(attribute) => Some(std::mem::replace(&mut attribute.value, value.to_string())),
+            None => {
+                self.list.push(Attribute { name: name.to_string(), value: value.to_string(), span: None });
+                None
+            }
+        }
+    }
+
+    /* Appends the attribute unless one with the same name exists, the first one wins like in the tokenizer */
+    pub fn push(&mut self, attribute: Attribute) -> bool {
+        if self.contains(&attribute.name) {
+            return false;
+        }
+        self.list.push(attribute);
+        return true;
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let pos = self.list.iter().position(|attribute| attribute.name.eq_ignore_ascii_case(name))?;
+        return Some(self.list.remove(pos).value);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Attribute> {
+        self.list.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+/* Two attribute lists are equal when they have the same names and values, regardless of order and spans */
+impl PartialEq for Attributes {
+    fn eq(&self, other: &Attributes) -> bool {
+        self.len() == other.len() && self.iter().all(|attribute| other.get(&attribute.name) == Some(attribute.value.as_str()))
+    }
+}
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = &'a Attribute;
+    type IntoIter = std::slice::Iter<'a, Attribute>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.iter()
+    }
+}
+
+/* A node of the tree, the children of an element keep text and other nodes in source order */
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+    /* Only parsed as text inside SVG and MathML, elsewhere kept as written */
+    CData(String),
+    ProcessingInstruction(ProcessingInstruction),
+    Doctype(Doctype),
+    /* What is between "<?" and "?>" of the <?xml ...?> declaration and of PHP blocks */
+    XML(String),
+    PHP(String),
+}
+impl Node {
+    pub fn as_element(&self) -> Option<&Element> {
+        match self {
+            Node::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Node::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn is_element(&self) -> bool {
+        matches!(self, Node::Element(_))
+    }
+
+    /* The text of this node and all its descendants, like textContent in the DOM */
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        return text;
+    }
+
+    fn push_text(&self, text: &mut String) {
+        match self {
+            Node::Element(element) => {
+                for child in &element.children {
+                    child.push_text(text);
+                }
+            }
+            Node::Text(data) | Node::CData(data) => text.push_str(data),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProcessingInstruction {
+    pub target: String,
+    pub data: String,
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Element {
     tag: Tag,
-    content: Option<String>,
     attributes: Option<Attributes>,
-    children: Option<Vec<Element>>,
+    children: Vec<Node>,
+    /* From the start of the start tag to the end of the end tag, None for elements implied by the parser */
+    span: Option<Span>,
 }
 impl Element {
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Element {
         Element {
             tag: Tag::Unknown,
-            content: None,
             attributes: None,
-            children: None
+            children: Vec::new(),
+            span: None,
         }
     }
 
-    pub fn new(tag: Tag, content: Option<String>, attributes: Option<Attributes>, children: Option<Vec<Element>>) -> Element {
-        Element {
-            tag: tag,
-            content: content,
-            attributes: attributes,
-            children: children,
+    pub fn new(tag: Tag, attributes: Option<Attributes>, children: Vec<Node>) -> Element {
+        Element { tag, attributes, children, span: None }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn tag(&self) -> &Tag {
+        &self.tag
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    /* The children that are elements, skipping text, comments and the like */
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(Node::as_element)
+    }
+
+    /* The text of all descendants, like textContent in the DOM */
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for child in &self.children {
+            child.push_text(&mut text);
         }
+        return text;
+    }
+
+    pub fn attributes(&self) -> Option<&Attributes> {
+        self.attributes.as_ref()
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.as_ref()?.get(name)
+    }
+
+    pub fn attribute_span(&self, name: &str) -> Option<AttributeSpan> {
+        self.attributes.as_ref()?.attribute(name)?.span
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /* Decode character references like "&amp;" in text and attribute values, disable to keep the raw form */
+    pub decode_entities: bool,
+    /* Fail on the first parse error instead of recovering like a browser */
+    pub strict: bool,
+    /* Charset given by the transport layer for byte input, e.g. the Content-Type header */
+    pub transport_encoding: Option<String>,
+    /* How whitespace in text is kept, text inside <pre>, <textarea> and the like is always preserved */
+    pub whitespace: Whitespace,
+    /* Keep text that is only whitespace, like the indentation between tags */
+    pub keep_whitespace_only_text: bool,
+}
+impl ParseOptions {
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> ParseOptions {
+        ParseOptions {
+            decode_entities: true,
+            strict: false,
+            transport_encoding: None,
+            whitespace: Whitespace::Preserve,
+            keep_whitespace_only_text: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whitespace {
+    /* Text is kept as written */
+    Preserve,
+    /* Every run of whitespace becomes a single space */
+    Collapse,
+    /* Like Collapse, and whitespace that would not render is removed: at the start and end of blocks and after another space */
+    Trim,
+}
+
+#[derive(Debug, Clone)]
+pub struct SerializeOptions {
+    pub quote: Quote,
+    /* The text still has its character references, so "&" is not escaped. Documents parsed with decode_entities
+    disabled set this themselves, elements serialized on their own need it passed in */
+    pub raw_entities: bool,
+}
+impl SerializeOptions {
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> SerializeOptions {
+        SerializeOptions { quote: Quote::Double, raw_entities: false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /* Spaces per level, also how wide a tab counts when measuring lines */
+    pub indent_width: usize,
+    pub use_tabs: bool,
+    /* Text is wrapped at its whitespace and longer start tags get one attribute per line, words are never split */
+    pub max_width: usize,
+}
+impl FormatOptions {
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> FormatOptions {
+        FormatOptions { indent_width: 2, use_tabs: false, max_width: 80 }
+    }
+}
+
+/* How attribute values are quoted when serializing */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    Double,
+    Single,
+    /* Unquoted when the value allows it, otherwise double quoted. Empty values are left out, e.g. <input disabled> */
+    Minimal,
+}
+
+/* ASCII whitespace, see https://infra.spec.whatwg.org/#ascii-whitespace */
+fn is_whitespace(ch: char) -> bool {
+    ch.is_ascii_whitespace()
+}
+
 pub struct DOM {}
 impl DOM {
-    pub fn tokenize(mut consumer: Consumer) -> Vec<Token> {
-        let mut tokens: Vec<Token> = Vec::<Token>::new();
-        let mut token: Token = Token { tag: Tag::None, raw: "".to_string(), tag_type: TokenType::None };
-        let mut token_type: TokenType = TokenType::None;
-        let mut ident: String = String::new();
-        while consumer.pos < consumer.size {
-            match consumer.ch {
-                '<' => {
-                    if ident.len() > 0 {
-                        tokens.push(Token {tag: Tag::None, raw: ident, tag_type: TokenType::Content});
-                        ident = String::new();
-                    }
-                    if consumer.peek() == '/' {
-                        //closing tag
-                        token_type = TokenType::Close;
-                        ident.push(consumer.ch);
-                    } else if consumer.peek() == '!' {
-                        if consumer.buf[consumer.pos + 2] == b'D' {
-                            //Doctype
-                            token_type = TokenType::SelfClosing;
-                        } else {
-                            // comment
-                            token_type = TokenType::Comment;
-                        }
-                        ident.push(consumer.ch);
-                    } else if consumer.peek() == '?' {
-                        //xml declatation
-                        /* TODO: Add functionality to parse PHP */
-                        if consumer.buf[consumer.pos + 2] != b'p'
-                            && consumer.buf[consumer.pos + 2] != b'='
-                        {
-                            token_type = TokenType::SelfClosing
-                        } else {
-                            token_type = TokenType::PHP
-                        }
-                        ident.push(consumer.ch);
-                    } else {
-                        // opening tag
-                        token_type = TokenType::Open;
-                        ident.push(consumer.ch);
-                    }
-                }
-                '/' => {
-                    if consumer.peek() == '>' {
-                        //self closing
-                        token_type = TokenType::SelfClosing;
-                        ident.push(consumer.ch);
-                    } else {
-                        ident.push(consumer.ch);
-                    }
-                }
-                '>' => {
-                    ident.push('>');
-                
-                    let copy_ident = ident.clone();
-                    let token: Token = match token_type {
-                        TokenType::SelfClosing => Token {tag: Self::parse_tag(&copy_ident), raw: copy_ident, tag_type: TokenType::SelfClosing},
-                        TokenType::Open => Token {tag: Self::parse_tag(&copy_ident), raw: copy_ident, tag_type: TokenType::Open},
-                        TokenType::Close => Token {tag: Self::parse_tag(&copy_ident), raw: copy_ident, tag_type: TokenType::Close},
-                        TokenType::Comment => Token {tag: Self::parse_tag(&copy_ident), raw: copy_ident, tag_type: TokenType::Comment},
-                        TokenType::None => Token {tag: Tag::None, raw: copy_ident, tag_type: TokenType::None},
-                        TokenType::PHP => Token {tag: Self::parse_tag(&copy_ident), raw: copy_ident, tag_type: TokenType::PHP},
-                        TokenType::Content => Token {tag: Tag::None, raw: copy_ident, tag_type: TokenType::Content }
-                    };
-                    tokens.push(token);
-                    ident = String::new();
-                }
-                _ => ident.push(consumer.ch),
-            }
-            consumer.eat();
-        }
-        return tokens;
-    }
-
-    pub fn parse(consumer: Consumer) -> Element {
-        let mut root_element: Element = Element::default();
-        let mut tokens = Self::tokenize(consumer);
-        let (elements, _) = Self::parse_elements(&mut tokens);
-        root_element.children = elements;
-        root_element.tag = Tag::Root;
-        return root_element;
-    }
-
-    fn convert_tag(str: &str) -> Vec<String> {
-        /* Will convert a string like "<html lang="en">" to ["html", "lang=\"en\""] */
-        let mut str = str.strip_prefix(| p | p == '<').unwrap_or(str).strip_suffix(|p| p == '>').unwrap_or(str).to_string();
-        let mut attrs: Vec<String> = Vec::new();
-        let mut attribute = String::new();
-        let mut in_quote: bool = false;
-
-        while str.len() > 0 {
-            match str.chars().nth(0).unwrap() {
-                '\"' => in_quote = !in_quote,
-                ' ' => {
-                    if in_quote {
-                        attribute.push(' ');
-                    } else {
-                        attrs.push(attribute);
-                        attribute = String::new();
-                    }
-                }
-                '/' => {
-                    if in_quote {
-                        attribute.push('/');
-                    }
-                }
-                ch => attribute.push(ch),
-            }
-            str = String::from_utf8(str.bytes().collect::<Vec<u8>>()[1..].to_vec()).unwrap();
-        }
-        if !attribute.is_empty() {
-            attrs.push(attribute);
-        }
-        return attrs
-    }
-
-    fn parse_attributes(str: &str) -> Option<Attributes> {
-        let attrs = Self::convert_tag(str)[1..].to_vec();
-        if attrs.len() < 1 {
-            return None;
-        } else {
-            let mut attr: Attributes = Attributes::new();
-            for mut str in attrs.clone() {
-                let mut key = String::new();
-                let mut val = String::new();
-                let mut is_key: bool = true;
-                let attrs = str.split("=").collect::<Vec<&str>>();
-                key = attrs[0].to_string();
-                if attrs.len() > 1 {
-                    val = clean_string(attrs[1]);
-                }
-                if key != "/" {
-                    attr.insert(key, val);
-                }
-                key = String::new();
-                val = String::new();
+    pub fn tokenize(consumer: Consumer) -> Vec<Token> {
+        Self::tokenize_with_options(consumer, &ParseOptions::default())
+    }
+
+    pub fn tokenize_with_options(consumer: Consumer, options: &ParseOptions) -> Vec<Token> {
+        Tokenizer::new(consumer, options).run().0
+    }
+
+    pub fn parse(consumer: Consumer) -> Result<Document, Error> {
+        Self::parse_with_options(consumer, &ParseOptions::default())
+    }
+
+    /* Parsing only fails in strict mode, otherwise errors are recovered from and kept on the document */
+    pub fn parse_with_options(consumer: Consumer, options: &ParseOptions) -> Result<Document, Error> {
+        Self::build(consumer, options, Encoding::Utf8)
+    }
+
+    pub fn parse_str(str: &str) -> Result<Document, Error> {
+        Self::parse_str_with_options(str, &ParseOptions::default())
+    }
+
+    pub fn parse_str_with_options(str: &str, options: &ParseOptions) -> Result<Document, Error> {
+        Self::build(Consumer::from(str), options, Encoding::Utf8)
+    }
+
+    /* Parses raw bytes of unknown encoding, see ParseOptions::transport_encoding */
+    pub fn parse_bytes(bytes: &[u8]) -> Result<Document, Error> {
+        Self::parse_bytes_with_options(bytes, &ParseOptions::default())
+    }
+
+    pub fn parse_bytes_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Document, Error> {
+        let (text, encoding) = Self::decode_bytes(bytes, options.transport_encoding.as_deref());
+        return Self::build(Consumer::from(&text), options, encoding);
+    }
+
+    pub fn parse_reader(reader: impl Read) -> Result<Document, Error> {
+        Self::parse_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    pub fn parse_reader_with_options(mut reader: impl Read, options: &ParseOptions) -> Result<Document, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        return Self::parse_bytes_with_options(&bytes, options);
+    }
+
+    /* Parses markup as the content of a context element like innerHTML does, the nodes end up under the root */
+    pub fn parse_fragment(str: &str, context: &str) -> Result<Document, Error> {
+        Self::parse_fragment_with_options(str, context, &ParseOptions::default())
+    }
+
+    pub fn parse_fragment_with_options(str: &str, context: &str, options: &ParseOptions) -> Result<Document, Error> {
+        let document = Self::fragment(str, context, options);
+        return Self::check(document, options);
+    }
+
+    /* The whole document as markup, see https://html.spec.whatwg.org/#serialising-html-fragments */
+    pub fn serialize(document: &Document) -> String {
+        Self::serialize_with_options(document, &SerializeOptions::default())
+    }
+
+    pub fn serialize_with_options(document: &Document, options: &SerializeOptions) -> String {
+        document.inner_html_with_options(document.root(), options)
+    }
+
+    /* Serializes with indentation, only whitespace that does not change rendering is added or removed */
+    pub fn format(document: &Document) -> String {
+        Self::format_with_options(document, &FormatOptions::default())
+    }
+
+    pub fn format_with_options(document: &Document, options: &FormatOptions) -> String {
+        formatter::format(document, options)
+    }
+
+    fn build(consumer: Consumer, options: &ParseOptions, encoding: Encoding) -> Result<Document, Error> {
+        let mut tokenizer = Tokenizer::new(consumer, options);
+        let mut document = TreeBuilder::new(options).build(&mut tokenizer);
+        document.encoding = encoding;
+        return Self::check(Self::merge_errors(document, tokenizer.errors()), options);
+    }
+
+    /* Never fails, the strict option is up to the caller */
+    fn fragment(str: &str, context: &str, options: &ParseOptions) -> Document {
+        let context = context.to_ascii_lowercase();
+        let mut tokenizer = Tokenizer::new_fragment(Consumer::from(str), options, &context);
+        let document = TreeBuilder::new_fragment(options, Self::parse_tag(&context)).build(&mut tokenizer);
+        return Self::merge_errors(document, tokenizer.errors());
+    }
+
+    fn merge_errors(mut document: Document, mut errors: Vec<ParseError>) -> Document {
+        errors.append(&mut document.errors);
+        errors.sort_by_key(|error| error.position.offset);
+        document.errors = errors;
+        return document;
+    }
+
+    fn check(document: Document, options: &ParseOptions) -> Result<Document, Error> {
+        if options.strict {
+            if let Some(error) = document.errors.first() {
+                return Err(Error::Parse(*error));
             }
-            return Some(attr);
         }
+        return Ok(document);
+    }
+
+    pub fn decode_bytes(bytes: &[u8], transport_encoding: Option<&str>) -> (String, Encoding) {
+        encoding::decode(bytes, transport_encoding)
     }
 
     fn parse_tag(str: &str) -> Tag {
-        let tag: &String = &Self::convert_tag(&str)[0];
+        /* Tag names are case-insensitive in HTML */
+        let tag: String = str.to_ascii_lowercase();
         match tag.as_str() {
-            "!DOCTYPE" => Tag::Doctype,
+            "!doctype" => Tag::Doctype,
             "?xml" => Tag::XML,
             "html" => Tag::Html,
             "head" => Tag::Head,
             "title" => Tag::Title,
+            "base" => Tag::Base,
+            "link" => Tag::Link,
             "meta" => Tag::Meta,
+            "style" => Tag::Style,
             "body" => Tag::Body,
-            "div" => Tag::Div,
-            "span" => Tag::Span,
-            "img" => Tag::Img,
+            "article" => Tag::Article,
+            "section" => Tag::Section,
+            "nav" => Tag::Nav,
+            "aside" => Tag::Aside,
             "h1" => Tag::H1,
             "h2" => Tag::H2,
             "h3" => Tag::H3,
             "h4" => Tag::H4,
             "h5" => Tag::H5,
             "h6" => Tag::H6,
+            "hgroup" => Tag::Hgroup,
+            "header" => Tag::Header,
+            "footer" => Tag::Footer,
+            "address" => Tag::Address,
             "p" => Tag::P,
-            "li" => Tag::Li,
+            "hr" => Tag::Hr,
+            "pre" => Tag::Pre,
+            "blockquote" => Tag::Blockquote,
             "ol" => Tag::Ol,
             "ul" => Tag::Ul,
-                /* TODO: Add more! */
+            "menu" => Tag::Menu,
+            "li" => Tag::Li,
+            "dl" => Tag::Dl,
+            "dt" => Tag::Dt,
+            "dd" => Tag::Dd,
+            "figure" => Tag::Figure,
+            "figcaption" => Tag::Figcaption,
+            "main" => Tag::Main,
+            "search" => Tag::Search,
+            "div" => Tag::Div,
+            "a" => Tag::A,
+            "em" => Tag::Em,
+            "strong" => Tag::Strong,
+            "small" => Tag::Small,
+            "s" => Tag::S,
+            "cite" => Tag::Cite,
+            "q" => Tag::Q,
+            "dfn" => Tag::Dfn,
+            "abbr" => Tag::Abbr,
+            "ruby" => Tag::Ruby,
+            "rt" => Tag::Rt,
+            "rp" => Tag::Rp,
+            "data" => Tag::Data,
+            "time" => Tag::Time,
+            "code" => Tag::Code,
+            "var" => Tag::Var,
+            "samp" => Tag::Samp,
+            "kbd" => Tag::Kbd,
+            "sub" => Tag::Sub,
+            "sup" => Tag::Sup,
+            "i" => Tag::I,
+            "b" => Tag::B,
+            "u" => Tag::U,
+            "mark" => Tag::Mark,
+            "bdi" => Tag::Bdi,
+            "bdo" => Tag::Bdo,
+            "span" => Tag::Span,
+            "br" => Tag::Br,
+            "wbr" => Tag::Wbr,
+            "ins" => Tag::Ins,
+            "del" => Tag::Del,
+            "picture" => Tag::Picture,
+            "source" => Tag::Source,
+            "img" => Tag::Img,
+            "iframe" => Tag::Iframe,
+            "embed" => Tag::Embed,
+            "object" => Tag::Object,
+            "video" => Tag::Video,
+            "audio" => Tag::Audio,
+            "track" => Tag::Track,
+            "map" => Tag::Map,
+            "area" => Tag::Area,
+            "math" => Tag::Math,
+            "svg" => Tag::Svg,
+            "table" => Tag::Table,
+            "caption" => Tag::Caption,
+            "colgroup" => Tag::Colgroup,
+            "col" => Tag::Col,
+            "tbody" => Tag::Tbody,
+            "thead" => Tag::Thead,
+            "tfoot" => Tag::Tfoot,
+            "tr" => Tag::Tr,
+            "td" => Tag::Td,
+            "th" => Tag::Th,
+            "form" => Tag::Form,
+            "label" => Tag::Label,
+            "input" => Tag::Input,
+            "button" => Tag::Button,
+            "select" => Tag::Select,
+            "datalist" => Tag::Datalist,
+            "optgroup" => Tag::Optgroup,
+            "option" => Tag::Option,
+            "textarea" => Tag::Textarea,
+            "output" => Tag::Output,
+            "progress" => Tag::Progress,
+            "meter" => Tag::Meter,
+            "fieldset" => Tag::Fieldset,
+            "legend" => Tag::Legend,
+            "details" => Tag::Details,
+            "summary" => Tag::Summary,
+            "dialog" => Tag::Dialog,
+            "script" => Tag::Script,
+            "noscript" => Tag::Noscript,
+            "template" => Tag::Template,
+            "slot" => Tag::Slot,
+            "canvas" => Tag::Canvas,
 
             "?php" => Tag::PHP,
 
-            str => todo!("Lägg till fler taggar: {}", str)
+            _ => Tag::Tag(tag),
         }
     }
 
+    /* Names the tokenizer reads as a tag name, see https://html.spec.whatwg.org/#tag-name-state */
+    pub fn is_valid_tag_name(name: &str) -> bool {
+        name.starts_with(|ch: char| ch.is_ascii_alphabetic())
+            && !name.contains(['\t', '\n', '\x0C', '\r', ' ', '/', '>', '\0'])
+    }
+
+    /* See https://html.spec.whatwg.org/#attributes-2 */
+    pub fn is_valid_attribute_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.contains(|ch: char| {
+                ch.is_control() || matches!(ch, ' ' | '"' | '\'' | '>' | '/' | '=') || matches!(ch as u32, 0xFDD0..=0xFDEF) || ch as u32 & 0xFFFE == 0xFFFE
+            })
+    }
+
+    /* See https://html.spec.whatwg.org/#valid-custom-element-name */
+    pub fn is_valid_custom_element_name(name: &str) -> bool {
+        const RESERVED: [&str; 8] = [
+            "annotation-xml",
+            "color-profile",
+            "font-face",
+            "font-face-src",
+            "font-face-uri",
+            "font-face-format",
+            "font-face-name",
+            "missing-glyph",
+        ];
+        let is_pcen_char = |ch: char| {
+            matches!(ch,
+                '-' | '.' | '0'..='9' | '_' | 'a'..='z' | '\u{B7}'
+                | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}'
+                | '\u{200C}'..='\u{200D}' | '\u{203F}'..='\u{2040}' | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}'
+                | '\u{3001}'..='\u{D7FF}' | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+        };
+        return name.starts_with(|ch: char| ch.is_ascii_lowercase())
+            && name.contains('-')
+            && name.chars().all(is_pcen_char)
+            && !RESERVED.contains(&name);
+    }
+
     fn parse_php_tag(str: &str) -> Element {
 
         return Element::default();
     }
+}
 
-    fn parse_elements(mut tokens: &mut Vec<Token>) -> (Option<Vec<Element>>, Option<String>) {
-        let mut elements: Vec<Element> = Vec::<Element>::new();
-        let mut element: Element = Element::default();
-        let mut content: String = String::new();
-
-        while tokens.len() > 0 {
-            let token = tokens[0].clone();
-            println!("Tokens left {}: {:?}", tokens.len(), tokens);
-            match token.tag_type {
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                TokenType::Open => {
-                    *tokens = tokens[1..].to_vec();
-                    element.tag = token.tag;
-                    element.attributes = Self::parse_attributes(&token.raw);
-                    let mut content = String::new();
-                    let mut inner_children: Option<Vec<Element>> = None;
-                    while tokens.len() > 0 {
-                        let (inner_children, inner_content) = Self::parse_elements(tokens);
-                        if tokens.len() > 0 {
-                            *tokens = tokens[1..].to_vec();
-                        }
-                        content.push_str(&inner_content.unwrap())
-                    }
-                    element.content = Some(content);
-                    element.children = inner_children;
-                    elements.push(element);
-                    element = Element::default();
+    fn names(predicate: fn(&Tag) -> bool) -> Vec<&'static str> {
+        const NAMES: &[&str] = &[
+            "a", "br", "div", "h1", "iframe", "img", "li", "listing", "my-element", "noembed", "noframes", "p", "plaintext",
+            "pre", "script", "span", "style", "svg", "table", "textarea", "title", "xmp",
+        ];
+        NAMES.iter().copied().filter(|name| predicate(&DOM::parse_tag(name))).collect()
+    }
 
-                },
+    fn html(document: &Document) -> String {
+        document.inner_html(document.root())
+    }
 
-                TokenType::Content => {
-                    content.push_str(&token.raw);
-                },
+    #[test]
+    fn empty_and_whitespace_only_input() {
+        for input in ["", " \n\t"] {
+            let document = DOM::parse_str(input).unwrap();
+            assert_eq!(html(&document), "<html><head></head><body></body></html>");
+            assert_eq!(document.quirks_mode(), QuirksMode::Quirks);
+            assert_eq!(document.errors().iter().map(|error| error.code.code()).collect::<Vec<_>>(), ["missing-doctype"]);
+        }
+        assert!(DOM::parse_str_with_options("", &ParseOptions { strict: true, ..ParseOptions::default() }).is_err());
+        assert_eq!(html(&DOM::parse_fragment("", "div").unwrap()), "");
+    }
 
+    #[test]
+    fn truncated_input() {
+        let cases = [
+            ("<!DOCTYPE html><p class=", "<!DOCTYPE html><html><head></head><body></body></html>", "eof-in-tag"),
+            ("<!DOCTYPE html><div><p>a", "<!DOCTYPE html><html><head></head><body><div><p>a</p></div></body></html>", "eof-in-element"),
+            ("<!DOCTYPE html><!-- a", "<!DOCTYPE html><!-- a--><html><head></head><body></body></html>", "eof-in-comment"),
+            ("<!DOCTYPE html><title>a", "<!DOCTYPE html><html><head><title>a</title></head><body></body></html>", "eof-in-element"),
+            ("<!DOCTYPE", "<!DOCTYPE><html><head></head><body></body></html>", "eof-in-doctype"),
+        ];
+        for (input, expected, code) in cases {
+            let document = DOM::parse_str(input).unwrap();
+            assert_eq!(html(&document), expected, "{}", input);
+            assert!(document.errors().iter().any(|error| error.code.code() == code), "{}", input);
+        }
+    }
 
+    #[test]
+    fn parse_reader_sniffs_the_encoding() {
+        let bytes: &[u8] = b"<meta charset=windows-1252><p>caf\xE9</p>";
+        let document = DOM::parse_reader(bytes).unwrap();
+        assert_eq!(document.encoding(), Encoding::Windows(1252));
+        assert_eq!(html(&document), "<html><head><meta charset=\"windows-1252\"></head><body><p>café</p></body></html>");
+    }
 
+    struct FailingReader;
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disconnected"))
+        }
+    }
 
-                
+    #[test]
+    fn read_errors_are_returned() {
+        match DOM::parse_reader(FailingReader) {
+            Err(Error::Io(error)) => assert_eq!(error.to_string(), "disconnected"),
+            other => panic!("expected an I/O error, got {:?}", other.map(|document| html(&document))),
+        }
+    }
 
-                TokenType::SelfClosing => {
-                    element.content = None;
-                    element.children = None;
-                    element.attributes = Self::parse_attributes(&tokens[0].raw);
-                    element.tag = Self::parse_tag(&tokens[0].raw);
-                    elements.push(element.clone());
-                },
+    #[test]
+    fn raw_text_elements() {
+        assert_eq!(names(Tag::is_raw_text), ["iframe", "noembed", "noframes", "script", "style", "xmp"]);
+        assert_eq!(names(Tag::is_escapable_raw_text), ["textarea", "title"]);
+        assert_eq!(
+            names(Tag::preserves_whitespace),
+            ["iframe", "listing", "noembed", "noframes", "plaintext", "pre", "script", "style", "textarea", "title", "xmp"]
+        );
+    }
 
-                TokenType::PHP => todo!(),
+    #[test]
+    fn content_categories() {
+        assert_eq!(names(Tag::is_void), ["br", "img"]);
+        assert_eq!(names(Tag::is_block), ["div", "h1", "li", "listing", "p", "plaintext", "pre", "table", "xmp"]);
+        assert_eq!(names(Tag::is_inline), ["a", "br", "iframe", "img", "my-element", "noembed", "noframes", "span", "svg", "textarea"]);
+        assert_eq!(names(Tag::is_phrasing), ["a", "br", "iframe", "img", "my-element", "script", "span", "svg", "textarea"]);
+        assert_eq!(names(Tag::is_heading), ["h1"]);
+        assert!(!Tag::Comment.is_element() && !Tag::Root.is_element() && Tag::Tag("x".to_string()).is_element());
+    }
 
-                _ => {},
-            }
+    #[test]
+    fn parser_categories() {
+        assert_eq!(names(Tag::is_formatting), ["a"]);
+        assert_eq!(
+            names(Tag::is_special),
+            [
+                "br", "div", "h1", "iframe", "img", "li", "listing", "noembed", "noframes", "p", "plaintext", "pre", "script",
+                "style", "table", "textarea", "title", "xmp",
+            ]
+        );
+        assert_eq!(names(Tag::is_foreign), ["svg"]);
+    }
 
-            if tokens.len() > 0 {
-                *tokens = tokens[1..].to_vec();
-            }
-            println!("Current Element: {:?}", element);
+    #[test]
+    fn custom_element_names() {
+        for name in ["my-element", "x-", "a-b-c", "math-α", "emotion-😍", "x.y-z_1"] {
+            assert!(DOM::is_valid_custom_element_name(name), "{}", name);
+            assert!(Tag::Tag(name.to_string()).is_custom_element(), "{}", name);
         }
-
-        if elements.len() > 0 {
-            return (Some(elements), Some(content))
-        }else{
-            return (None, Some(content));
+        for name in ["", "myelement", "x", "My-element", "my-Element", "1-element", "-element", "my element", "my-element!"] {
+            assert!(!DOM::is_valid_custom_element_name(name), "{}", name);
+            assert!(!Tag::Tag(name.to_string()).is_custom_element(), "{}", name);
         }
+        for name in [
+            "annotation-xml", "color-profile", "font-face", "font-face-src", "font-face-uri", "font-face-format", "font-face-name",
+            "missing-glyph",
+        ] {
+            assert!(!DOM::is_valid_custom_element_name(name), "{}", name);
+        }
+        assert!(DOM::parse_tag("my-element").is_custom_element());
+        assert!(!DOM::parse_tag("div").is_custom_element() && !Tag::Comment.is_custom_element());
     }
-
-    
 }
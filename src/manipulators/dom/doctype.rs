@@ -0,0 +1,183 @@
+#![allow(dead_code)]
+
+/* The fields of <!DOCTYPE name PUBLIC "public id" "system id">, missing parts are None */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Doctype {
+    pub name: Option<String>,
+    pub public_id: Option<String>,
+    pub system_id: Option<String>,
+    /* Set by the tokenizer for malformed doctypes, which always put the document in quirks mode */
+    pub force_quirks: bool,
+}
+
+/* See https://dom.spec.whatwg.org/#concept-document-mode */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirksMode {
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
+}
+
+/* Public identifiers that start with one of these put the document in quirks mode,
+see https://html.spec.whatwg.org/#the-initial-insertion-mode */
+const QUIRKS_PUBLIC_PREFIXES: &[&str] = &[
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html 2.0 level 1//",
+    "-//ietf//dtd html 2.0 level 2//",
+    "-//ietf//dtd html 2.0 strict level 1//",
+    "-//ietf//dtd html 2.0 strict level 2//",
+    "-//ietf//dtd html 2.0 strict//",
+    "-//ietf//dtd html 2.0//",
+    "-//ietf//dtd html 2.1e//",
+    "-//ietf//dtd html 3.0//",
+    "-//ietf//dtd html 3.2 final//",
+    "-//ietf//dtd html 3.2//",
+    "-//ietf//dtd html 3//",
+    "-//ietf//dtd html level 0//",
+    "-//ietf//dtd html level 1//",
+    "-//ietf//dtd html level 2//",
+    "-//ietf//dtd html level 3//",
+    "-//ietf//dtd html strict level 0//",
+    "-//ietf//dtd html strict level 1//",
+    "-//ietf//dtd html strict level 2//",
+    "-//ietf//dtd html strict level 3//",
+    "-//ietf//dtd html strict//",
+    "-//ietf//dtd html//",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer 2.0 html strict//",
+    "-//microsoft//dtd internet explorer 2.0 html//",
+    "-//microsoft//dtd internet explorer 2.0 tables//",
+    "-//microsoft//dtd internet explorer 3.0 html strict//",
+    "-//microsoft//dtd internet explorer 3.0 html//",
+    "-//microsoft//dtd internet explorer 3.0 tables//",
+    "-//netscape comm. corp.//dtd html//",
+    "-//netscape comm. corp.//dtd strict html//",
+    "-//o'reilly and associates//dtd html 2.0//",
+    "-//o'reilly and associates//dtd html extended 1.0//",
+    "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+    "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sun microsystems corp.//dtd hotjava html//",
+    "-//sun microsystems corp.//dtd hotjava strict html//",
+    "-//w3c//dtd html 3 1995-03-24//",
+    "-//w3c//dtd html 3.2 draft//",
+    "-//w3c//dtd html 3.2 final//",
+    "-//w3c//dtd html 3.2//",
+    "-//w3c//dtd html 3.2s draft//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental 19960712//",
+    "-//w3c//dtd html experimental 970421//",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html 2.0//",
+    "-//webtechs//dtd mozilla html//",
+];
+const QUIRKS_PUBLIC_IDS: &[&str] = &["-//w3o//dtd w3 html strict 3.0//en//", "-/w3c/dtd html 4.0 transitional/en", "html"];
+const QUIRKS_SYSTEM_ID: &str = "http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd";
+/* Quirks without a system identifier, limited quirks with one */
+const HTML4_PUBLIC_PREFIXES: &[&str] = &["-//w3c//dtd html 4.01 frameset//", "-//w3c//dtd html 4.01 transitional//"];
+const LIMITED_QUIRKS_PUBLIC_PREFIXES: &[&str] = &["-//w3c//dtd xhtml 1.0 frameset//", "-//w3c//dtd xhtml 1.0 transitional//"];
+
+impl Doctype {
+    pub fn quirks_mode(&self) -> QuirksMode {
+        /* The identifiers are compared ASCII case-insensitively */
+        let public_id = self.public_id.as_deref().map(|id| id.to_ascii_lowercase());
+        let system_id = self.system_id.as_deref().map(|id| id.to_ascii_lowercase());
+        let public_starts_with = |prefixes: &[&str]| match &public_id {
+            Some(id) => prefixes.iter().any(|prefix| id.starts_with(prefix)),
+            None => false,
+        };
+
+        if self.force_quirks
+            || self.name.as_deref() != Some("html")
+            || public_id.as_deref().is_some_and(|id| QUIRKS_PUBLIC_IDS.contains(&id))
+            || system_id.as_deref() == Some(QUIRKS_SYSTEM_ID)
+            || public_starts_with(QUIRKS_PUBLIC_PREFIXES)
+            || (system_id.is_none() && public_starts_with(HTML4_PUBLIC_PREFIXES))
+        {
+            return QuirksMode::Quirks;
+        }
+        if public_starts_with(LIMITED_QUIRKS_PUBLIC_PREFIXES) || (system_id.is_some() && public_starts_with(HTML4_PUBLIC_PREFIXES)) {
+            return QuirksMode::LimitedQuirks;
+        }
+        return QuirksMode::NoQuirks;
+    }
+
+    /* Only <!DOCTYPE html>, optionally with the legacy "about:legacy-compat" system identifier, is conforming */
+    pub fn is_conforming(&self) -> bool {
+        self.name.as_deref() == Some("html")
+            && self.public_id.is_none()
+            && matches!(self.system_id.as_deref(), None | Some("about:legacy-compat"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    fn doctype(public_id: Option<&str>, system_id: Option<&str>) -> Doctype {
+        Doctype { name: Some("html".to_string()), public_id: public_id.map(String::from), system_id: system_id.map(String::from), force_quirks: false }
+    }
+
+    #[test]
+    fn quirks_tables() {
+        assert_eq!(doctype(None, None).quirks_mode(), QuirksMode::NoQuirks);
+        assert_eq!(doctype(Some("-//W3C//DTD HTML 4.01//EN"), None).quirks_mode(), QuirksMode::NoQuirks);
+        assert_eq!(doctype(Some("-//W3O//DTD W3 HTML Strict 3.0//EN//"), None).quirks_mode(), QuirksMode::Quirks);
+        assert_eq!(doctype(Some("HTML"), None).quirks_mode(), QuirksMode::Quirks);
+        assert_eq!(doctype(Some("-//IETF//DTD HTML 2.0//EN"), None).quirks_mode(), QuirksMode::Quirks);
+        assert_eq!(doctype(None, Some("http://www.ibm.com/data/dtd/v11/IBMXHTML1-transitional.dtd")).quirks_mode(), QuirksMode::Quirks);
+        assert_eq!(doctype(Some("-//W3C//DTD XHTML 1.0 Transitional//EN"), None).quirks_mode(), QuirksMode::LimitedQuirks);
+        assert_eq!(doctype(Some("-//W3C//DTD XHTML 1.0 Frameset//EN"), Some("x")).quirks_mode(), QuirksMode::LimitedQuirks);
+    }
+
+    #[test]
+    fn html4_transitional_depends_on_the_system_identifier() {
+        let public_id = Some("-//W3C//DTD HTML 4.01 Transitional//EN");
+        assert_eq!(doctype(public_id, None).quirks_mode(), QuirksMode::Quirks);
+        assert_eq!(doctype(public_id, Some("http://www.w3.org/TR/html4/loose.dtd")).quirks_mode(), QuirksMode::LimitedQuirks);
+    }
+
+    #[test]
+    fn name_and_force_quirks() {
+        let mut other = doctype(None, None);
+        other.name = Some("svg".to_string());
+        assert_eq!(other.quirks_mode(), QuirksMode::Quirks);
+        let mut forced = doctype(None, None);
+        forced.force_quirks = true;
+        assert_eq!(forced.quirks_mode(), QuirksMode::Quirks);
+    }
+
+    #[test]
+    fn conforming_doctypes() {
+        assert!(doctype(None, None).is_conforming());
+        assert!(doctype(None, Some("about:legacy-compat")).is_conforming());
+        assert!(!doctype(Some("-//W3C//DTD HTML 4.01//EN"), None).is_conforming());
+    }
+
+    #[test]
+    fn mode_of_parsed_documents() {
+        assert_eq!(DOM::parse_str("<!DOCTYPE html><p>").unwrap().quirks_mode(), QuirksMode::NoQuirks);
+        assert_eq!(DOM::parse_str("<p>").unwrap().quirks_mode(), QuirksMode::Quirks);
+        assert_eq!(DOM::parse_str("<!DOCTYPE>").unwrap().quirks_mode(), QuirksMode::Quirks);
+        let document = DOM::parse_str("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"x\">").unwrap();
+        assert_eq!(document.quirks_mode(), QuirksMode::LimitedQuirks);
+    }
+
+    #[test]
+    fn tables_close_paragraphs_unless_in_quirks_mode() {
+        let parent_of_table = |html: &str| {
+            let document = DOM::parse_str(html).unwrap();
+            let table = document.query_selector("table").unwrap().unwrap();
+            return document.element(document.parent(table).unwrap()).unwrap().tag().name().to_string();
+        };
+        assert_eq!(parent_of_table("<!DOCTYPE html><p><table>"), "body");
+        assert_eq!(parent_of_table("<p><table>"), "p");
+    }
+}
@@ -0,0 +1,514 @@
+#![allow(dead_code)]
+
+use super::{Attributes, Doctype, Element, Encoding, HierarchyError, Node, ParseError, ParseOptions, QuirksMode, Tag, DOM};
+
+/* A handle to a node of a Document, only meaningful for the document it came from */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+/* Elements are stored without their children, the tree is kept in the links */
+#[derive(Debug, Clone)]
+struct Entry {
+    data: Node,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+/* The result of parsing, the recovered tree together with every parse error in source order */
+#[derive(Debug, Clone)]
+pub struct Document {
+    nodes: Vec<Entry>,
+    pub(super) errors: Vec<ParseError>,
+    pub(super) encoding: Encoding,
+    pub(super) doctype: Option<Doctype>,
+    pub(super) quirks_mode: QuirksMode,
+    /* Parsed without decoding character references, so an "&" in text and attribute values already starts one */
+    pub(super) raw_entities: bool,
+}
+impl Document {
+    pub fn new() -> Document {
+        let mut document = Document {
+            nodes: Vec::new(),
+            errors: Vec::new(),
+            encoding: Encoding::Utf8,
+            doctype: None,
+            quirks_mode: QuirksMode::NoQuirks,
+            raw_entities: false,
+        };
+        document.add(Node::Element(Element::new(Tag::Root, None, Vec::new())));
+        return document;
+    }
+
+    /* The document node, an element with Tag::Root that holds the doctype and <html> */
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /* Elements are returned without children, use the links or to_node to get at them */
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0].data
+    }
+
+    pub fn element(&self, id: NodeId) -> Option<&Element> {
+        self.node(id).as_element()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].last_child
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].previous_sibling
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].next_sibling
+    }
+
+    /* The <html> element, None only for documents built by hand */
+    pub fn document_element(&self) -> Option<NodeId> {
+        let mut child = self.first_child(self.root());
+        while let Some(id) = child {
+            if self.node(id).is_element() {
+                return Some(id);
+            }
+            child = self.next_sibling(id);
+        }
+        return None;
+    }
+
+    /* Copies the node and everything below it into an owned tree */
+    pub fn to_node(&self, id: NodeId) -> Node {
+        let mut node = self.node(id).clone();
+        if let Node::Element(element) = &mut node {
+            let mut child = self.first_child(id);
+            while let Some(child_id) = child {
+                element.children.push(self.to_node(child_id));
+                child = self.next_sibling(child_id);
+            }
+        }
+        return node;
+    }
+
+    /* The whole tree as an owned element with Tag::Root */
+    pub fn into_root(self) -> Element {
+        match self.to_node(self.root()) {
+            Node::Element(root) => root,
+            _ => unreachable!("the root is always an element"),
+        }
+    }
+
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /* The encoding the input was decoded with, UTF-8 for string input */
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /* None when the document has no doctype, which puts it in quirks mode */
+    pub fn doctype(&self) -> Option<&Doctype> {
+        self.doctype.as_ref()
+    }
+
+    pub fn quirks_mode(&self) -> QuirksMode {
+        self.quirks_mode
+    }
+
+    /* ---------- Mutation ---------- */
+
+    /* The create methods add nodes that are not linked into the tree yet */
+    pub fn create_element(&mut self, name: &str) -> Result<NodeId, HierarchyError> {
+        if !DOM::is_valid_tag_name(name) {
+            return Err(HierarchyError::InvalidName);
+        }
+        return Ok(self.add(Node::Element(Element::new(DOM::parse_tag(name), None, Vec::new()))));
+    }
+
+    pub fn create_text(&mut self, text: &str) -> NodeId {
+        self.add(Node::Text(text.to_string()))
+    }
+
+    /* Adds an owned node together with its descendants */
+    pub fn create_node(&mut self, node: Node) -> NodeId {
+        match node {
+            Node::Element(mut element) => {
+                let children = std::mem::take(&mut element.children);
+                let id = self.add(Node::Element(element));
+                for child in children {
+                    let child = self.create_node(child);
+                    self.append(id, child);
+                }
+                return id;
+            }
+            other => return self.add(other),
+        }
+    }
+
+    /* Copies a node of another document together with its descendants */
+    pub fn import(&mut self, other: &Document, id: NodeId) -> NodeId {
+        self.create_node(other.to_node(id))
+    }
+
+    /* The insert methods move the node if it is already in the tree */
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> Result<(), HierarchyError> {
+        self.check_insert(parent, child)?;
+        self.detach(child);
+        self.append(parent, child);
+        return Ok(());
+    }
+
+    pub fn prepend(&mut self, parent: NodeId, child: NodeId) -> Result<(), HierarchyError> {
+        self.check_insert(parent, child)?;
+        self.detach(child);
+        match self.first_child(parent) {
+            Some(first) => self.link_before(first, child),
+            None => self.append(parent, child),
+        }
+        return Ok(());
+    }
+
+    pub fn insert_before(&mut self, reference: NodeId, node: NodeId) -> Result<(), HierarchyError> {
+        let parent = self.parent(reference).ok_or(HierarchyError::NoParent)?;
+        self.check_insert(parent, node)?;
+        if node != reference {
+            self.detach(node);
+            self.link_before(reference, node);
+        }
+        return Ok(());
+    }
+
+    pub fn insert_after(&mut self, reference: NodeId, node: NodeId) -> Result<(), HierarchyError> {
+        let parent = self.parent(reference).ok_or(HierarchyError::NoParent)?;
+        self.check_insert(parent, node)?;
+        if node != reference {
+            self.detach(node);
+            match self.next_sibling(reference) {
+                Some(next) => self.link_before(next, node),
+                None => self.append(parent, node),
+            }
+        }
+        return Ok(());
+    }
+
+    /* Detaches the node, it keeps its descendants and can be inserted again */
+    pub fn remove(&mut self, id: NodeId) -> Result<(), HierarchyError> {
+        if id == self.root() {
+            return Err(HierarchyError::Root);
+        }
+        self.detach(id);
+        return Ok(());
+    }
+
+    pub fn replace_with(&mut self, id: NodeId, replacement: NodeId) -> Result<(), HierarchyError> {
+        if id != replacement {
+            self.insert_before(id, replacement)?;
+            self.detach(id);
+        }
+        return Ok(());
+    }
+
+    /* Puts the wrapper element where the node is and moves the node into it, after any children it has */
+    pub fn wrap(&mut self, id: NodeId, wrapper: NodeId) -> Result<(), HierarchyError> {
+        if self.element(wrapper).is_none() {
+            return Err(HierarchyError::NotAnElement);
+        }
+        if id == wrapper {
+            return Err(HierarchyError::Cycle);
+        }
+        self.insert_before(id, wrapper)?;
+        return self.append_child(wrapper, id);
+    }
+
+    /* Replaces the element by its children */
+    pub fn unwrap(&mut self, id: NodeId) -> Result<(), HierarchyError> {
+        if id == self.root() {
+            return Err(HierarchyError::Root);
+        }
+        if self.element(id).is_none() {
+            return Err(HierarchyError::NotAnElement);
+        }
+        if self.parent(id).is_none() {
+            return Err(HierarchyError::NoParent);
+        }
+        while let Some(child) = self.first_child(id) {
+            self.detach(child);
+            self.link_before(id, child);
+        }
+        self.detach(id);
+        return Ok(());
+    }
+
+    pub fn set_attribute(&mut self, id: NodeId, name: &str, value: &str) -> Result<(), HierarchyError> {
+        if !DOM::is_valid_attribute_name(name) {
+            return Err(HierarchyError::InvalidName);
+        }
+        let element = self.element_mut(id)?;
+        element.attributes.get_or_insert_with(Attributes::new).insert(&name.to_ascii_lowercase(), value);
+        return Ok(());
+    }
+
+    /* Returns the old value */
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str) -> Result<Option<String>, HierarchyError> {
+        let element = self.element_mut(id)?;
+        let value = element.attributes.as_mut().and_then(|attributes| attributes.remove(name));
+        if element.attributes.as_ref().is_some_and(Attributes::is_empty) {
+            element.attributes = None;
+        }
+        return Ok(value);
+    }
+
+    /* Replaces the children of an element with one text node, or the data of a text, comment or CDATA node */
+    pub fn set_text(&mut self, id: NodeId, text: &str) -> Result<(), HierarchyError> {
+        match &mut self.nodes[id.0].data {
+            Node::Text(data) | Node::Comment(data) | Node::CData(data) => {
+                *data = text.to_string();
+                return Ok(());
+            }
+            Node::Element(_) => {}
+            _ => return Err(HierarchyError::NotAnElement),
+        }
+        self.remove_children(id);
+        if !text.is_empty() {
+            let text = self.create_text(text);
+            self.append(id, text);
+        }
+        return Ok(());
+    }
+
+    /* Replaces the children of an element with the markup, parsed as a fragment in the context of the element */
+    pub fn set_inner_html(&mut self, id: NodeId, html: &str) -> Result<(), HierarchyError> {
+        let tag = self.element(id).ok_or(HierarchyError::NotAnElement)?.tag.clone();
+        let options = ParseOptions { decode_entities: !self.raw_entities, ..ParseOptions::default() };
+        let fragment = match tag {
+            Tag::Root => DOM::parse_str_with_options(html, &options).unwrap_or_default(),
+            tag => DOM::fragment(html, tag.name(), &options),
+        };
+        self.remove_children(id);
+        let mut child = fragment.first_child(fragment.root());
+        while let Some(child_id) = child {
+            let copy = self.import(&fragment, child_id);
+            self.append(id, copy);
+            child = fragment.next_sibling(child_id);
+        }
+        return Ok(());
+    }
+
+    fn element_mut(&mut self, id: NodeId) -> Result<&mut Element, HierarchyError> {
+        match &mut self.nodes[id.0].data {
+            Node::Element(element) => Ok(element),
+            _ => Err(HierarchyError::NotAnElement),
+        }
+    }
+
+    fn check_insert(&self, parent: NodeId, child: NodeId) -> Result<(), HierarchyError> {
+        if child == self.root() {
+            return Err(HierarchyError::Root);
+        }
+        if self.element(parent).is_none() {
+            return Err(HierarchyError::NotAnElement);
+        }
+        let mut ancestor = Some(parent);
+        while let Some(id) = ancestor {
+            if id == child {
+                return Err(HierarchyError::Cycle);
+            }
+            ancestor = self.parent(id);
+        }
+        return Ok(());
+    }
+
+    fn remove_children(&mut self, id: NodeId) {
+        while let Some(child) = self.first_child(id) {
+            self.detach(child);
+        }
+    }
+
+    /* Unlinks the node from its parent and siblings */
+    fn detach(&mut self, id: NodeId) {
+        let entry = &mut self.nodes[id.0];
+        let parent = entry.parent.take();
+        let previous = entry.previous_sibling.take();
+        let next = entry.next_sibling.take();
+        match (previous, parent) {
+            (Some(previous), _) => self.nodes[previous.0].next_sibling = next,
+            (None, Some(parent)) => self.nodes[parent.0].first_child = next,
+            (None, None) => {}
+        }
+        match (next, parent) {
+            (Some(next), _) => self.nodes[next.0].previous_sibling = previous,
+            (None, Some(parent)) => self.nodes[parent.0].last_child = previous,
+            (None, None) => {}
+        }
+    }
+
+    /* Links a detached node in front of the sibling */
+    fn link_before(&mut self, sibling: NodeId, id: NodeId) {
+        let parent = self.nodes[sibling.0].parent;
+        let previous = self.nodes[sibling.0].previous_sibling;
+        self.nodes[id.0].parent = parent;
+        self.nodes[id.0].previous_sibling = previous;
+        self.nodes[id.0].next_sibling = Some(sibling);
+        self.nodes[sibling.0].previous_sibling = Some(id);
+        match (previous, parent) {
+            (Some(previous), _) => self.nodes[previous.0].next_sibling = Some(id),
+            (None, Some(parent)) => self.nodes[parent.0].first_child = Some(id),
+            (None, None) => {}
+        }
+    }
+
+    /* ---------- Building ---------- */
+
+    /* Adds a node that is not linked into the tree yet */
+    pub(super) fn add(&mut self, data: Node) -> NodeId {
+        self.nodes.push(Entry {
+            data,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+        });
+        return NodeId(self.nodes.len() - 1);
+    }
+
+    /* Links a detached node as the last child, without the checks of append_child */
+    pub(super) fn append(&mut self, parent: NodeId, child: NodeId) {
+        let last = self.nodes[parent.0].last_child;
+        self.nodes[child.0].parent = Some(parent);
+        self.nodes[child.0].previous_sibling = last;
+        match last {
+            Some(last) => self.nodes[last.0].next_sibling = Some(child),
+            None => self.nodes[parent.0].first_child = Some(child),
+        }
+        self.nodes[parent.0].last_child = Some(child);
+    }
+}
+impl Default for Document {
+    fn default() -> Document {
+        Document::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(html: &str) -> (Document, NodeId) {
+        let document = DOM::parse_fragment(html, "body").unwrap();
+        let root = document.root();
+        return (document, root);
+    }
+
+    fn find(document: &Document, selectors: &str) -> NodeId {
+        document.query_selector(selectors).unwrap().unwrap()
+    }
+
+    #[test]
+    fn inserting_moves_nodes() {
+        let (mut document, root) = parse("<p>a</p><div><b>b</b></div>");
+        let p = find(&document, "p");
+        let b = find(&document, "b");
+        document.append_child(p, b).unwrap();
+        assert_eq!(document.inner_html(root), "<p>a<b>b</b></p><div></div>");
+        let em = document.create_element("em").unwrap();
+        document.prepend(p, em).unwrap();
+        let text = document.create_text("<c>");
+        document.insert_after(em, text).unwrap();
+        assert_eq!(document.inner_html(root), "<p><em></em>&lt;c&gt;a<b>b</b></p><div></div>");
+        let div = find(&document, "div");
+        document.insert_before(p, div).unwrap();
+        assert_eq!(document.inner_html(root), "<div></div><p><em></em>&lt;c&gt;a<b>b</b></p>");
+        assert_eq!(document.parent(div), Some(root));
+    }
+
+    #[test]
+    fn invalid_insertions_are_rejected() {
+        let (mut document, root) = parse("<div><p>a</p></div>");
+        let div = find(&document, "div");
+        let p = find(&document, "p");
+        let text = document.first_child(p).unwrap();
+        let orphan = document.create_element("span").unwrap();
+        assert_eq!(document.append_child(p, div), Err(HierarchyError::Cycle));
+        assert_eq!(document.append_child(text, orphan), Err(HierarchyError::NotAnElement));
+        assert_eq!(document.insert_before(orphan, p), Err(HierarchyError::NoParent));
+        assert_eq!(document.remove(root), Err(HierarchyError::Root));
+        assert_eq!(document.inner_html(root), "<div><p>a</p></div>");
+    }
+
+    #[test]
+    fn wrap_unwrap_and_replace() {
+        let (mut document, root) = parse("<p>a <b>b</b> c</p>");
+        let b = find(&document, "b");
+        let wrapper = document.create_element("i").unwrap();
+        document.wrap(b, wrapper).unwrap();
+        assert_eq!(document.inner_html(root), "<p>a <i><b>b</b></i> c</p>");
+        document.unwrap(b).unwrap();
+        assert_eq!(document.inner_html(root), "<p>a <i>b</i> c</p>");
+        let replacement = document.create_text("x");
+        document.replace_with(wrapper, replacement).unwrap();
+        assert_eq!(document.inner_html(root), "<p>a x c</p>");
+        assert_eq!(document.parent(wrapper), None);
+        let p = find(&document, "p");
+        document.remove(p).unwrap();
+        assert_eq!(document.inner_html(root), "");
+        assert_eq!(document.inner_html(p), "a x c");
+    }
+
+    #[test]
+    fn attributes_and_text() {
+        let (mut document, root) = parse("<p class=a>x<b>y</b></p>");
+        let p = find(&document, "p");
+        document.set_attribute(p, "ID", "z").unwrap();
+        document.set_attribute(p, "class", "b").unwrap();
+        assert_eq!(document.inner_html(root), "<p class=\"b\" id=\"z\">x<b>y</b></p>");
+        assert_eq!(document.remove_attribute(p, "class").unwrap(), Some("b".to_string()));
+        assert_eq!(document.remove_attribute(p, "id").unwrap(), Some("z".to_string()));
+        assert!(document.element(p).unwrap().attributes().is_none());
+        document.set_text(p, "a & b").unwrap();
+        assert_eq!(document.inner_html(root), "<p>a &amp; b</p>");
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let (mut document, root) = parse("<p>a</p>");
+        let p = find(&document, "p");
+        for name in ["", "1a", "-a", "a b", "a/b", "a>", "\0a"] {
+            assert_eq!(document.create_element(name), Err(HierarchyError::InvalidName), "{:?}", name);
+        }
+        for name in ["", "a b", "a=b", "a\"", "'a", "a/", "a>", "a\u{7}"] {
+            assert_eq!(document.set_attribute(p, name, "x"), Err(HierarchyError::InvalidName), "{:?}", name);
+        }
+        let custom = document.create_element("my-élément").unwrap();
+        document.set_attribute(custom, "data-x:y", "1").unwrap();
+        document.set_attribute(custom, "@click", "2").unwrap();
+        document.append_child(p, custom).unwrap();
+        assert_eq!(document.inner_html(root), "<p>a<my-élément data-x:y=\"1\" @click=\"2\"></my-élément></p>");
+    }
+
+    #[test]
+    fn inner_html_is_parsed_in_the_context_of_the_element() {
+        let (mut document, root) = parse("<table></table><p></p>");
+        let table = find(&document, "table");
+        document.set_inner_html(table, "<tr><td>a").unwrap();
+        let p = find(&document, "p");
+        document.set_inner_html(p, "<td>b</td>&amp;").unwrap();
+        assert_eq!(document.inner_html(root), "<table><tbody><tr><td>a</td></tr></tbody></table><p>b&amp;</p>");
+        let (mut other, _) = parse("<i>c</i>");
+        let copy = other.import(&document, table);
+        other.append_child(other.root(), copy).unwrap();
+        assert_eq!(other.inner_html(other.root()), "<i>c</i><table><tbody><tr><td>a</td></tr></tbody></table>");
+    }
+}
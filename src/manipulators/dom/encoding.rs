@@ -0,0 +1,790 @@
+#![allow(dead_code)]
+
+/* Encoding sniffing and decoding for byte input, see https://html.spec.whatwg.org/#encoding-sniffing-algorithm
+and https://encoding.spec.whatwg.org/ for the labels and single byte indexes */
+
+use super::is_whitespace;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /* windows-1250 to windows-1258 */
+    Windows(u16),
+    /* ISO-8859-2 to ISO-8859-16, ISO-8859-1 is decoded as windows-1252 like browsers do */
+    Iso8859(u8),
+    /* Bytes 0x80 to 0xFF map to the private use area U+F780 to U+F7FF */
+    XUserDefined,
+}
+impl Encoding {
+    pub fn name(&self) -> String {
+        match self {
+            Encoding::Utf8 => "UTF-8".to_string(),
+            Encoding::Utf16Le => "UTF-16LE".to_string(),
+            Encoding::Utf16Be => "UTF-16BE".to_string(),
+            Encoding::Windows(number) => format!("windows-{}", number),
+            Encoding::Iso8859(number) => format!("ISO-8859-{}", number),
+            Encoding::XUserDefined => "x-user-defined".to_string(),
+        }
+    }
+
+    /* See https://encoding.spec.whatwg.org/#concept-encoding-get */
+    pub fn for_label(label: &str) -> Option<Encoding> {
+        let label = label.trim_matches(is_whitespace).to_ascii_lowercase();
+        let encoding = match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8" | "x-unicode20utf8" => Encoding::Utf8,
+            "unicodefffe" | "utf-16be" => Encoding::Utf16Be,
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16" | "utf-16le" => Encoding::Utf16Le,
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819" | "iso-8859-1" | "iso-ir-100"
+            | "iso8859-1" | "iso88591" | "iso_8859-1" | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii"
+            | "windows-1252" | "x-cp1252" => Encoding::Windows(1252),
+            "cp1250" | "windows-1250" | "x-cp1250" => Encoding::Windows(1250),
+            "cp1251" | "windows-1251" | "x-cp1251" => Encoding::Windows(1251),
+            "cp1253" | "windows-1253" | "x-cp1253" => Encoding::Windows(1253),
+            "cp1254" | "csisolatin5" | "iso-8859-9" | "iso-ir-148" | "iso8859-9" | "iso88599" | "iso_8859-9"
+            | "iso_8859-9:1989" | "l5" | "latin5" | "windows-1254" | "x-cp1254" => Encoding::Windows(1254),
+            "cp1255" | "windows-1255" | "x-cp1255" => Encoding::Windows(1255),
+            "cp1256" | "windows-1256" | "x-cp1256" => Encoding::Windows(1256),
+            "cp1257" | "windows-1257" | "x-cp1257" => Encoding::Windows(1257),
+            "cp1258" | "windows-1258" | "x-cp1258" => Encoding::Windows(1258),
+            "csisolatin2" | "iso-8859-2" | "iso-ir-101" | "iso8859-2" | "iso88592" | "iso_8859-2" | "iso_8859-2:1987"
+            | "l2" | "latin2" => Encoding::Iso8859(2),
+            "csisolatin3" | "iso-8859-3" | "iso-ir-109" | "iso8859-3" | "iso88593" | "iso_8859-3" | "iso_8859-3:1988"
+            | "l3" | "latin3" => Encoding::Iso8859(3),
+            "csisolatin4" | "iso-8859-4" | "iso-ir-110" | "iso8859-4" | "iso88594" | "iso_8859-4" | "iso_8859-4:1988"
+            | "l4" | "latin4" => Encoding::Iso8859(4),
+            "csisolatincyrillic" | "cyrillic" | "iso-8859-5" | "iso-ir-144" | "iso8859-5" | "iso88595" | "iso_8859-5"
+            | "iso_8859-5:1988" => Encoding::Iso8859(5),
+            "arabic" | "asmo-708" | "csiso88596e" | "csiso88596i" | "csisolatinarabic" | "ecma-114" | "iso-8859-6"
+            | "iso-8859-6-e" | "iso-8859-6-i" | "iso-ir-127" | "iso8859-6" | "iso88596" | "iso_8859-6"
+            | "iso_8859-6:1987" => Encoding::Iso8859(6),
+            "csisolatingreek" | "ecma-118" | "elot_928" | "greek" | "greek8" | "iso-8859-7" | "iso-ir-126" | "iso8859-7"
+            | "iso88597" | "iso_8859-7" | "iso_8859-7:1987" | "sun_eu_greek" => Encoding::Iso8859(7),
+            "csiso88598e" | "csisolatinhebrew" | "hebrew" | "iso-8859-8" | "iso-8859-8-e" | "iso-ir-138" | "iso8859-8"
+            | "iso88598" | "iso_8859-8" | "iso_8859-8:1988" | "visual" | "csiso88598i" | "iso-8859-8-i" | "logical" => {
+                Encoding::Iso8859(8)
+            }
+            "csisolatin6" | "iso-8859-10" | "iso-ir-157" | "iso8859-10" | "iso885910" | "l6" | "latin6" => Encoding::Iso8859(10),
+            "iso-8859-13" | "iso8859-13" | "iso885913" => Encoding::Iso8859(13),
+            "iso-8859-14" | "iso8859-14" | "iso885914" => Encoding::Iso8859(14),
+            "csisolatin9" | "iso-8859-15" | "iso8859-15" | "iso885915" | "iso_8859-15" | "l9" => Encoding::Iso8859(15),
+            "iso-8859-16" => Encoding::Iso8859(16),
+            "x-user-defined" => Encoding::XUserDefined,
+            _ => return None,
+        };
+        return Some(encoding);
+    }
+
+    fn table(&self) -> Option<&'static [char; 128]> {
+        let table = match self {
+            Encoding::Windows(1250) => &WINDOWS_1250,
+            Encoding::Windows(1251) => &WINDOWS_1251,
+            Encoding::Windows(1252) => &WINDOWS_1252,
+            Encoding::Windows(1253) => &WINDOWS_1253,
+            Encoding::Windows(1254) => &WINDOWS_1254,
+            Encoding::Windows(1255) => &WINDOWS_1255,
+            Encoding::Windows(1256) => &WINDOWS_1256,
+            Encoding::Windows(1257) => &WINDOWS_1257,
+            Encoding::Windows(1258) => &WINDOWS_1258,
+            Encoding::Iso8859(2) => &ISO_8859_2,
+            Encoding::Iso8859(3) => &ISO_8859_3,
+            Encoding::Iso8859(4) => &ISO_8859_4,
+            Encoding::Iso8859(5) => &ISO_8859_5,
+            Encoding::Iso8859(6) => &ISO_8859_6,
+            Encoding::Iso8859(7) => &ISO_8859_7,
+            Encoding::Iso8859(8) => &ISO_8859_8,
+            Encoding::Iso8859(10) => &ISO_8859_10,
+            Encoding::Iso8859(13) => &ISO_8859_13,
+            Encoding::Iso8859(14) => &ISO_8859_14,
+            Encoding::Iso8859(15) => &ISO_8859_15,
+            Encoding::Iso8859(16) => &ISO_8859_16,
+            _ => return None,
+        };
+        return Some(table);
+    }
+
+    /* Decodes bytes without a byte order mark, invalid sequences become U+FFFD */
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).to_string(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let units = bytes.chunks(2).map(|pair| match pair {
+                    [first, second] if *self == Encoding::Utf16Le => u16::from_le_bytes([*first, *second]),
+                    [first, second] => u16::from_be_bytes([*first, *second]),
+                    /* A lone trailing byte is an error */
+                    _ => 0xD800,
+                });
+                char::decode_utf16(units).map(|unit| unit.unwrap_or('\u{FFFD}')).collect()
+            }
+            Encoding::XUserDefined => {
+                bytes.iter().map(|&byte| if byte < 0x80 { byte as char } else { char::from_u32(0xF700 + byte as u32).unwrap() }).collect()
+            }
+            _ => {
+                let table = self.table().unwrap_or(&WINDOWS_1252);
+                bytes.iter().map(|&byte| if byte < 0x80 { byte as char } else { table[byte as usize - 0x80] }).collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confidence {
+    Certain,
+    Tentative,
+}
+
+/* Number of bytes the meta prescan looks at */
+const PRESCAN_LIMIT: usize = 1024;
+
+fn bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some((Encoding::Utf8, 3))
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        Some((Encoding::Utf16Be, 2))
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        Some((Encoding::Utf16Le, 2))
+    } else {
+        None
+    }
+}
+
+/* Determines the encoding of an HTML byte stream.
+transport is the charset given by the transport layer, either a label like "utf-8" or a full Content-Type value */
+pub fn sniff(bytes: &[u8], transport: Option<&str>) -> (Encoding, Confidence) {
+    if let Some((encoding, _)) = bom(bytes) {
+        return (encoding, Confidence::Certain);
+    }
+    if let Some(transport) = transport {
+        let label = extract_from_content(transport.to_ascii_lowercase().as_bytes()).unwrap_or(transport.to_string());
+        if let Some(encoding) = Encoding::for_label(&label) {
+            return (encoding, Confidence::Certain);
+        }
+    }
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LIMIT)]) {
+        return (encoding, Confidence::Tentative);
+    }
+    /* Frequency analysis is left to the implementation, valid non-ASCII UTF-8 is a strong enough signal */
+    if !bytes.is_ascii() && std::str::from_utf8(bytes).is_ok() {
+        return (Encoding::Utf8, Confidence::Tentative);
+    }
+    return (Encoding::Windows(1252), Confidence::Tentative);
+}
+
+/* Sniffs the encoding and decodes the bytes into the text the tokenizer consumes */
+pub fn decode(bytes: &[u8], transport: Option<&str>) -> (String, Encoding) {
+    let (encoding, _) = sniff(bytes, transport);
+    let bytes = match bom(bytes) {
+        Some((bom_encoding, length)) if bom_encoding == encoding => &bytes[length..],
+        _ => bytes,
+    };
+    return (encoding.decode(bytes), encoding);
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/* See https://html.spec.whatwg.org/#prescan-a-byte-stream-to-determine-its-encoding */
+fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            /* The "-->" may share its dashes with the "<!--" */
+            match rest[2..].windows(3).position(|window| window == b"-->") {
+                Some(end) => pos += 2 + end + 2,
+                None => return None,
+            }
+        } else if starts_with_ignore_case(rest, b"<meta") && rest.get(5).is_some_and(|&byte| byte.is_ascii_whitespace() || byte == b'/') {
+            pos += 5;
+            let mut names: Vec<Vec<u8>> = Vec::new();
+            let mut got_pragma = false;
+            let mut need_pragma: Option<bool> = None;
+            let mut charset: Option<Option<Encoding>> = None;
+            while let Some((name, value)) = get_attribute(bytes, &mut pos) {
+                if names.contains(&name) {
+                    continue;
+                }
+                match name.as_slice() {
+                    b"http-equiv" if value == b"content-type" => got_pragma = true,
+                    b"content" if charset.is_none() => {
+                        if let Some(label) = extract_from_content(&value) {
+                            charset = Some(Encoding::for_label(&label));
+                            need_pragma = Some(true);
+                        }
+                    }
+                    b"charset" => {
+                        charset = Some(Encoding::for_label(&String::from_utf8_lossy(&value)));
+                        need_pragma = Some(false);
+                    }
+                    _ => {}
+                }
+                names.push(name);
+            }
+            let pragma_satisfied = match need_pragma {
+                Some(need) => got_pragma || !need,
+                None => false,
+            };
+            if let (true, Some(Some(encoding))) = (pragma_satisfied, charset) {
+                return match encoding {
+                    Encoding::Utf16Le | Encoding::Utf16Be => Some(Encoding::Utf8),
+                    Encoding::XUserDefined => Some(Encoding::Windows(1252)),
+                    encoding => Some(encoding),
+                };
+            }
+        } else if rest.len() > 1 && rest[0] == b'<' && (rest[1].is_ascii_alphabetic() || (rest[1] == b'/' && rest.get(2).is_some_and(|byte| byte.is_ascii_alphabetic()))) {
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            match rest.iter().position(|&byte| byte == b'>') {
+                Some(end) => pos += end,
+                None => return None,
+            }
+        }
+        pos += 1;
+    }
+    return None;
+}
+
+/* See https://html.spec.whatwg.org/#concept-get-attributes-when-sniffing, returns None at ">" or the end of the input */
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    while *pos < bytes.len() && (bytes[*pos].is_ascii_whitespace() || bytes[*pos] == b'/') {
+        *pos += 1;
+    }
+    if *bytes.get(*pos)? == b'>' {
+        return None;
+    }
+    let mut name: Vec<u8> = Vec::new();
+    let mut value: Vec<u8> = Vec::new();
+    loop {
+        let byte = *bytes.get(*pos)?;
+        if byte == b'=' && !name.is_empty() {
+            *pos += 1;
+            break;
+        } else if byte.is_ascii_whitespace() {
+            while bytes.get(*pos).is_some_and(|&byte| byte.is_ascii_whitespace()) {
+                *pos += 1;
+            }
+            if *bytes.get(*pos)? != b'=' {
+                return Some((name, value));
+            }
+            *pos += 1;
+            break;
+        } else if byte == b'/' || byte == b'>' {
+            return Some((name, value));
+        }
+        name.push(byte.to_ascii_lowercase());
+        *pos += 1;
+    }
+    while bytes.get(*pos).is_some_and(|&byte| byte.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    let byte = *bytes.get(*pos)?;
+    if byte == b'"' || byte == b'\'' {
+        *pos += 1;
+        loop {
+            let next = *bytes.get(*pos)?;
+            *pos += 1;
+            if next == byte {
+                return Some((name, value));
+            }
+            value.push(next.to_ascii_lowercase());
+        }
+    }
+    if byte == b'>' {
+        return Some((name, value));
+    }
+    loop {
+        let byte = *bytes.get(*pos)?;
+        if byte.is_ascii_whitespace() || byte == b'>' {
+            return Some((name, value));
+        }
+        value.push(byte.to_ascii_lowercase());
+        *pos += 1;
+    }
+}
+
+/* See https://html.spec.whatwg.org/#algorithm-for-extracting-a-character-encoding-from-a-meta-element */
+fn extract_from_content(content: &[u8]) -> Option<String> {
+    let mut pos = 0;
+    loop {
+        let found = content[pos..].windows(7).position(|window| window.eq_ignore_ascii_case(b"charset"))?;
+        pos += found + 7;
+        while content.get(pos).is_some_and(|&byte| byte.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        if content.get(pos) == Some(&b'=') {
+            pos += 1;
+            break;
+        }
+    }
+    while content.get(pos).is_some_and(|&byte| byte.is_ascii_whitespace()) {
+        pos += 1;
+    }
+    let value = match content.get(pos)? {
+        quote @ (b'"' | b'\'') => {
+            let end = content[pos + 1..].iter().position(|byte| byte == quote)?;
+            &content[pos + 1..pos + 1 + end]
+        }
+        _ => {
+            let end = content[pos..].iter().position(|&byte| byte.is_ascii_whitespace() || byte == b';').unwrap_or(content.len() - pos);
+            &content[pos..pos + end]
+        }
+    };
+    return Some(String::from_utf8_lossy(value).to_string());
+}
+
+static WINDOWS_1250: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{83}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{88}', '\u{2030}', '\u{160}', '\u{2039}', '\u{15A}', '\u{164}', '\u{17D}', '\u{179}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{98}', '\u{2122}', '\u{161}', '\u{203A}', '\u{15B}', '\u{165}', '\u{17E}', '\u{17A}',
+    '\u{A0}', '\u{2C7}', '\u{2D8}', '\u{141}', '\u{A4}', '\u{104}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{15E}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{17B}',
+    '\u{B0}', '\u{B1}', '\u{2DB}', '\u{142}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{105}', '\u{15F}', '\u{BB}', '\u{13D}', '\u{2DD}', '\u{13E}', '\u{17C}',
+    '\u{154}', '\u{C1}', '\u{C2}', '\u{102}', '\u{C4}', '\u{139}', '\u{106}', '\u{C7}',
+    '\u{10C}', '\u{C9}', '\u{118}', '\u{CB}', '\u{11A}', '\u{CD}', '\u{CE}', '\u{10E}',
+    '\u{110}', '\u{143}', '\u{147}', '\u{D3}', '\u{D4}', '\u{150}', '\u{D6}', '\u{D7}',
+    '\u{158}', '\u{16E}', '\u{DA}', '\u{170}', '\u{DC}', '\u{DD}', '\u{162}', '\u{DF}',
+    '\u{155}', '\u{E1}', '\u{E2}', '\u{103}', '\u{E4}', '\u{13A}', '\u{107}', '\u{E7}',
+    '\u{10D}', '\u{E9}', '\u{119}', '\u{EB}', '\u{11B}', '\u{ED}', '\u{EE}', '\u{10F}',
+    '\u{111}', '\u{144}', '\u{148}', '\u{F3}', '\u{F4}', '\u{151}', '\u{F6}', '\u{F7}',
+    '\u{159}', '\u{16F}', '\u{FA}', '\u{171}', '\u{FC}', '\u{FD}', '\u{163}', '\u{2D9}',
+];
+
+static WINDOWS_1251: [char; 128] = [
+    '\u{402}', '\u{403}', '\u{201A}', '\u{453}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{20AC}', '\u{2030}', '\u{409}', '\u{2039}', '\u{40A}', '\u{40C}', '\u{40B}', '\u{40F}',
+    '\u{452}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{98}', '\u{2122}', '\u{459}', '\u{203A}', '\u{45A}', '\u{45C}', '\u{45B}', '\u{45F}',
+    '\u{A0}', '\u{40E}', '\u{45E}', '\u{408}', '\u{A4}', '\u{490}', '\u{A6}', '\u{A7}',
+    '\u{401}', '\u{A9}', '\u{404}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{407}',
+    '\u{B0}', '\u{B1}', '\u{406}', '\u{456}', '\u{491}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{451}', '\u{2116}', '\u{454}', '\u{BB}', '\u{458}', '\u{405}', '\u{455}', '\u{457}',
+    '\u{410}', '\u{411}', '\u{412}', '\u{413}', '\u{414}', '\u{415}', '\u{416}', '\u{417}',
+    '\u{418}', '\u{419}', '\u{41A}', '\u{41B}', '\u{41C}', '\u{41D}', '\u{41E}', '\u{41F}',
+    '\u{420}', '\u{421}', '\u{422}', '\u{423}', '\u{424}', '\u{425}', '\u{426}', '\u{427}',
+    '\u{428}', '\u{429}', '\u{42A}', '\u{42B}', '\u{42C}', '\u{42D}', '\u{42E}', '\u{42F}',
+    '\u{430}', '\u{431}', '\u{432}', '\u{433}', '\u{434}', '\u{435}', '\u{436}', '\u{437}',
+    '\u{438}', '\u{439}', '\u{43A}', '\u{43B}', '\u{43C}', '\u{43D}', '\u{43E}', '\u{43F}',
+    '\u{440}', '\u{441}', '\u{442}', '\u{443}', '\u{444}', '\u{445}', '\u{446}', '\u{447}',
+    '\u{448}', '\u{449}', '\u{44A}', '\u{44B}', '\u{44C}', '\u{44D}', '\u{44E}', '\u{44F}',
+];
+
+static WINDOWS_1252: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+    '\u{A0}', '\u{A1}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{AA}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{B9}', '\u{BA}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{BF}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{C3}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{CC}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{D0}', '\u{D1}', '\u{D2}', '\u{D3}', '\u{D4}', '\u{D5}', '\u{D6}', '\u{D7}',
+    '\u{D8}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{DD}', '\u{DE}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{E3}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{EC}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{F0}', '\u{F1}', '\u{F2}', '\u{F3}', '\u{F4}', '\u{F5}', '\u{F6}', '\u{F7}',
+    '\u{F8}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{FD}', '\u{FE}', '\u{FF}',
+];
+
+static WINDOWS_1253: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{88}', '\u{2030}', '\u{8A}', '\u{2039}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{98}', '\u{2122}', '\u{9A}', '\u{203A}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{385}', '\u{386}', '\u{A3}', '\u{A4}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{FFFD}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{2015}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{384}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{388}', '\u{389}', '\u{38A}', '\u{BB}', '\u{38C}', '\u{BD}', '\u{38E}', '\u{38F}',
+    '\u{390}', '\u{391}', '\u{392}', '\u{393}', '\u{394}', '\u{395}', '\u{396}', '\u{397}',
+    '\u{398}', '\u{399}', '\u{39A}', '\u{39B}', '\u{39C}', '\u{39D}', '\u{39E}', '\u{39F}',
+    '\u{3A0}', '\u{3A1}', '\u{FFFD}', '\u{3A3}', '\u{3A4}', '\u{3A5}', '\u{3A6}', '\u{3A7}',
+    '\u{3A8}', '\u{3A9}', '\u{3AA}', '\u{3AB}', '\u{3AC}', '\u{3AD}', '\u{3AE}', '\u{3AF}',
+    '\u{3B0}', '\u{3B1}', '\u{3B2}', '\u{3B3}', '\u{3B4}', '\u{3B5}', '\u{3B6}', '\u{3B7}',
+    '\u{3B8}', '\u{3B9}', '\u{3BA}', '\u{3BB}', '\u{3BC}', '\u{3BD}', '\u{3BE}', '\u{3BF}',
+    '\u{3C0}', '\u{3C1}', '\u{3C2}', '\u{3C3}', '\u{3C4}', '\u{3C5}', '\u{3C6}', '\u{3C7}',
+    '\u{3C8}', '\u{3C9}', '\u{3CA}', '\u{3CB}', '\u{3CC}', '\u{3CD}', '\u{3CE}', '\u{FFFD}',
+];
+
+static WINDOWS_1254: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{9E}', '\u{178}',
+    '\u{A0}', '\u{A1}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{AA}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{B9}', '\u{BA}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{BF}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{C3}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{CC}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{11E}', '\u{D1}', '\u{D2}', '\u{D3}', '\u{D4}', '\u{D5}', '\u{D6}', '\u{D7}',
+    '\u{D8}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{130}', '\u{15E}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{E3}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{EC}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{11F}', '\u{F1}', '\u{F2}', '\u{F3}', '\u{F4}', '\u{F5}', '\u{F6}', '\u{F7}',
+    '\u{F8}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{131}', '\u{15F}', '\u{FF}',
+];
+
+static WINDOWS_1255: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{8A}', '\u{2039}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{9A}', '\u{203A}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{A1}', '\u{A2}', '\u{A3}', '\u{20AA}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{D7}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{B9}', '\u{F7}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{BF}',
+    '\u{5B0}', '\u{5B1}', '\u{5B2}', '\u{5B3}', '\u{5B4}', '\u{5B5}', '\u{5B6}', '\u{5B7}',
+    '\u{5B8}', '\u{5B9}', '\u{FFFD}', '\u{5BB}', '\u{5BC}', '\u{5BD}', '\u{5BE}', '\u{5BF}',
+    '\u{5C0}', '\u{5C1}', '\u{5C2}', '\u{5C3}', '\u{5F0}', '\u{5F1}', '\u{5F2}', '\u{5F3}',
+    '\u{5F4}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{5D0}', '\u{5D1}', '\u{5D2}', '\u{5D3}', '\u{5D4}', '\u{5D5}', '\u{5D6}', '\u{5D7}',
+    '\u{5D8}', '\u{5D9}', '\u{5DA}', '\u{5DB}', '\u{5DC}', '\u{5DD}', '\u{5DE}', '\u{5DF}',
+    '\u{5E0}', '\u{5E1}', '\u{5E2}', '\u{5E3}', '\u{5E4}', '\u{5E5}', '\u{5E6}', '\u{5E7}',
+    '\u{5E8}', '\u{5E9}', '\u{5EA}', '\u{FFFD}', '\u{FFFD}', '\u{200E}', '\u{200F}', '\u{FFFD}',
+];
+
+static WINDOWS_1256: [char; 128] = [
+    '\u{20AC}', '\u{67E}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{679}', '\u{2039}', '\u{152}', '\u{686}', '\u{698}', '\u{688}',
+    '\u{6AF}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{6A9}', '\u{2122}', '\u{691}', '\u{203A}', '\u{153}', '\u{200C}', '\u{200D}', '\u{6BA}',
+    '\u{A0}', '\u{60C}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{6BE}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{B9}', '\u{61B}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{61F}',
+    '\u{6C1}', '\u{621}', '\u{622}', '\u{623}', '\u{624}', '\u{625}', '\u{626}', '\u{627}',
+    '\u{628}', '\u{629}', '\u{62A}', '\u{62B}', '\u{62C}', '\u{62D}', '\u{62E}', '\u{62F}',
+    '\u{630}', '\u{631}', '\u{632}', '\u{633}', '\u{634}', '\u{635}', '\u{636}', '\u{D7}',
+    '\u{637}', '\u{638}', '\u{639}', '\u{63A}', '\u{640}', '\u{641}', '\u{642}', '\u{643}',
+    '\u{E0}', '\u{644}', '\u{E2}', '\u{645}', '\u{646}', '\u{647}', '\u{648}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{649}', '\u{64A}', '\u{EE}', '\u{EF}',
+    '\u{64B}', '\u{64C}', '\u{64D}', '\u{64E}', '\u{F4}', '\u{64F}', '\u{650}', '\u{F7}',
+    '\u{651}', '\u{F9}', '\u{652}', '\u{FB}', '\u{FC}', '\u{200E}', '\u{200F}', '\u{6D2}',
+];
+
+static WINDOWS_1257: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{83}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{88}', '\u{2030}', '\u{8A}', '\u{2039}', '\u{8C}', '\u{A8}', '\u{2C7}', '\u{B8}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{98}', '\u{2122}', '\u{9A}', '\u{203A}', '\u{9C}', '\u{AF}', '\u{2DB}', '\u{9F}',
+    '\u{A0}', '\u{FFFD}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{FFFD}', '\u{A6}', '\u{A7}',
+    '\u{D8}', '\u{A9}', '\u{156}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{C6}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{F8}', '\u{B9}', '\u{157}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{E6}',
+    '\u{104}', '\u{12E}', '\u{100}', '\u{106}', '\u{C4}', '\u{C5}', '\u{118}', '\u{112}',
+    '\u{10C}', '\u{C9}', '\u{179}', '\u{116}', '\u{122}', '\u{136}', '\u{12A}', '\u{13B}',
+    '\u{160}', '\u{143}', '\u{145}', '\u{D3}', '\u{14C}', '\u{D5}', '\u{D6}', '\u{D7}',
+    '\u{172}', '\u{141}', '\u{15A}', '\u{16A}', '\u{DC}', '\u{17B}', '\u{17D}', '\u{DF}',
+    '\u{105}', '\u{12F}', '\u{101}', '\u{107}', '\u{E4}', '\u{E5}', '\u{119}', '\u{113}',
+    '\u{10D}', '\u{E9}', '\u{17A}', '\u{117}', '\u{123}', '\u{137}', '\u{12B}', '\u{13C}',
+    '\u{161}', '\u{144}', '\u{146}', '\u{F3}', '\u{14D}', '\u{F5}', '\u{F6}', '\u{F7}',
+    '\u{173}', '\u{142}', '\u{15B}', '\u{16B}', '\u{FC}', '\u{17C}', '\u{17E}', '\u{2D9}',
+];
+
+static WINDOWS_1258: [char; 128] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{8A}', '\u{2039}', '\u{152}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{9A}', '\u{203A}', '\u{153}', '\u{9D}', '\u{9E}', '\u{178}',
+    '\u{A0}', '\u{A1}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{AA}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{B9}', '\u{BA}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{BF}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{102}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{300}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{110}', '\u{D1}', '\u{309}', '\u{D3}', '\u{D4}', '\u{1A0}', '\u{D6}', '\u{D7}',
+    '\u{D8}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{1AF}', '\u{303}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{103}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{301}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{111}', '\u{F1}', '\u{323}', '\u{F3}', '\u{F4}', '\u{1A1}', '\u{F6}', '\u{F7}',
+    '\u{F8}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{1B0}', '\u{20AB}', '\u{FF}',
+];
+
+static ISO_8859_2: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{104}', '\u{2D8}', '\u{141}', '\u{A4}', '\u{13D}', '\u{15A}', '\u{A7}',
+    '\u{A8}', '\u{160}', '\u{15E}', '\u{164}', '\u{179}', '\u{AD}', '\u{17D}', '\u{17B}',
+    '\u{B0}', '\u{105}', '\u{2DB}', '\u{142}', '\u{B4}', '\u{13E}', '\u{15B}', '\u{2C7}',
+    '\u{B8}', '\u{161}', '\u{15F}', '\u{165}', '\u{17A}', '\u{2DD}', '\u{17E}', '\u{17C}',
+    '\u{154}', '\u{C1}', '\u{C2}', '\u{102}', '\u{C4}', '\u{139}', '\u{106}', '\u{C7}',
+    '\u{10C}', '\u{C9}', '\u{118}', '\u{CB}', '\u{11A}', '\u{CD}', '\u{CE}', '\u{10E}',
+    '\u{110}', '\u{143}', '\u{147}', '\u{D3}', '\u{D4}', '\u{150}', '\u{D6}', '\u{D7}',
+    '\u{158}', '\u{16E}', '\u{DA}', '\u{170}', '\u{DC}', '\u{DD}', '\u{162}', '\u{DF}',
+    '\u{155}', '\u{E1}', '\u{E2}', '\u{103}', '\u{E4}', '\u{13A}', '\u{107}', '\u{E7}',
+    '\u{10D}', '\u{E9}', '\u{119}', '\u{EB}', '\u{11B}', '\u{ED}', '\u{EE}', '\u{10F}',
+    '\u{111}', '\u{144}', '\u{148}', '\u{F3}', '\u{F4}', '\u{151}', '\u{F6}', '\u{F7}',
+    '\u{159}', '\u{16F}', '\u{FA}', '\u{171}', '\u{FC}', '\u{FD}', '\u{163}', '\u{2D9}',
+];
+
+static ISO_8859_3: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{126}', '\u{2D8}', '\u{A3}', '\u{A4}', '\u{FFFD}', '\u{124}', '\u{A7}',
+    '\u{A8}', '\u{130}', '\u{15E}', '\u{11E}', '\u{134}', '\u{AD}', '\u{FFFD}', '\u{17B}',
+    '\u{B0}', '\u{127}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{125}', '\u{B7}',
+    '\u{B8}', '\u{131}', '\u{15F}', '\u{11F}', '\u{135}', '\u{BD}', '\u{FFFD}', '\u{17C}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{FFFD}', '\u{C4}', '\u{10A}', '\u{108}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{CC}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{FFFD}', '\u{D1}', '\u{D2}', '\u{D3}', '\u{D4}', '\u{120}', '\u{D6}', '\u{D7}',
+    '\u{11C}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{16C}', '\u{15C}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{FFFD}', '\u{E4}', '\u{10B}', '\u{109}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{EC}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{FFFD}', '\u{F1}', '\u{F2}', '\u{F3}', '\u{F4}', '\u{121}', '\u{F6}', '\u{F7}',
+    '\u{11D}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{16D}', '\u{15D}', '\u{2D9}',
+];
+
+static ISO_8859_4: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{104}', '\u{138}', '\u{156}', '\u{A4}', '\u{128}', '\u{13B}', '\u{A7}',
+    '\u{A8}', '\u{160}', '\u{112}', '\u{122}', '\u{166}', '\u{AD}', '\u{17D}', '\u{AF}',
+    '\u{B0}', '\u{105}', '\u{2DB}', '\u{157}', '\u{B4}', '\u{129}', '\u{13C}', '\u{2C7}',
+    '\u{B8}', '\u{161}', '\u{113}', '\u{123}', '\u{167}', '\u{14A}', '\u{17E}', '\u{14B}',
+    '\u{100}', '\u{C1}', '\u{C2}', '\u{C3}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{12E}',
+    '\u{10C}', '\u{C9}', '\u{118}', '\u{CB}', '\u{116}', '\u{CD}', '\u{CE}', '\u{12A}',
+    '\u{110}', '\u{145}', '\u{14C}', '\u{136}', '\u{D4}', '\u{D5}', '\u{D6}', '\u{D7}',
+    '\u{D8}', '\u{172}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{168}', '\u{16A}', '\u{DF}',
+    '\u{101}', '\u{E1}', '\u{E2}', '\u{E3}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{12F}',
+    '\u{10D}', '\u{E9}', '\u{119}', '\u{EB}', '\u{117}', '\u{ED}', '\u{EE}', '\u{12B}',
+    '\u{111}', '\u{146}', '\u{14D}', '\u{137}', '\u{F4}', '\u{F5}', '\u{F6}', '\u{F7}',
+    '\u{F8}', '\u{173}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{169}', '\u{16B}', '\u{2D9}',
+];
+
+static ISO_8859_5: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{401}', '\u{402}', '\u{403}', '\u{404}', '\u{405}', '\u{406}', '\u{407}',
+    '\u{408}', '\u{409}', '\u{40A}', '\u{40B}', '\u{40C}', '\u{AD}', '\u{40E}', '\u{40F}',
+    '\u{410}', '\u{411}', '\u{412}', '\u{413}', '\u{414}', '\u{415}', '\u{416}', '\u{417}',
+    '\u{418}', '\u{419}', '\u{41A}', '\u{41B}', '\u{41C}', '\u{41D}', '\u{41E}', '\u{41F}',
+    '\u{420}', '\u{421}', '\u{422}', '\u{423}', '\u{424}', '\u{425}', '\u{426}', '\u{427}',
+    '\u{428}', '\u{429}', '\u{42A}', '\u{42B}', '\u{42C}', '\u{42D}', '\u{42E}', '\u{42F}',
+    '\u{430}', '\u{431}', '\u{432}', '\u{433}', '\u{434}', '\u{435}', '\u{436}', '\u{437}',
+    '\u{438}', '\u{439}', '\u{43A}', '\u{43B}', '\u{43C}', '\u{43D}', '\u{43E}', '\u{43F}',
+    '\u{440}', '\u{441}', '\u{442}', '\u{443}', '\u{444}', '\u{445}', '\u{446}', '\u{447}',
+    '\u{448}', '\u{449}', '\u{44A}', '\u{44B}', '\u{44C}', '\u{44D}', '\u{44E}', '\u{44F}',
+    '\u{2116}', '\u{451}', '\u{452}', '\u{453}', '\u{454}', '\u{455}', '\u{456}', '\u{457}',
+    '\u{458}', '\u{459}', '\u{45A}', '\u{45B}', '\u{45C}', '\u{A7}', '\u{45E}', '\u{45F}',
+];
+
+static ISO_8859_6: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{A4}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{60C}', '\u{AD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{61B}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{61F}',
+    '\u{FFFD}', '\u{621}', '\u{622}', '\u{623}', '\u{624}', '\u{625}', '\u{626}', '\u{627}',
+    '\u{628}', '\u{629}', '\u{62A}', '\u{62B}', '\u{62C}', '\u{62D}', '\u{62E}', '\u{62F}',
+    '\u{630}', '\u{631}', '\u{632}', '\u{633}', '\u{634}', '\u{635}', '\u{636}', '\u{637}',
+    '\u{638}', '\u{639}', '\u{63A}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{640}', '\u{641}', '\u{642}', '\u{643}', '\u{644}', '\u{645}', '\u{646}', '\u{647}',
+    '\u{648}', '\u{649}', '\u{64A}', '\u{64B}', '\u{64C}', '\u{64D}', '\u{64E}', '\u{64F}',
+    '\u{650}', '\u{651}', '\u{652}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+];
+
+static ISO_8859_7: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{2018}', '\u{2019}', '\u{A3}', '\u{20AC}', '\u{20AF}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{37A}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{FFFD}', '\u{2015}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{384}', '\u{385}', '\u{386}', '\u{B7}',
+    '\u{388}', '\u{389}', '\u{38A}', '\u{BB}', '\u{38C}', '\u{BD}', '\u{38E}', '\u{38F}',
+    '\u{390}', '\u{391}', '\u{392}', '\u{393}', '\u{394}', '\u{395}', '\u{396}', '\u{397}',
+    '\u{398}', '\u{399}', '\u{39A}', '\u{39B}', '\u{39C}', '\u{39D}', '\u{39E}', '\u{39F}',
+    '\u{3A0}', '\u{3A1}', '\u{FFFD}', '\u{3A3}', '\u{3A4}', '\u{3A5}', '\u{3A6}', '\u{3A7}',
+    '\u{3A8}', '\u{3A9}', '\u{3AA}', '\u{3AB}', '\u{3AC}', '\u{3AD}', '\u{3AE}', '\u{3AF}',
+    '\u{3B0}', '\u{3B1}', '\u{3B2}', '\u{3B3}', '\u{3B4}', '\u{3B5}', '\u{3B6}', '\u{3B7}',
+    '\u{3B8}', '\u{3B9}', '\u{3BA}', '\u{3BB}', '\u{3BC}', '\u{3BD}', '\u{3BE}', '\u{3BF}',
+    '\u{3C0}', '\u{3C1}', '\u{3C2}', '\u{3C3}', '\u{3C4}', '\u{3C5}', '\u{3C6}', '\u{3C7}',
+    '\u{3C8}', '\u{3C9}', '\u{3CA}', '\u{3CB}', '\u{3CC}', '\u{3CD}', '\u{3CE}', '\u{FFFD}',
+];
+
+static ISO_8859_8: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{FFFD}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{A5}', '\u{A6}', '\u{A7}',
+    '\u{A8}', '\u{A9}', '\u{D7}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{B4}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{B8}', '\u{B9}', '\u{F7}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}',
+    '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{2017}',
+    '\u{5D0}', '\u{5D1}', '\u{5D2}', '\u{5D3}', '\u{5D4}', '\u{5D5}', '\u{5D6}', '\u{5D7}',
+    '\u{5D8}', '\u{5D9}', '\u{5DA}', '\u{5DB}', '\u{5DC}', '\u{5DD}', '\u{5DE}', '\u{5DF}',
+    '\u{5E0}', '\u{5E1}', '\u{5E2}', '\u{5E3}', '\u{5E4}', '\u{5E5}', '\u{5E6}', '\u{5E7}',
+    '\u{5E8}', '\u{5E9}', '\u{5EA}', '\u{FFFD}', '\u{FFFD}', '\u{200E}', '\u{200F}', '\u{FFFD}',
+];
+
+static ISO_8859_10: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{104}', '\u{112}', '\u{122}', '\u{12A}', '\u{128}', '\u{136}', '\u{A7}',
+    '\u{13B}', '\u{110}', '\u{160}', '\u{166}', '\u{17D}', '\u{AD}', '\u{16A}', '\u{14A}',
+    '\u{B0}', '\u{105}', '\u{113}', '\u{123}', '\u{12B}', '\u{129}', '\u{137}', '\u{B7}',
+    '\u{13C}', '\u{111}', '\u{161}', '\u{167}', '\u{17E}', '\u{2015}', '\u{16B}', '\u{14B}',
+    '\u{100}', '\u{C1}', '\u{C2}', '\u{C3}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{12E}',
+    '\u{10C}', '\u{C9}', '\u{118}', '\u{CB}', '\u{116}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{D0}', '\u{145}', '\u{14C}', '\u{D3}', '\u{D4}', '\u{D5}', '\u{D6}', '\u{168}',
+    '\u{D8}', '\u{172}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{DD}', '\u{DE}', '\u{DF}',
+    '\u{101}', '\u{E1}', '\u{E2}', '\u{E3}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{12F}',
+    '\u{10D}', '\u{E9}', '\u{119}', '\u{EB}', '\u{117}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{F0}', '\u{146}', '\u{14D}', '\u{F3}', '\u{F4}', '\u{F5}', '\u{F6}', '\u{169}',
+    '\u{F8}', '\u{173}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{FD}', '\u{FE}', '\u{138}',
+];
+
+static ISO_8859_13: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{201D}', '\u{A2}', '\u{A3}', '\u{A4}', '\u{201E}', '\u{A6}', '\u{A7}',
+    '\u{D8}', '\u{A9}', '\u{156}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{C6}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{201C}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{F8}', '\u{B9}', '\u{157}', '\u{BB}', '\u{BC}', '\u{BD}', '\u{BE}', '\u{E6}',
+    '\u{104}', '\u{12E}', '\u{100}', '\u{106}', '\u{C4}', '\u{C5}', '\u{118}', '\u{112}',
+    '\u{10C}', '\u{C9}', '\u{179}', '\u{116}', '\u{122}', '\u{136}', '\u{12A}', '\u{13B}',
+    '\u{160}', '\u{143}', '\u{145}', '\u{D3}', '\u{14C}', '\u{D5}', '\u{D6}', '\u{D7}',
+    '\u{172}', '\u{141}', '\u{15A}', '\u{16A}', '\u{DC}', '\u{17B}', '\u{17D}', '\u{DF}',
+    '\u{105}', '\u{12F}', '\u{101}', '\u{107}', '\u{E4}', '\u{E5}', '\u{119}', '\u{113}',
+    '\u{10D}', '\u{E9}', '\u{17A}', '\u{117}', '\u{123}', '\u{137}', '\u{12B}', '\u{13C}',
+    '\u{161}', '\u{144}', '\u{146}', '\u{F3}', '\u{14D}', '\u{F5}', '\u{F6}', '\u{F7}',
+    '\u{173}', '\u{142}', '\u{15B}', '\u{16B}', '\u{FC}', '\u{17C}', '\u{17E}', '\u{2019}',
+];
+
+static ISO_8859_14: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{1E02}', '\u{1E03}', '\u{A3}', '\u{10A}', '\u{10B}', '\u{1E0A}', '\u{A7}',
+    '\u{1E80}', '\u{A9}', '\u{1E82}', '\u{1E0B}', '\u{1EF2}', '\u{AD}', '\u{AE}', '\u{178}',
+    '\u{1E1E}', '\u{1E1F}', '\u{120}', '\u{121}', '\u{1E40}', '\u{1E41}', '\u{B6}', '\u{1E56}',
+    '\u{1E81}', '\u{1E57}', '\u{1E83}', '\u{1E60}', '\u{1EF3}', '\u{1E84}', '\u{1E85}', '\u{1E61}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{C3}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{CC}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{174}', '\u{D1}', '\u{D2}', '\u{D3}', '\u{D4}', '\u{D5}', '\u{D6}', '\u{1E6A}',
+    '\u{D8}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{DD}', '\u{176}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{E3}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{EC}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{175}', '\u{F1}', '\u{F2}', '\u{F3}', '\u{F4}', '\u{F5}', '\u{F6}', '\u{1E6B}',
+    '\u{F8}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{FD}', '\u{177}', '\u{FF}',
+];
+
+static ISO_8859_15: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{A1}', '\u{A2}', '\u{A3}', '\u{20AC}', '\u{A5}', '\u{160}', '\u{A7}',
+    '\u{161}', '\u{A9}', '\u{AA}', '\u{AB}', '\u{AC}', '\u{AD}', '\u{AE}', '\u{AF}',
+    '\u{B0}', '\u{B1}', '\u{B2}', '\u{B3}', '\u{17D}', '\u{B5}', '\u{B6}', '\u{B7}',
+    '\u{17E}', '\u{B9}', '\u{BA}', '\u{BB}', '\u{152}', '\u{153}', '\u{178}', '\u{BF}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{C3}', '\u{C4}', '\u{C5}', '\u{C6}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{CC}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{D0}', '\u{D1}', '\u{D2}', '\u{D3}', '\u{D4}', '\u{D5}', '\u{D6}', '\u{D7}',
+    '\u{D8}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{DD}', '\u{DE}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{E3}', '\u{E4}', '\u{E5}', '\u{E6}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{EC}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{F0}', '\u{F1}', '\u{F2}', '\u{F3}', '\u{F4}', '\u{F5}', '\u{F6}', '\u{F7}',
+    '\u{F8}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{FD}', '\u{FE}', '\u{FF}',
+];
+
+static ISO_8859_16: [char; 128] = [
+    '\u{80}', '\u{81}', '\u{82}', '\u{83}', '\u{84}', '\u{85}', '\u{86}', '\u{87}',
+    '\u{88}', '\u{89}', '\u{8A}', '\u{8B}', '\u{8C}', '\u{8D}', '\u{8E}', '\u{8F}',
+    '\u{90}', '\u{91}', '\u{92}', '\u{93}', '\u{94}', '\u{95}', '\u{96}', '\u{97}',
+    '\u{98}', '\u{99}', '\u{9A}', '\u{9B}', '\u{9C}', '\u{9D}', '\u{9E}', '\u{9F}',
+    '\u{A0}', '\u{104}', '\u{105}', '\u{141}', '\u{20AC}', '\u{201E}', '\u{160}', '\u{A7}',
+    '\u{161}', '\u{A9}', '\u{218}', '\u{AB}', '\u{179}', '\u{AD}', '\u{17A}', '\u{17B}',
+    '\u{B0}', '\u{B1}', '\u{10C}', '\u{142}', '\u{17D}', '\u{201D}', '\u{B6}', '\u{B7}',
+    '\u{17E}', '\u{10D}', '\u{219}', '\u{BB}', '\u{152}', '\u{153}', '\u{178}', '\u{17C}',
+    '\u{C0}', '\u{C1}', '\u{C2}', '\u{102}', '\u{C4}', '\u{106}', '\u{C6}', '\u{C7}',
+    '\u{C8}', '\u{C9}', '\u{CA}', '\u{CB}', '\u{CC}', '\u{CD}', '\u{CE}', '\u{CF}',
+    '\u{110}', '\u{143}', '\u{D2}', '\u{D3}', '\u{D4}', '\u{150}', '\u{D6}', '\u{15A}',
+    '\u{170}', '\u{D9}', '\u{DA}', '\u{DB}', '\u{DC}', '\u{118}', '\u{21A}', '\u{DF}',
+    '\u{E0}', '\u{E1}', '\u{E2}', '\u{103}', '\u{E4}', '\u{107}', '\u{E6}', '\u{E7}',
+    '\u{E8}', '\u{E9}', '\u{EA}', '\u{EB}', '\u{EC}', '\u{ED}', '\u{EE}', '\u{EF}',
+    '\u{111}', '\u{144}', '\u{F2}', '\u{F3}', '\u{F4}', '\u{151}', '\u{F6}', '\u{15B}',
+    '\u{171}', '\u{F9}', '\u{FA}', '\u{FB}', '\u{FC}', '\u{119}', '\u{21B}', '\u{FF}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    #[test]
+    fn labels() {
+        assert_eq!(Encoding::for_label(" UTF8 "), Some(Encoding::Utf8));
+        assert_eq!(Encoding::for_label("latin1"), Some(Encoding::Windows(1252)));
+        assert_eq!(Encoding::for_label("iso-8859-2"), Some(Encoding::Iso8859(2)));
+        assert_eq!(Encoding::for_label("utf-7"), None);
+        assert_eq!(Encoding::Iso8859(2).name(), "ISO-8859-2");
+        assert_eq!(Encoding::for_label("X-User-Defined"), Some(Encoding::XUserDefined));
+        assert_eq!(Encoding::XUserDefined.name(), "x-user-defined");
+    }
+
+    #[test]
+    fn byte_order_mark_wins() {
+        assert_eq!(sniff(b"\xEF\xBB\xBF<meta charset=latin2>", Some("latin1")), (Encoding::Utf8, Confidence::Certain));
+        assert_eq!(decode(b"\xFF\xFEa\x00<\x00", None), ("a<".to_string(), Encoding::Utf16Le));
+        assert_eq!(decode(b"\xFE\xFF\x00a", None), ("a".to_string(), Encoding::Utf16Be));
+    }
+
+    #[test]
+    fn transport_layer_then_meta() {
+        assert_eq!(sniff(b"<meta charset=latin2>", Some("text/html; charset=windows-1251")), (Encoding::Windows(1251), Confidence::Certain));
+        assert_eq!(sniff(b"<meta charset=latin2>", None), (Encoding::Iso8859(2), Confidence::Tentative));
+        let pragma = b"<!-- <meta charset=latin2> --><meta http-equiv=Content-Type content='text/html; charset=windows-1250'><meta charset=latin3>";
+        assert_eq!(sniff(pragma, None).0, Encoding::Windows(1250));
+        /* UTF-16 in a meta is taken as UTF-8, the bytes are ASCII compatible after all */
+        assert_eq!(sniff(b"<meta charset=utf-16>", None).0, Encoding::Utf8);
+        /* Only a meta makes x-user-defined windows-1252, the transport layer label is used as is */
+        assert_eq!(sniff(b"<meta charset=x-user-defined>", None).0, Encoding::Windows(1252));
+        assert_eq!(sniff(b"", Some("x-user-defined")), (Encoding::XUserDefined, Confidence::Certain));
+    }
+
+    #[test]
+    fn fallbacks() {
+        assert_eq!(sniff("<p>caf\u{E9}".as_bytes(), None), (Encoding::Utf8, Confidence::Tentative));
+        assert_eq!(sniff(b"<p>caf\xE9", None), (Encoding::Windows(1252), Confidence::Tentative));
+        assert_eq!(Encoding::Windows(1252).decode(b"\x80\xE9"), "\u{20AC}\u{E9}");
+        assert_eq!(Encoding::Utf8.decode(b"a\xFFb"), "a\u{FFFD}b");
+        assert_eq!(Encoding::XUserDefined.decode(b"a\x80\xE9\xFF"), "a\u{F780}\u{F7E9}\u{F7FF}");
+    }
+
+    #[test]
+    fn parsed_bytes_record_their_encoding() {
+        let document = DOM::parse_bytes(b"<meta charset=windows-1251><p>\xCF\xF0\xE8").unwrap();
+        assert_eq!(document.encoding(), Encoding::Windows(1251));
+        assert_eq!(document.to_node(document.root()).text(), "\u{41F}\u{440}\u{438}");
+    }
+}
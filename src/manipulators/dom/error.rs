@@ -0,0 +1,214 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use super::Position;
+
+/* Tokenizer errors use the codes of https://html.spec.whatwg.org/#parse-errors,
+the spec leaves tree construction errors unnamed so those use codes of our own */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    AbruptClosingOfEmptyComment,
+    AbruptDoctypePublicIdentifier,
+    AbruptDoctypeSystemIdentifier,
+    AbsenceOfDigitsInNumericCharacterReference,
+    CdataInHtmlContent,
+    CharacterReferenceOutsideUnicodeRange,
+    ControlCharacterInInputStream,
+    ControlCharacterReference,
+    DuplicateAttribute,
+    EndTagWithAttributes,
+    EndTagWithTrailingSolidus,
+    EofBeforeTagName,
+    EofInCdata,
+    EofInComment,
+    EofInDoctype,
+    EofInScriptHtmlCommentLikeText,
+    EofInTag,
+    IncorrectlyClosedComment,
+    IncorrectlyOpenedComment,
+    InvalidCharacterSequenceAfterDoctypeName,
+    InvalidFirstCharacterOfTagName,
+    MissingAttributeValue,
+    MissingDoctypeName,
+    MissingDoctypePublicIdentifier,
+    MissingDoctypeSystemIdentifier,
+    MissingEndTagName,
+    MissingQuoteBeforeDoctypePublicIdentifier,
+    MissingQuoteBeforeDoctypeSystemIdentifier,
+    MissingSemicolonAfterCharacterReference,
+    MissingWhitespaceAfterDoctypePublicKeyword,
+    MissingWhitespaceAfterDoctypeSystemKeyword,
+    MissingWhitespaceBeforeDoctypeName,
+    MissingWhitespaceBetweenAttributes,
+    MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+    NestedComment,
+    NoncharacterCharacterReference,
+    NoncharacterInInputStream,
+    NonVoidHtmlElementStartTagWithTrailingSolidus,
+    NullCharacterReference,
+    SurrogateCharacterReference,
+    UnexpectedCharacterAfterDoctypeSystemIdentifier,
+    UnexpectedCharacterInAttributeName,
+    UnexpectedCharacterInUnquotedAttributeValue,
+    UnexpectedEqualsSignBeforeAttributeName,
+    UnexpectedNullCharacter,
+    UnexpectedQuestionMarkInsteadOfTagName,
+    UnexpectedSolidusInTag,
+    UnknownNamedCharacterReference,
+
+    /* Tree construction */
+    MissingDoctype,
+    UnexpectedDoctype,
+    UnexpectedStartTag,
+    /* A start tag like <a> inside an open <a>, which closes the open one first */
+    UnexpectedStartTagImpliesEndTag,
+    UnexpectedEndTag,
+    /* An end tag that also closes elements opened after its element */
+    MisnestedEndTag,
+    UnexpectedText,
+    EofInElement,
+    NonConformingDoctype,
+}
+impl ErrorCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::AbruptClosingOfEmptyComment => "abrupt-closing-of-empty-comment",
+            ErrorCode::AbruptDoctypePublicIdentifier => "abrupt-doctype-public-identifier",
+            ErrorCode::AbruptDoctypeSystemIdentifier => "abrupt-doctype-system-identifier",
+            ErrorCode::AbsenceOfDigitsInNumericCharacterReference => "absence-of-digits-in-numeric-character-reference",
+            ErrorCode::CdataInHtmlContent => "cdata-in-html-content",
+            ErrorCode::CharacterReferenceOutsideUnicodeRange => "character-reference-outside-unicode-range",
+            ErrorCode::ControlCharacterInInputStream => "control-character-in-input-stream",
+            ErrorCode::ControlCharacterReference => "control-character-reference",
+            ErrorCode::DuplicateAttribute => "duplicate-attribute",
+            ErrorCode::EndTagWithAttributes => "end-tag-with-attributes",
+            ErrorCode::EndTagWithTrailingSolidus => "end-tag-with-trailing-solidus",
+            ErrorCode::EofBeforeTagName => "eof-before-tag-name",
+            ErrorCode::EofInCdata => "eof-in-cdata",
+            ErrorCode::EofInComment => "eof-in-comment",
+            ErrorCode::EofInDoctype => "eof-in-doctype",
+            ErrorCode::EofInScriptHtmlCommentLikeText => "eof-in-script-html-comment-like-text",
+            ErrorCode::EofInTag => "eof-in-tag",
+            ErrorCode::IncorrectlyClosedComment => "incorrectly-closed-comment",
+            ErrorCode::IncorrectlyOpenedComment => "incorrectly-opened-comment",
+            ErrorCode::InvalidCharacterSequenceAfterDoctypeName => "invalid-character-sequence-after-doctype-name",
+            ErrorCode::InvalidFirstCharacterOfTagName => "invalid-first-character-of-tag-name",
+            ErrorCode::MissingAttributeValue => "missing-attribute-value",
+            ErrorCode::MissingDoctypeName => "missing-doctype-name",
+            ErrorCode::MissingDoctypePublicIdentifier => "missing-doctype-public-identifier",
+            ErrorCode::MissingDoctypeSystemIdentifier => "missing-doctype-system-identifier",
+            ErrorCode::MissingEndTagName => "missing-end-tag-name",
+            ErrorCode::MissingQuoteBeforeDoctypePublicIdentifier => "missing-quote-before-doctype-public-identifier",
+            ErrorCode::MissingQuoteBeforeDoctypeSystemIdentifier => "missing-quote-before-doctype-system-identifier",
+            ErrorCode::MissingSemicolonAfterCharacterReference => "missing-semicolon-after-character-reference",
+            ErrorCode::MissingWhitespaceAfterDoctypePublicKeyword => "missing-whitespace-after-doctype-public-keyword",
+            ErrorCode::MissingWhitespaceAfterDoctypeSystemKeyword => "missing-whitespace-after-doctype-system-keyword",
+            ErrorCode::MissingWhitespaceBeforeDoctypeName => "missing-whitespace-before-doctype-name",
+            ErrorCode::MissingWhitespaceBetweenAttributes => "missing-whitespace-between-attributes",
+            ErrorCode::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers => "missing-whitespace-between-doctype-public-and-system-identifiers",
+            ErrorCode::NestedComment => "nested-comment",
+            ErrorCode::NoncharacterCharacterReference => "noncharacter-character-reference",
+            ErrorCode::NoncharacterInInputStream => "noncharacter-in-input-stream",
+            ErrorCode::NonVoidHtmlElementStartTagWithTrailingSolidus => "non-void-html-element-start-tag-with-trailing-solidus",
+            ErrorCode::NullCharacterReference => "null-character-reference",
+            ErrorCode::SurrogateCharacterReference => "surrogate-character-reference",
+            ErrorCode::UnexpectedCharacterAfterDoctypeSystemIdentifier => "unexpected-character-after-doctype-system-identifier",
+            ErrorCode::UnexpectedCharacterInAttributeName => "unexpected-character-in-attribute-name",
+            ErrorCode::UnexpectedCharacterInUnquotedAttributeValue => "unexpected-character-in-unquoted-attribute-value",
+            ErrorCode::UnexpectedEqualsSignBeforeAttributeName => "unexpected-equals-sign-before-attribute-name",
+            ErrorCode::UnexpectedNullCharacter => "unexpected-null-character",
+            ErrorCode::UnexpectedQuestionMarkInsteadOfTagName => "unexpected-question-mark-instead-of-tag-name",
+            ErrorCode::UnexpectedSolidusInTag => "unexpected-solidus-in-tag",
+            ErrorCode::UnknownNamedCharacterReference => "unknown-named-character-reference",
+            ErrorCode::MissingDoctype => "missing-doctype",
+            ErrorCode::UnexpectedDoctype => "unexpected-doctype",
+            ErrorCode::UnexpectedStartTag => "unexpected-start-tag",
+            ErrorCode::UnexpectedStartTagImpliesEndTag => "unexpected-start-tag-implies-end-tag",
+            ErrorCode::UnexpectedEndTag => "unexpected-end-tag",
+            ErrorCode::MisnestedEndTag => "misnested-end-tag",
+            ErrorCode::UnexpectedText => "unexpected-text",
+            ErrorCode::EofInElement => "eof-in-element",
+            ErrorCode::NonConformingDoctype => "non-conforming-doctype",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseError {
+    pub code: ErrorCode,
+    pub position: Position,
+}
+impl ParseError {
+    pub fn new(code: ErrorCode, position: Position) -> ParseError {
+        ParseError { code, position }
+    }
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.code.code(), self.position.line, self.position.column)
+    }
+}
+impl std::error::Error for ParseError {}
+
+/* Everything the parse entry points can fail with */
+#[derive(Debug)]
+pub enum Error {
+    /* Only returned in strict mode */
+    Parse(ParseError),
+    Io(std::io::Error),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(error) => write!(f, "parse error: {}", error),
+            Error::Io(error) => write!(f, "could not read input: {}", error),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(error) => Some(error),
+            Error::Io(error) => Some(error),
+        }
+    }
+}
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Error {
+        Error::Parse(error)
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+/* Returned by the Document mutation methods when a change would break the tree */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyError {
+    /* Only elements have children and attributes */
+    NotAnElement,
+    /* A node cannot be inserted into itself or one of its descendants */
+    Cycle,
+    /* The document node cannot be moved, removed or replaced */
+    Root,
+    /* The node has no parent to insert next to */
+    NoParent,
+    /* The tag or attribute name could not be written as markup */
+    InvalidName,
+}
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            HierarchyError::NotAnElement => "the node is not an element",
+            HierarchyError::Cycle => "a node cannot be inserted into itself or its descendants",
+            HierarchyError::Root => "the document node cannot be moved",
+            HierarchyError::NoParent => "the node has no parent",
+            HierarchyError::InvalidName => "the name is not a valid tag or attribute name",
+        };
+        write!(f, "{}", message)
+    }
+}
+impl std::error::Error for HierarchyError {}
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+
+use super::serializer::{escape, write_attribute};
+use super::{is_whitespace, Document, Edge, Element, FormatOptions, Node, NodeId, Quote};
+
+/* Pieces of inline content, a line can only be broken where there is a Space */
+enum Piece {
+    Markup(String),
+    Space,
+}
+
+/* The children of an element laid out as blocks */
+enum Item {
+    /* On a line of its own */
+    Block(NodeId),
+    /* Inline content, as units that are separated by whitespace */
+    Run(Vec<String>),
+}
+
+struct Formatter<'a> {
+    document: &'a Document,
+    options: &'a FormatOptions,
+    output: String,
+    /* Elements rendered as blocks and the elements that contain one */
+    blocks: HashSet<NodeId>,
+}
+
+pub(super) fn format(document: &Document, options: &FormatOptions) -> String {
+    let mut blocks = HashSet::new();
+    for edge in document.traverse(document.root()) {
+        if let Edge::Exit(id) = edge {
+            let block = match document.element(id) {
+                Some(element) if element.tag().is_element() => {
+                    element.tag().is_block() || element.tag().name() == "head" || document.children(id).any(|child| blocks.contains(&child))
+                }
+                _ => false,
+            };
+            if block {
+                blocks.insert(id);
+            }
+        }
+    }
+    let mut formatter = Formatter { document, options, output: String::new(), blocks };
+    for item in formatter.items(document.root(), 0) {
+        formatter.item(item, 0);
+    }
+    return formatter.output;
+}
+
+impl<'a> Formatter<'a> {
+    fn indent(&self, depth: usize) -> String {
+        match self.options.use_tabs {
+            true => "\t".repeat(depth),
+            false => " ".repeat(depth * self.options.indent_width),
+        }
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        self.output.push_str(&self.indent(depth));
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn fits(&self, depth: usize, text: &str) -> bool {
+        depth * self.options.indent_width + text.chars().count() <= self.options.max_width && !text.contains('\n')
+    }
+
+    fn item(&mut self, item: Item, depth: usize) {
+        match item {
+            Item::Block(id) => self.block(id, depth),
+            Item::Run(units) => self.fill(&units, depth),
+        }
+    }
+
+    /* Comments, doctypes and metadata elements render nothing, so they only get a line of their own when
+    there is whitespace or a block on both sides, otherwise adding whitespace around them could show up */
+    fn items(&self, id: NodeId, depth: usize) -> Vec<Item> {
+        let document = self.document;
+        let children: Vec<NodeId> = document.children(id).collect();
+        let ends_with_whitespace = |id: NodeId| document.node(id).as_text().is_some_and(|text| text.ends_with(is_whitespace));
+        let starts_with_whitespace = |id: NodeId| document.node(id).as_text().is_some_and(|text| text.starts_with(is_whitespace));
+        let mut items = Vec::new();
+        let mut pieces = Vec::new();
+        let mut boundary_before = true;
+        for (index, &child) in children.iter().enumerate() {
+            let own_line = self.blocks.contains(&child)
+                || (self.is_invisible(child) && boundary_before && {
+                    let mut next = children[index + 1..].iter();
+                    loop {
+                        match next.next() {
+                            None => break true,
+                            Some(&next) if self.blocks.contains(&next) || starts_with_whitespace(next) => break true,
+                            Some(&next) if self.is_invisible(next) => continue,
+                            Some(_) => break false,
+                        }
+                    }
+                });
+            if own_line {
+                if let Some(run) = run(std::mem::take(&mut pieces)) {
+                    items.push(run);
+                }
+                items.push(Item::Block(child));
+            } else {
+                self.inline(child, depth, &mut pieces);
+            }
+            boundary_before = own_line || ends_with_whitespace(child);
+        }
+        if let Some(run) = run(pieces) {
+            items.push(run);
+        }
+        return items;
+    }
+
+    fn is_invisible(&self, id: NodeId) -> bool {
+        match self.document.node(id) {
+            Node::Element(element) => element.tag().is_metadata(),
+            Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) | Node::XML(_) | Node::PHP(_) => true,
+            Node::Text(_) | Node::CData(_) => false,
+        }
+    }
+
+    fn block(&mut self, id: NodeId, depth: usize) {
+        let element = match self.document.node(id) {
+            Node::Element(element) if element.tag().is_element() && !element.tag().preserves_whitespace() => element,
+            _ => {
+                let html = self.document.outer_html(id);
+                self.line(depth, &html);
+                return;
+            }
+        };
+        let start = start_tag(element, self.document.raw_entities);
+        let end = format!("</{}>", element.tag().name());
+        let wrap = element.attributes().is_some_and(|attributes| !attributes.is_empty()) && !self.fits(depth, &start);
+        let items = match element.tag().is_void() {
+            true => Vec::new(),
+            false => self.items(id, depth + 1),
+        };
+        if wrap {
+            self.line(depth, &format!("<{}", element.tag().name()));
+            for attribute in element.attributes().into_iter().flatten() {
+                let mut line = String::new();
+                write_attribute(&mut line, attribute, Quote::Double, self.document.raw_entities);
+                self.line(depth + 1, &line);
+            }
+            if element.tag().is_void() || items.is_empty() {
+                let close = if element.tag().is_void() { String::new() } else { end };
+                self.line(depth, &format!(">{}", close));
+                return;
+            }
+            self.line(depth, ">");
+        } else if element.tag().is_void() {
+            self.line(depth, &start);
+            return;
+        } else {
+            let single = match items.as_slice() {
+                [] => Some(String::new()),
+                [Item::Run(units)] => Some(units.join(" ")),
+                _ => None,
+            };
+            match single.map(|content| format!("{}{}{}", start, content, end)) {
+                Some(line) if self.fits(depth, &line) || items.is_empty() => {
+                    self.line(depth, &line);
+                    return;
+                }
+                _ => self.line(depth, &start),
+            }
+        }
+        for item in items {
+            self.item(item, depth + 1);
+        }
+        self.line(depth, &end);
+    }
+
+    /* Inline content keeps its markup, only the whitespace in it can become a line break */
+    fn inline(&self, id: NodeId, depth: usize, pieces: &mut Vec<Piece>) {
+        match self.document.node(id) {
+            Node::Text(text) => {
+                let mut word = String::new();
+                for ch in text.chars() {
+                    if !is_whitespace(ch) {
+                        word.push(ch);
+                        continue;
+                    }
+                    if !word.is_empty() {
+                        let mut markup = String::new();
+                        escape(&mut markup, &std::mem::take(&mut word), None, self.document.raw_entities);
+                        pieces.push(Piece::Markup(markup));
+                    }
+                    pieces.push(Piece::Space);
+                }
+                if !word.is_empty() {
+                    let mut markup = String::new();
+                    escape(&mut markup, &word, None, self.document.raw_entities);
+                    pieces.push(Piece::Markup(markup));
+                }
+            }
+            Node::Element(element) if element.tag().is_element() && !element.tag().preserves_whitespace() => {
+                let start = start_tag(element, self.document.raw_entities);
+                let wrap = element.attributes().is_some_and(|attributes| !attributes.is_empty()) && !self.fits(depth, &start);
+                match wrap {
+                    true => pieces.push(Piece::Markup(self.wrapped_start_tag(element, depth))),
+                    false => pieces.push(Piece::Markup(start)),
+                }
+                if !element.tag().is_void() {
+                    for child in self.document.children(id) {
+                        self.inline(child, depth, pieces);
+                    }
+                    pieces.push(Piece::Markup(format!("</{}>", element.tag().name())));
+                }
+            }
+            _ => pieces.push(Piece::Markup(self.document.outer_html(id))),
+        }
+    }
+
+    /* One attribute per line like block() does, for an inline start tag too long for a line of its own.
+    The whitespace is inside the tag, so it never shows up in the rendered text */
+    fn wrapped_start_tag(&self, element: &Element, depth: usize) -> String {
+        let mut tag = format!("<{}", element.tag().name());
+        for attribute in element.attributes().into_iter().flatten() {
+            tag.push('\n');
+            tag.push_str(&self.indent(depth + 1));
+            write_attribute(&mut tag, attribute, Quote::Double, self.document.raw_entities);
+        }
+        tag.push('\n');
+        tag.push_str(&self.indent(depth));
+        tag.push('>');
+        return tag;
+    }
+
+    /* Puts as many units on a line as fit, a unit that is too long gets a line of its own */
+    fn fill(&mut self, units: &[String], depth: usize) {
+        let start = depth * self.options.indent_width;
+        let mut line = String::new();
+        let mut column = start;
+        for unit in units {
+            let first_line = unit.split('\n').next().unwrap_or_default().chars().count();
+            if !line.is_empty() {
+                if column + 1 + first_line > self.options.max_width {
+                    self.line(depth, &std::mem::take(&mut line));
+                    column = start;
+                } else {
+                    line.push(' ');
+                    column += 1;
+                }
+            }
+            line.push_str(unit);
+            column = match unit.rfind('\n') {
+                Some(index) => unit[index + 1..].chars().count(),
+                None => column + first_line,
+            };
+        }
+        if !line.is_empty() {
+            self.line(depth, &line);
+        }
+    }
+}
+
+/* Joins markup that is not separated by whitespace into units, whitespace at the ends of a run never renders */
+fn run(pieces: Vec<Piece>) -> Option<Item> {
+    let mut units: Vec<String> = Vec::new();
+    let mut joined = false;
+    for piece in pieces {
+        match piece {
+            Piece::Space => joined = false,
+            Piece::Markup(markup) => {
+                match units.last_mut() {
+                    Some(last) if joined => last.push_str(&markup),
+                    _ => units.push(markup),
+                }
+                joined = true;
+            }
+        }
+    }
+    return match units.is_empty() {
+        true => None,
+        false => Some(Item::Run(units)),
+    };
+}
+
+fn start_tag(element: &Element, raw_entities: bool) -> String {
+    let mut tag = format!("<{}", element.tag().name());
+    for attribute in element.attributes().into_iter().flatten() {
+        tag.push(' ');
+        write_attribute(&mut tag, attribute, Quote::Double, raw_entities);
+    }
+    tag.push('>');
+    return tag;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manipulators::dom::{FormatOptions, DOM};
+
+    fn format(html: &str) -> String {
+        return DOM::format(&DOM::parse_str(html).unwrap());
+    }
+
+    const PAGE: &str = "<!DOCTYPE html><html><head><title>Title</title><meta charset=utf-8></head><body>\
+        <div class=\"container main\"><p>Some <b>bold</b> text, <a href=\"https://example.com/path?with=query&amp;and=more\" \
+        class=\"link external\" title=\"An external link\">a link</a> and more words that need to be wrapped because the line is long.</p>\
+        <ul><li>one</li><li>two <i>three</i></li></ul><pre>  keep\n    this</pre><!-- note --></div></body></html>";
+
+    #[test]
+    fn lines_fit_the_max_width() {
+        let formatted = format(PAGE);
+        for line in formatted.lines() {
+            assert!(line.chars().count() <= 80, "{:?} is too long in\n{}", line, formatted);
+        }
+        assert!(formatted.contains("<a\n"));
+        assert!(formatted.contains("\n          class=\"link external\"\n"));
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let once = format(PAGE);
+        assert_eq!(format(&once), once);
+        let narrow = FormatOptions { max_width: 30, ..FormatOptions::default() };
+        let once = DOM::format_with_options(&DOM::parse_str(PAGE).unwrap(), &narrow);
+        assert_eq!(DOM::format_with_options(&DOM::parse_str(&once).unwrap(), &narrow), once);
+    }
+
+    #[test]
+    fn rendered_text_is_kept() {
+        let document = DOM::parse_str(PAGE).unwrap();
+        let formatted = DOM::parse_str(&format(PAGE)).unwrap();
+        let words = |document: &crate::manipulators::dom::Document| {
+            let text = document.node(document.root()).text();
+            return text.split_whitespace().map(String::from).collect::<Vec<String>>();
+        };
+        assert_eq!(words(&formatted), words(&document));
+        assert!(format(PAGE).contains("<pre>  keep\n    this</pre>"));
+    }
+}
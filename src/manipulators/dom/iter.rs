@@ -0,0 +1,264 @@
+use super::{Document, Element, Node, NodeId};
+
+/* Yielded by the traverse iterators, every node is entered before its children and exited after them */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge<T> {
+    Enter(T),
+    Exit(T),
+}
+impl<T> Edge<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Edge<U> {
+        match self {
+            Edge::Enter(value) => Edge::Enter(f(value)),
+            Edge::Exit(value) => Edge::Exit(f(value)),
+        }
+    }
+}
+
+/* ---------- Document ---------- */
+
+impl Document {
+    pub fn children(&self, id: NodeId) -> Siblings<'_> {
+        Siblings { document: self, next: self.first_child(id), forward: true }
+    }
+
+    /* Nearest first, the node itself is not included */
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors { document: self, next: self.parent(id) }
+    }
+
+    pub fn following_siblings(&self, id: NodeId) -> Siblings<'_> {
+        Siblings { document: self, next: self.next_sibling(id), forward: true }
+    }
+
+    /* Nearest first */
+    pub fn preceding_siblings(&self, id: NodeId) -> Siblings<'_> {
+        Siblings { document: self, next: self.previous_sibling(id), forward: false }
+    }
+
+    /* Depth-first in document order, the node itself is not included */
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        let mut traverse = self.traverse(id);
+        traverse.next();
+        Descendants { traverse }
+    }
+
+    /* Depth-first, starting with Enter and ending with Exit of the node itself */
+    pub fn traverse(&self, id: NodeId) -> Traverse<'_> {
+        Traverse { document: self, root: id, next: Some(Edge::Enter(id)) }
+    }
+}
+
+pub struct Siblings<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+    forward: bool,
+}
+impl Iterator for Siblings<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = if self.forward { self.document.next_sibling(id) } else { self.document.previous_sibling(id) };
+        return Some(id);
+    }
+}
+
+pub struct Ancestors<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.document.parent(id);
+        return Some(id);
+    }
+}
+
+pub struct Traverse<'a> {
+    document: &'a Document,
+    root: NodeId,
+    next: Option<Edge<NodeId>>,
+}
+impl Iterator for Traverse<'_> {
+    type Item = Edge<NodeId>;
+
+    fn next(&mut self) -> Option<Edge<NodeId>> {
+        let edge = self.next?;
+        self.next = match edge {
+            Edge::Enter(id) => match self.document.first_child(id) {
+                Some(child) => Some(Edge::Enter(child)),
+                None => Some(Edge::Exit(id)),
+            },
+            Edge::Exit(id) if id == self.root => None,
+            Edge::Exit(id) => match self.document.next_sibling(id) {
+                Some(sibling) => Some(Edge::Enter(sibling)),
+                None => self.document.parent(id).map(Edge::Exit),
+            },
+        };
+        return Some(edge);
+    }
+}
+
+pub struct Descendants<'a> {
+    traverse: Traverse<'a>,
+}
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        loop {
+            match self.traverse.next()? {
+                Edge::Enter(id) => return Some(id),
+                Edge::Exit(id) if id == self.traverse.root => return None,
+                Edge::Exit(_) => {}
+            }
+        }
+    }
+}
+
+/* ---------- Owned trees ---------- */
+
+impl Node {
+    /* Depth-first, the node itself is not included */
+    pub fn descendants(&self) -> NodeDescendants<'_> {
+        NodeDescendants { traverse: NodeTraverse::new(self.child_nodes()) }
+    }
+
+    /* Depth-first, starting with Enter and ending with Exit of the node itself */
+    pub fn traverse(&self) -> NodeTraverse<'_> {
+        NodeTraverse::new(std::slice::from_ref(self))
+    }
+
+    fn child_nodes(&self) -> &[Node] {
+        match self {
+            Node::Element(element) => element.children(),
+            _ => &[],
+        }
+    }
+}
+
+impl Element {
+    /* Depth-first, the element itself is not included */
+    pub fn descendants(&self) -> NodeDescendants<'_> {
+        NodeDescendants { traverse: NodeTraverse::new(self.children()) }
+    }
+
+    /* Depth-first over the descendants, the element itself has no Enter and Exit since it is not a Node */
+    pub fn traverse(&self) -> NodeTraverse<'_> {
+        NodeTraverse::new(self.children())
+    }
+}
+
+pub struct NodeTraverse<'a> {
+    roots: std::slice::Iter<'a, Node>,
+    /* The entered nodes with the children that are left */
+    stack: Vec<(&'a Node, std::slice::Iter<'a, Node>)>,
+}
+impl<'a> NodeTraverse<'a> {
+    fn new(roots: &'a [Node]) -> NodeTraverse<'a> {
+        NodeTraverse { roots: roots.iter(), stack: Vec::new() }
+    }
+}
+impl<'a> Iterator for NodeTraverse<'a> {
+    type Item = Edge<&'a Node>;
+
+    fn next(&mut self) -> Option<Edge<&'a Node>> {
+        let next = match self.stack.last_mut() {
+            Some((_, children)) => children.next(),
+            None => self.roots.next(),
+        };
+        match next {
+            Some(node) => {
+                self.stack.push((node, node.child_nodes().iter()));
+                return Some(Edge::Enter(node));
+            }
+            None => {
+                let (node, _) = self.stack.pop()?;
+                return Some(Edge::Exit(node));
+            }
+        }
+    }
+}
+
+pub struct NodeDescendants<'a> {
+    traverse: NodeTraverse<'a>,
+}
+impl<'a> Iterator for NodeDescendants<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            if let Edge::Enter(node) = self.traverse.next()? {
+                return Some(node);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    /* Tag names for elements and the text for text nodes */
+    fn label(node: &Node) -> String {
+        match node {
+            Node::Element(element) => element.tag().name().to_string(),
+            node => node.as_text().unwrap_or_default().to_string(),
+        }
+    }
+
+    fn labels(document: &Document, ids: impl Iterator<Item = NodeId>) -> Vec<String> {
+        ids.map(|id| label(document.node(id))).collect()
+    }
+
+    fn edges(document: &Document, id: NodeId) -> Vec<String> {
+        let edge = |edge: Edge<NodeId>| match edge {
+            Edge::Enter(id) => format!("+{}", label(document.node(id))),
+            Edge::Exit(id) => format!("-{}", label(document.node(id))),
+        };
+        return document.traverse(id).map(edge).collect();
+    }
+
+    #[test]
+    fn document_iterators() {
+        let document = DOM::parse_fragment("<ul><li>a</li><li>b<i>c</i></li><li>d</li></ul>", "body").unwrap();
+        let ul = document.query_selector("ul").unwrap().unwrap();
+        let second = document.query_selector("li:nth-child(2)").unwrap().unwrap();
+        let i = document.query_selector("i").unwrap().unwrap();
+        assert_eq!(labels(&document, document.children(ul)), ["li", "li", "li"]);
+        assert_eq!(labels(&document, document.descendants(ul)), ["li", "a", "li", "b", "i", "c", "li", "d"]);
+        assert_eq!(labels(&document, document.ancestors(i)), ["li", "ul", ""]);
+        assert_eq!(labels(&document, document.following_siblings(second).chain(document.preceding_siblings(second))), ["li", "li"]);
+        assert_eq!(edges(&document, second), ["+li", "+b", "-b", "+i", "+c", "-c", "-i", "-li"]);
+    }
+
+    #[test]
+    fn traversal_stays_inside_the_subtree() {
+        let document = DOM::parse_fragment("<p>a</p><p>b</p>", "body").unwrap();
+        let first = document.query_selector("p").unwrap().unwrap();
+        let text = document.first_child(first).unwrap();
+        assert_eq!(edges(&document, text), ["+a", "-a"]);
+        assert_eq!(document.descendants(text).count(), 0);
+        assert_eq!(labels(&document, document.descendants(first)), ["a"]);
+    }
+
+    #[test]
+    fn owned_iterators() {
+        let document = DOM::parse_fragment("<div><p>a</p>b</div>", "body").unwrap();
+        let div = document.to_node(document.query_selector("div").unwrap().unwrap());
+        let descendants: Vec<String> = div.descendants().map(label).collect();
+        assert_eq!(descendants, ["p", "a", "b"]);
+        let edges: Vec<Edge<String>> = div.traverse().map(|edge| edge.map(label)).collect();
+        assert_eq!(edges.first(), Some(&Edge::Enter("div".to_string())));
+        assert_eq!(edges.last(), Some(&Edge::Exit("div".to_string())));
+        assert_eq!(edges.len(), 8);
+        let element = div.as_element().unwrap();
+        assert_eq!(element.traverse().count(), 6);
+        assert_eq!(element.descendants().map(label).collect::<Vec<String>>(), descendants);
+    }
+}
@@ -0,0 +1,946 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use super::{is_whitespace, Document, Element, Node, NodeId, Tag};
+
+/* A parsed selector list, see https://www.w3.org/TR/selectors-4/ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    selectors: Vec<ComplexSelector>,
+}
+
+/* Compounds joined by combinators, matched right to left */
+#[derive(Debug, Clone, PartialEq)]
+struct ComplexSelector {
+    /* Every compound with the combinator on its left, the first one only means something in :has() */
+    parts: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combinator {
+    /* Whitespace */
+    Descendant,
+    /* > */
+    Child,
+    /* + */
+    NextSibling,
+    /* ~ */
+    SubsequentSibling,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Compound {
+    simple: Vec<Simple>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Simple {
+    Universal,
+    /* Lowercase, HTML element names are matched case-insensitively */
+    Type(String),
+    Id(String),
+    Class(String),
+    Attribute(AttributeSelector),
+    PseudoClass(PseudoClass),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeSelector {
+    name: String,
+    /* None for [name], which only checks that the attribute is there */
+    value: Option<(Operator, String)>,
+    /* Set by the "i" flag in [name=value i] */
+    case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    /* = */
+    Equals,
+    /* ~= */
+    Includes,
+    /* |= */
+    DashMatch,
+    /* ^= */
+    Prefix,
+    /* $= */
+    Suffix,
+    /* *= */
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PseudoClass {
+    Root,
+    Scope,
+    Empty,
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
+    /* The selector of :nth-child(an+b of S) limits the siblings that are counted */
+    NthChild(Nth, Option<Selector>),
+    NthLastChild(Nth, Option<Selector>),
+    NthOfType(Nth),
+    NthLastOfType(Nth),
+    Not(Selector),
+    Is(Selector),
+    Where(Selector),
+    /* Relative selectors, their first combinator is relative to the element being matched */
+    Has(Vec<ComplexSelector>),
+}
+
+/* Matches the positions a*n+b for any n >= 0, positions start at 1 */
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Nth {
+    a: i32,
+    b: i32,
+}
+
+/* Compared ids first, then classes, then types, see https://www.w3.org/TR/selectors-4/#specificity-rules */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Specificity {
+    pub ids: u32,
+    /* Classes, attribute selectors and pseudo-classes */
+    pub classes: u32,
+    pub types: u32,
+}
+impl std::ops::Add for Specificity {
+    type Output = Specificity;
+
+    fn add(self, other: Specificity) -> Specificity {
+        Specificity { ids: self.ids + other.ids, classes: self.classes + other.classes, types: self.types + other.types }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorErrorKind {
+    Empty,
+    UnexpectedEnd,
+    UnexpectedCharacter(char),
+    ExpectedIdentifier,
+    ExpectedValue,
+    UnknownPseudoClass(String),
+    InvalidNth,
+}
+
+/* The offset is in bytes into the selector text */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+    pub kind: SelectorErrorKind,
+    pub offset: usize,
+}
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SelectorErrorKind::Empty => write!(f, "empty selector")?,
+            SelectorErrorKind::UnexpectedEnd => write!(f, "unexpected end of selector")?,
+            SelectorErrorKind::UnexpectedCharacter(ch) => write!(f, "unexpected character {:?}", ch)?,
+            SelectorErrorKind::ExpectedIdentifier => write!(f, "expected an identifier")?,
+            SelectorErrorKind::ExpectedValue => write!(f, "expected an identifier or a string")?,
+            SelectorErrorKind::UnknownPseudoClass(name) => write!(f, "unknown pseudo-class :{}", name)?,
+            SelectorErrorKind::InvalidNth => write!(f, "expected an+b, odd or even")?,
+        }
+        write!(f, " at offset {}", self.offset)
+    }
+}
+impl std::error::Error for SelectorError {}
+
+fn is_name_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_' || !ch.is_ascii()
+}
+
+fn is_name(ch: char) -> bool {
+    is_name_start(ch) || ch.is_ascii_digit() || ch == '-'
+}
+
+/* ---------- Parsing ---------- */
+
+struct Parser<'a> {
+    input: &'a str,
+    offset: usize,
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.offset..].chars().next()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.input[self.offset..].chars().nth(n)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.offset += ch.len_utf8();
+        return Some(ch);
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.offset += expected.len_utf8();
+            return true;
+        }
+        return false;
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.offset;
+        while self.peek().is_some_and(is_whitespace) {
+            self.offset += 1;
+        }
+        return self.offset > start;
+    }
+
+    fn error(&self, kind: SelectorErrorKind) -> SelectorError {
+        SelectorError { kind, offset: self.offset }
+    }
+
+    fn unexpected(&self) -> SelectorError {
+        match self.peek() {
+            Some(ch) => self.error(SelectorErrorKind::UnexpectedCharacter(ch)),
+            None => self.error(SelectorErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn selector_list(&mut self) -> Result<Vec<ComplexSelector>, SelectorError> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.complex_selector(Combinator::Descendant)?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    /* The argument of :has(), every selector may start with a combinator */
+    fn relative_selector_list(&mut self) -> Result<Vec<ComplexSelector>, SelectorError> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let combinator = self.combinator().unwrap_or(Combinator::Descendant);
+            self.skip_whitespace();
+            selectors.push(self.complex_selector(combinator)?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn complex_selector(&mut self, first: Combinator) -> Result<ComplexSelector, SelectorError> {
+        let mut parts = vec![(first, self.compound()?)];
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = match self.combinator() {
+                Some(combinator) => {
+                    self.skip_whitespace();
+                    combinator
+                }
+                None if whitespace && !matches!(self.peek(), None | Some(',' | ')')) => Combinator::Descendant,
+                None => return Ok(ComplexSelector { parts }),
+            };
+            parts.push((combinator, self.compound()?));
+        }
+    }
+
+    fn combinator(&mut self) -> Option<Combinator> {
+        let combinator = match self.peek()? {
+            '>' => Combinator::Child,
+            '+' => Combinator::NextSibling,
+            '~' => Combinator::SubsequentSibling,
+            _ => return None,
+        };
+        self.next();
+        return Some(combinator);
+    }
+
+    fn compound(&mut self) -> Result<Compound, SelectorError> {
+        let mut simple = Vec::new();
+        match self.peek() {
+            Some('*') => {
+                self.next();
+                simple.push(Simple::Universal);
+            }
+            Some(ch) if is_name_start(ch) || ch == '-' || ch == '\\' => {
+                simple.push(Simple::Type(self.identifier()?.to_ascii_lowercase()));
+            }
+            _ => {}
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.next();
+                    simple.push(Simple::Id(self.identifier()?));
+                }
+                Some('.') => {
+                    self.next();
+                    simple.push(Simple::Class(self.identifier()?));
+                }
+                Some('[') => {
+                    self.next();
+                    simple.push(Simple::Attribute(self.attribute()?));
+                }
+                Some(':') => {
+                    self.next();
+                    simple.push(Simple::PseudoClass(self.pseudo_class()?));
+                }
+                _ => break,
+            }
+        }
+        if simple.is_empty() {
+            return Err(match self.peek() {
+                Some(',' | ')') => self.error(SelectorErrorKind::Empty),
+                _ => self.unexpected(),
+            });
+        }
+        return Ok(Compound { simple });
+    }
+
+    /* [name], [name=value] and [name=value i] after the "[" */
+    fn attribute(&mut self) -> Result<AttributeSelector, SelectorError> {
+        self.skip_whitespace();
+        let name = self.identifier()?.to_ascii_lowercase();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(AttributeSelector { name, value: None, case_insensitive: false });
+        }
+        let operator = match self.next() {
+            Some('=') => Operator::Equals,
+            Some(ch @ ('~' | '|' | '^' | '$' | '*')) if self.eat('=') => match ch {
+                '~' => Operator::Includes,
+                '|' => Operator::DashMatch,
+                '^' => Operator::Prefix,
+                '$' => Operator::Suffix,
+                _ => Operator::Substring,
+            },
+            Some(ch) => {
+                self.offset -= ch.len_utf8();
+                return Err(self.unexpected());
+            }
+            None => return Err(self.error(SelectorErrorKind::UnexpectedEnd)),
+        };
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.next();
+                self.string(quote)?
+            }
+            Some(ch) if is_name(ch) || ch == '\\' => self.identifier()?,
+            Some(_) => return Err(self.error(SelectorErrorKind::ExpectedValue)),
+            None => return Err(self.error(SelectorErrorKind::UnexpectedEnd)),
+        };
+        self.skip_whitespace();
+        let mut case_insensitive = false;
+        match self.peek() {
+            Some('i' | 'I') => {
+                self.next();
+                case_insensitive = true;
+            }
+            Some('s' | 'S') => {
+                self.next();
+            }
+            _ => {}
+        }
+        self.skip_whitespace();
+        if !self.eat(']') {
+            return Err(self.unexpected());
+        }
+        return Ok(AttributeSelector { name, value: Some((operator, value)), case_insensitive });
+    }
+
+    /* After the ":" */
+    fn pseudo_class(&mut self) -> Result<PseudoClass, SelectorError> {
+        let start = self.offset;
+        let name = self.identifier()?.to_ascii_lowercase();
+        let unknown = |name: String| SelectorError { kind: SelectorErrorKind::UnknownPseudoClass(name), offset: start };
+        if !self.eat('(') {
+            return match name.as_str() {
+                "root" => Ok(PseudoClass::Root),
+                "scope" => Ok(PseudoClass::Scope),
+                "empty" => Ok(PseudoClass::Empty),
+                "first-child" => Ok(PseudoClass::FirstChild),
+                "last-child" => Ok(PseudoClass::LastChild),
+                "only-child" => Ok(PseudoClass::OnlyChild),
+                "first-of-type" => Ok(PseudoClass::FirstOfType),
+                "last-of-type" => Ok(PseudoClass::LastOfType),
+                "only-of-type" => Ok(PseudoClass::OnlyOfType),
+                _ => Err(unknown(name)),
+            };
+        }
+        self.skip_whitespace();
+        let pseudo_class = match name.as_str() {
+            "nth-child" => PseudoClass::NthChild(self.nth()?, self.of_selector()?),
+            "nth-last-child" => PseudoClass::NthLastChild(self.nth()?, self.of_selector()?),
+            "nth-of-type" => PseudoClass::NthOfType(self.nth()?),
+            "nth-last-of-type" => PseudoClass::NthLastOfType(self.nth()?),
+            "not" => PseudoClass::Not(Selector { selectors: self.selector_list()? }),
+            "is" => PseudoClass::Is(Selector { selectors: self.selector_list()? }),
+            "where" => PseudoClass::Where(Selector { selectors: self.selector_list()? }),
+            "has" => PseudoClass::Has(self.relative_selector_list()?),
+            _ => return Err(unknown(name)),
+        };
+        self.skip_whitespace();
+        if !self.eat(')') {
+            return Err(self.unexpected());
+        }
+        return Ok(pseudo_class);
+    }
+
+    /* See https://www.w3.org/TR/css-syntax-3/#anb-microsyntax */
+    fn nth(&mut self) -> Result<Nth, SelectorError> {
+        let start = self.offset;
+        let invalid = SelectorError { kind: SelectorErrorKind::InvalidNth, offset: start };
+        let rest = &self.input[self.offset..];
+        for (keyword, nth) in [("odd", Nth { a: 2, b: 1 }), ("even", Nth { a: 2, b: 0 })] {
+            if rest.get(..keyword.len()).is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+                && !rest[keyword.len()..].starts_with(is_name)
+            {
+                self.offset += keyword.len();
+                return Ok(nth);
+            }
+        }
+        let sign = match self.peek() {
+            Some('-') => -1,
+            _ => 1,
+        };
+        if matches!(self.peek(), Some('-' | '+')) {
+            self.next();
+        }
+        let digits = self.integer()?;
+        if !self.eat('n') && !self.eat('N') {
+            return match digits {
+                Some(b) => Ok(Nth { a: 0, b: sign * b }),
+                None => Err(invalid),
+            };
+        }
+        let a = sign * digits.unwrap_or(1);
+        self.skip_whitespace();
+        let sign = match self.peek() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return Ok(Nth { a, b: 0 }),
+        };
+        self.next();
+        self.skip_whitespace();
+        return match self.integer()? {
+            Some(b) => Ok(Nth { a, b: sign * b }),
+            None => Err(invalid),
+        };
+    }
+
+    /* None without digits, an error when the number does not fit */
+    fn integer(&mut self) -> Result<Option<i32>, SelectorError> {
+        let start = self.offset;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.offset += 1;
+        }
+        if start == self.offset {
+            return Ok(None);
+        }
+        return match self.input[start..self.offset].parse() {
+            Ok(integer) => Ok(Some(integer)),
+            Err(_) => Err(SelectorError { kind: SelectorErrorKind::InvalidNth, offset: start }),
+        };
+    }
+
+    /* The optional "of S" of :nth-child() and :nth-last-child() */
+    fn of_selector(&mut self) -> Result<Option<Selector>, SelectorError> {
+        self.skip_whitespace();
+        let rest = &self.input[self.offset..];
+        if rest.get(..2).is_some_and(|word| word.eq_ignore_ascii_case("of")) && rest[2..].starts_with(is_whitespace) {
+            self.offset += 2;
+            return Ok(Some(Selector { selectors: self.selector_list()? }));
+        }
+        return Ok(None);
+    }
+
+    /* See https://www.w3.org/TR/css-syntax-3/#consume-name, ids, classes and types are identifiers which cannot start with a digit */
+    fn name(&mut self) -> Result<String, SelectorError> {
+        let mut name = String::new();
+        while let Some(ch) = self.peek() {
+            if is_name(ch) {
+                self.next();
+                name.push(ch);
+            } else if ch == '\\' {
+                name.push(self.escape()?);
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            return Err(self.error(SelectorErrorKind::ExpectedIdentifier));
+        }
+        return Ok(name);
+    }
+
+    fn identifier(&mut self) -> Result<String, SelectorError> {
+        let start = match (self.peek(), self.peek_at(1)) {
+            (Some('-'), Some(ch)) => is_name_start(ch) || ch == '-' || ch == '\\',
+            (Some(ch), _) => is_name_start(ch) || ch == '\\',
+            (None, _) => false,
+        };
+        if !start {
+            return Err(self.error(SelectorErrorKind::ExpectedIdentifier));
+        }
+        return self.name();
+    }
+
+    /* The quote has been consumed */
+    fn string(&mut self, quote: char) -> Result<String, SelectorError> {
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error(SelectorErrorKind::UnexpectedEnd)),
+                Some(ch) if ch == quote => {
+                    self.next();
+                    return Ok(value);
+                }
+                Some('\\') if self.peek_at(1) == Some('\n') => {
+                    self.offset += 2;
+                }
+                Some('\\') => value.push(self.escape()?),
+                Some(ch) => {
+                    self.next();
+                    value.push(ch);
+                }
+            }
+        }
+    }
+
+    /* "\" followed by up to six hex digits and an optional space, or by any other character */
+    fn escape(&mut self) -> Result<char, SelectorError> {
+        self.next();
+        let mut hex = String::new();
+        while hex.len() < 6 && self.peek().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+            hex.push(self.next().unwrap_or_default());
+        }
+        if hex.is_empty() {
+            return self.next().ok_or(self.error(SelectorErrorKind::UnexpectedEnd));
+        }
+        self.eat(' ');
+        let code = u32::from_str_radix(&hex, 16).unwrap_or(0xFFFD);
+        return Ok(match char::from_u32(code) {
+            Some('\0') | None => '\u{FFFD}',
+            Some(ch) => ch,
+        });
+    }
+}
+
+impl Selector {
+    pub fn parse(input: &str) -> Result<Selector, SelectorError> {
+        if input.trim_matches(is_whitespace).is_empty() {
+            return Err(SelectorError { kind: SelectorErrorKind::Empty, offset: 0 });
+        }
+        let mut parser = Parser { input, offset: 0 };
+        let selectors = parser.selector_list()?;
+        if parser.offset < input.len() {
+            return Err(parser.unexpected());
+        }
+        return Ok(Selector { selectors });
+    }
+
+    /* The highest specificity in the list */
+    pub fn specificity(&self) -> Specificity {
+        self.selectors.iter().map(ComplexSelector::specificity).max().unwrap_or_default()
+    }
+
+    /* The highest specificity of the selectors in the list that match the element, for ranking matches */
+    pub fn matching_specificity(&self, document: &Document, id: NodeId) -> Option<Specificity> {
+        let context = Context::new(document, None);
+        self.selectors.iter().filter(|selector| selector.matches(context, id)).map(ComplexSelector::specificity).max()
+    }
+
+    /* ---------- Matching ---------- */
+
+    pub fn matches(&self, document: &Document, id: NodeId) -> bool {
+        self.matches_in(Context::new(document, None), id)
+    }
+
+    fn matches_in(&self, context: Context, id: NodeId) -> bool {
+        self.selectors.iter().any(|selector| selector.matches(context, id))
+    }
+}
+
+/* :scope is the element that a query runs on, or the document element. :root is the document element */
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    document: &'a Document,
+    scope: Option<NodeId>,
+    root: Option<NodeId>,
+}
+impl<'a> Context<'a> {
+    fn new(document: &'a Document, scope: Option<NodeId>) -> Context<'a> {
+        let root = document.document_element();
+        let scope = scope.filter(|&id| is_element(document, id)).or(root);
+        Context { document, scope, root }
+    }
+}
+
+fn is_element(document: &Document, id: NodeId) -> bool {
+    document.element(id).is_some_and(|element| element.tag().is_element())
+}
+
+/* The elements that a combinator can reach from the element on its right, nearest first */
+fn related<'a>(document: &'a Document, combinator: Combinator, id: NodeId) -> Box<dyn Iterator<Item = NodeId> + 'a> {
+    match combinator {
+        Combinator::Descendant => Box::new(document.ancestors(id)),
+        Combinator::Child => Box::new(document.parent(id).into_iter()),
+        Combinator::NextSibling => Box::new(document.preceding_siblings(id).filter(move |&sibling| is_element(document, sibling)).take(1)),
+        Combinator::SubsequentSibling => Box::new(document.preceding_siblings(id).filter(move |&sibling| is_element(document, sibling))),
+    }
+}
+
+impl ComplexSelector {
+    fn matches(&self, context: Context, id: NodeId) -> bool {
+        self.matches_part(context, id, self.parts.len() - 1, None)
+    }
+
+    /* The anchor is the element that a relative selector of :has() is relative to */
+    fn matches_part(&self, context: Context, id: NodeId, index: usize, anchor: Option<NodeId>) -> bool {
+        let (combinator, compound) = &self.parts[index];
+        if !compound.matches(context, id) {
+            return false;
+        }
+        if index == 0 {
+            return match anchor {
+                Some(anchor) => related(context.document, *combinator, id).any(|other| other == anchor),
+                None => true,
+            };
+        }
+        related(context.document, *combinator, id).any(|other| self.matches_part(context, other, index - 1, anchor))
+    }
+
+    /* Relative selectors can only reach the descendants and the following siblings of the anchor */
+    fn matches_relative(&self, context: Context, anchor: NodeId) -> bool {
+        let document = context.document;
+        let candidates: Box<dyn Iterator<Item = NodeId>> = match self.parts[0].0 {
+            Combinator::Descendant | Combinator::Child => Box::new(document.descendants(anchor)),
+            Combinator::NextSibling | Combinator::SubsequentSibling => Box::new(
+                document.following_siblings(anchor).flat_map(|sibling| std::iter::once(sibling).chain(document.descendants(sibling))),
+            ),
+        };
+        let last = self.parts.len() - 1;
+        return candidates.into_iter().any(|id| self.matches_part(context, id, last, Some(anchor)));
+    }
+
+    fn specificity(&self) -> Specificity {
+        self.parts.iter().flat_map(|(_, compound)| &compound.simple).map(Simple::specificity).fold(Specificity::default(), |sum, specificity| sum + specificity)
+    }
+}
+
+impl Compound {
+    fn matches(&self, context: Context, id: NodeId) -> bool {
+        let element = match context.document.element(id) {
+            Some(element) if element.tag().is_element() => element,
+            _ => return false,
+        };
+        self.simple.iter().all(|simple| simple.matches(context, id, element))
+    }
+}
+
+impl Simple {
+    fn matches(&self, context: Context, id: NodeId, element: &Element) -> bool {
+        match self {
+            Simple::Universal => true,
+            Simple::Type(name) => element.tag().name().eq_ignore_ascii_case(name),
+            Simple::Id(id) => element.attribute("id") == Some(id.as_str()),
+            Simple::Class(class) => element.attribute("class").is_some_and(|value| value.split(is_whitespace).any(|item| item == class)),
+            Simple::Attribute(selector) => selector.matches(element),
+            Simple::PseudoClass(pseudo_class) => pseudo_class.matches(context, id, element),
+        }
+    }
+
+    fn specificity(&self) -> Specificity {
+        match self {
+            Simple::Universal => Specificity::default(),
+            Simple::Type(_) => Specificity { types: 1, ..Specificity::default() },
+            Simple::Id(_) => Specificity { ids: 1, ..Specificity::default() },
+            Simple::Class(_) | Simple::Attribute(_) => Specificity { classes: 1, ..Specificity::default() },
+            Simple::PseudoClass(pseudo_class) => pseudo_class.specificity(),
+        }
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, context: Context, id: NodeId, element: &Element) -> bool {
+        let document = context.document;
+        let same_type = |sibling: NodeId| document.element(sibling).is_some_and(|sibling| sibling.tag().name() == element.tag().name());
+        match self {
+            PseudoClass::Root => context.root == Some(id),
+            PseudoClass::Scope => context.scope == Some(id),
+            /* Comments and processing instructions do not count, whitespace does */
+            PseudoClass::Empty => document.children(id).all(|child| match document.node(child) {
+                Node::Element(_) => false,
+                Node::Text(text) | Node::CData(text) => text.is_empty(),
+                _ => true,
+            }),
+            PseudoClass::FirstChild => position(document, id, false, |_| true) == 1,
+            PseudoClass::LastChild => position(document, id, true, |_| true) == 1,
+            PseudoClass::OnlyChild => position(document, id, false, |_| true) == 1 && position(document, id, true, |_| true) == 1,
+            PseudoClass::FirstOfType => position(document, id, false, same_type) == 1,
+            PseudoClass::LastOfType => position(document, id, true, same_type) == 1,
+            PseudoClass::OnlyOfType => position(document, id, false, same_type) == 1 && position(document, id, true, same_type) == 1,
+            PseudoClass::NthChild(nth, of) | PseudoClass::NthLastChild(nth, of) => {
+                let from_end = matches!(self, PseudoClass::NthLastChild(..));
+                match of {
+                    Some(of) => of.matches_in(context, id) && nth.matches(position(document, id, from_end, |sibling| of.matches_in(context, sibling))),
+                    None => nth.matches(position(document, id, from_end, |_| true)),
+                }
+            }
+            PseudoClass::NthOfType(nth) => nth.matches(position(document, id, false, same_type)),
+            PseudoClass::NthLastOfType(nth) => nth.matches(position(document, id, true, same_type)),
+            PseudoClass::Not(selector) => !selector.matches_in(context, id),
+            PseudoClass::Is(selector) | PseudoClass::Where(selector) => selector.matches_in(context, id),
+            PseudoClass::Has(selectors) => selectors.iter().any(|selector| selector.matches_relative(context, id)),
+        }
+    }
+
+    fn specificity(&self) -> Specificity {
+        let class = Specificity { classes: 1, ..Specificity::default() };
+        match self {
+            PseudoClass::Where(_) => Specificity::default(),
+            PseudoClass::Not(selector) | PseudoClass::Is(selector) => selector.specificity(),
+            PseudoClass::Has(selectors) => selectors.iter().map(ComplexSelector::specificity).max().unwrap_or_default(),
+            PseudoClass::NthChild(_, Some(of)) | PseudoClass::NthLastChild(_, Some(of)) => class + of.specificity(),
+            _ => class,
+        }
+    }
+}
+
+/* The position of the element among its element siblings that are counted, starting at 1 */
+fn position(document: &Document, id: NodeId, from_end: bool, counted: impl Fn(NodeId) -> bool) -> i32 {
+    let siblings = match from_end {
+        true => document.following_siblings(id),
+        false => document.preceding_siblings(id),
+    };
+    return 1 + siblings.filter(|&sibling| is_element(document, sibling) && counted(sibling)).count() as i32;
+}
+
+impl Nth {
+    fn matches(&self, position: i32) -> bool {
+        if self.a == 0 {
+            return position == self.b;
+        }
+        /* Wide enough that position - b cannot overflow */
+        let n = i64::from(position) - i64::from(self.b);
+        return n % i64::from(self.a) == 0 && n / i64::from(self.a) >= 0;
+    }
+}
+
+impl AttributeSelector {
+    fn matches(&self, element: &Element) -> bool {
+        let actual = match element.attribute(&self.name) {
+            Some(actual) => actual,
+            None => return false,
+        };
+        let (operator, expected) = match &self.value {
+            Some(value) => value,
+            None => return true,
+        };
+        let (actual, expected) = match self.case_insensitive {
+            true => (actual.to_lowercase(), expected.to_lowercase()),
+            false => (actual.to_string(), expected.clone()),
+        };
+        match operator {
+            Operator::Equals => actual == expected,
+            Operator::Includes => !expected.is_empty() && !expected.contains(is_whitespace) && actual.split(is_whitespace).any(|item| item == expected),
+            Operator::DashMatch => actual == expected || actual.starts_with(&format!("{}-", expected)),
+            Operator::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+            Operator::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+            Operator::Substring => !expected.is_empty() && actual.contains(&expected),
+        }
+    }
+}
+
+/* ---------- Queries ---------- */
+
+impl Document {
+    /* The first element of the document that matches, in document order */
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<NodeId>, SelectorError> {
+        let selector = Selector::parse(selectors)?;
+        return Ok(self.select(self.root(), &selector).next());
+    }
+
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<NodeId>, SelectorError> {
+        let selector = Selector::parse(selectors)?;
+        return Ok(self.select(self.root(), &selector).collect());
+    }
+
+    /* The descendants of the scope that match, for selectors that are used more than once */
+    pub fn select<'a>(&'a self, scope: NodeId, selector: &'a Selector) -> impl Iterator<Item = NodeId> + 'a {
+        self.select_in(Context::new(self, Some(scope)), scope, selector)
+    }
+
+    fn select_in<'a>(&'a self, context: Context<'a>, scope: NodeId, selector: &'a Selector) -> impl Iterator<Item = NodeId> + 'a {
+        self.descendants(scope).filter(move |&id| selector.matches_in(context, id))
+    }
+}
+
+impl Element {
+    /* Only the descendants of the element are searched, the element itself never matches */
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<&Element>, SelectorError> {
+        let selector = Selector::parse(selectors)?;
+        let (document, scope) = self.to_document();
+        let context = self.context(&document, scope);
+        return Ok(document.select_in(context, scope, &selector).next().map(|id| self.resolve(&document, scope, id)));
+    }
+
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<&Element>, SelectorError> {
+        let selector = Selector::parse(selectors)?;
+        let (document, scope) = self.to_document();
+        let context = self.context(&document, scope);
+        return Ok(document.select_in(context, scope, &selector).map(|id| self.resolve(&document, scope, id)).collect());
+    }
+
+    /* Matching needs parent and sibling links, so the element is copied into a document */
+    fn to_document(&self) -> (Document, NodeId) {
+        let mut document = Document::new();
+        let scope = document.create_node(Node::Element(self.clone()));
+        let root = document.root();
+        document.append(root, scope);
+        return (document, scope);
+    }
+
+    /* The element is not part of a document, so nothing is :root unless it is a whole document from Document::into_root */
+    fn context<'a>(&self, document: &'a Document, scope: NodeId) -> Context<'a> {
+        let mut context = Context::new(document, Some(scope));
+        context.root = match self.tag() {
+            Tag::Root => document.children(scope).find(|&child| is_element(document, child)),
+            _ => None,
+        };
+        return context;
+    }
+
+    /* Finds the element that the node of the copy was made from */
+    fn resolve(&self, document: &Document, scope: NodeId, id: NodeId) -> &Element {
+        let mut path: Vec<usize> = Vec::new();
+        let mut current = id;
+        while current != scope {
+            path.push(document.preceding_siblings(current).count());
+            current = document.parent(current).unwrap_or(scope);
+        }
+        let mut element = self;
+        for &index in path.iter().rev() {
+            element = match &element.children()[index] {
+                Node::Element(child) => child,
+                _ => unreachable!("only elements are on the path to a match"),
+            };
+        }
+        return element;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    #[test]
+    fn ids_must_be_identifiers() {
+        assert_eq!(Selector::parse("#1a").unwrap_err(), SelectorError { kind: SelectorErrorKind::ExpectedIdentifier, offset: 1 });
+        assert_eq!(Selector::parse("p#-1").unwrap_err().offset, 2);
+        assert!(Selector::parse("#a1").is_ok());
+        assert!(Selector::parse("#\\31 a").is_ok());
+        let document = DOM::parse_str("<p id=1a>").unwrap();
+        assert_eq!(document.query_selector_all("#\\31 a").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn root_of_an_element_query_is_not_the_element() {
+        let document = DOM::parse_str("<div><p>a</p><section><p>b</p></section></div>").unwrap();
+        let div = document.query_selector("div").unwrap().unwrap();
+        let div = document.to_node(div);
+        let div = div.as_element().unwrap();
+        assert!(div.query_selector(":root > p").unwrap().is_none());
+        assert!(div.query_selector(":root p").unwrap().is_none());
+        assert_eq!(div.query_selector_all(":scope > p").unwrap().len(), 1);
+        let root = document.into_root();
+        assert_eq!(root.query_selector_all(":root p").unwrap().len(), 2);
+        assert_eq!(root.query_selector(":root").unwrap().map(|element| element.tag().name()), Some("html"));
+    }
+
+    fn texts(document: &Document, selectors: &str) -> Vec<String> {
+        let ids = document.query_selector_all(selectors).unwrap();
+        return ids.into_iter().map(|id| document.to_node(id).text()).collect();
+    }
+
+    fn specificity(selectors: &str) -> (u32, u32, u32) {
+        let specificity = Selector::parse(selectors).unwrap().specificity();
+        return (specificity.ids, specificity.classes, specificity.types);
+    }
+
+    #[test]
+    fn specificity_of_selectors() {
+        assert_eq!(specificity("*"), (0, 0, 0));
+        assert_eq!(specificity("li"), (0, 0, 1));
+        assert_eq!(specificity("ul li.a[href]:first-child"), (0, 3, 2));
+        assert_eq!(specificity("#x > p + a ~ b"), (1, 0, 3));
+        assert_eq!(specificity("a, #x, .b"), (1, 0, 0));
+        assert_eq!(specificity(":not(#x, .b) :is(p, .c)"), (1, 1, 0));
+        assert_eq!(specificity(":where(#x, .b) p"), (0, 0, 1));
+        assert_eq!(specificity(":has(> #x)"), (1, 0, 0));
+        assert_eq!(specificity(":nth-child(2n of .a)"), (0, 2, 0));
+        assert!(Selector::parse("#a").unwrap().specificity() > Selector::parse(".a.b.c.d").unwrap().specificity());
+    }
+
+    #[test]
+    fn combinators() {
+        let document = DOM::parse_str("<div><p>1</p><span><p>2</p></span><p>3</p><b>4</b><p>5</p></div>").unwrap();
+        assert_eq!(texts(&document, "div p"), ["1", "2", "3", "5"]);
+        assert_eq!(texts(&document, "div > p"), ["1", "3", "5"]);
+        assert_eq!(texts(&document, "span + p"), ["3"]);
+        assert_eq!(texts(&document, "span ~ p"), ["3", "5"]);
+        assert_eq!(texts(&document, "p + b, span"), ["2", "4"]);
+    }
+
+    #[test]
+    fn structural_pseudo_classes() {
+        let document = DOM::parse_str("<ul><li>1</li><li class=a>2</li><li>3</li><li class=a>4</li><li class=a>5</li></ul><p></p><p> </p>").unwrap();
+        assert_eq!(texts(&document, "li:first-child, li:last-child"), ["1", "5"]);
+        assert_eq!(texts(&document, "li:nth-child(odd)"), ["1", "3", "5"]);
+        assert_eq!(texts(&document, "li:nth-child(-n+2)"), ["1", "2"]);
+        assert_eq!(texts(&document, "li:nth-last-child(2)"), ["4"]);
+        assert_eq!(texts(&document, "li:nth-child(2 of .a)"), ["4"]);
+        assert_eq!(texts(&document, "li:nth-last-child(1 of :not(.a))"), ["3"]);
+        assert_eq!(document.query_selector_all("p:empty").unwrap().len(), 1);
+        assert_eq!(document.query_selector_all("p:first-of-type").unwrap().len(), 1);
+        assert_eq!(document.query_selector_all("ul:only-of-type").unwrap().len(), 1);
+        assert_eq!(document.query_selector_all("li:only-child").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn logical_pseudo_classes() {
+        let document = DOM::parse_str("<div id=x><p class=a>1</p></div><div><p>2</p><em>3</em></div>").unwrap();
+        assert_eq!(texts(&document, "p:not(.a)"), ["2"]);
+        assert_eq!(texts(&document, ":is(#x, em) > *, em"), ["1", "3"]);
+        assert_eq!(texts(&document, "div:has(> em) p"), ["2"]);
+        assert_eq!(texts(&document, "div:has(.a)"), ["1"]);
+        assert_eq!(texts(&document, "p:has(+ em)"), ["2"]);
+    }
+
+    #[test]
+    fn nth_arithmetic_does_not_overflow() {
+        let document = DOM::parse_str("<ul><li>1</li><li>2</li></ul>").unwrap();
+        assert_eq!(texts(&document, "li:nth-child(n-2147483647)"), ["1", "2"]);
+        assert_eq!(texts(&document, "li:nth-child(-n+2147483647)"), ["1", "2"]);
+        assert_eq!(texts(&document, "li:nth-child(2147483647n+2)"), ["2"]);
+        let overflow = SelectorError { kind: SelectorErrorKind::InvalidNth, offset: 13 };
+        assert_eq!(Selector::parse(":nth-child(99999999999n)").unwrap_err().offset, 11);
+        assert_eq!(Selector::parse(":nth-child(n+99999999999)").unwrap_err(), overflow);
+        assert_eq!(Selector::parse(":nth-child(-99999999999)").unwrap_err().kind, SelectorErrorKind::InvalidNth);
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        assert!(Selector::parse("").is_err());
+        assert!(Selector::parse("p >").is_err());
+        assert!(Selector::parse("p,").is_err());
+        assert!(Selector::parse(":nth-child(x)").is_err());
+        assert!(Selector::parse(":unknown").is_err());
+    }
+}
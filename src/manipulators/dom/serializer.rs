@@ -0,0 +1,302 @@
+use super::{Attribute, Document, Edge, Element, Node, NodeId, Quote, SerializeOptions, Tag};
+
+/* Text inside these is written as is, see https://html.spec.whatwg.org/#serialising-html-fragments */
+fn is_raw(tag: &Tag) -> bool {
+    tag.is_raw_text() || tag.name() == "plaintext"
+}
+
+struct Serializer<'a> {
+    options: &'a SerializeOptions,
+    /* Text and attribute values keep their character references, see Document::raw_entities */
+    raw_entities: bool,
+    output: String,
+    /* Whether text is raw for every open element, starting with the parent of the serialized nodes */
+    raw: Vec<bool>,
+    /* Depth inside a void element, whose children are never written */
+    skip: usize,
+    /* Set right after <pre>, <textarea> and <listing>, see newline() */
+    newline: bool,
+}
+impl<'a> Serializer<'a> {
+    fn new(options: &'a SerializeOptions, parent: Option<&Tag>, raw_entities: bool) -> Serializer<'a> {
+        let raw = parent.is_some_and(is_raw);
+        Serializer { options, raw_entities, output: String::new(), raw: vec![raw], skip: 0, newline: false }
+    }
+
+    fn edge(&mut self, edge: Edge<&Node>) {
+        let newline = std::mem::take(&mut self.newline);
+        match edge {
+            Edge::Enter(Node::Element(_)) if self.skip > 0 => self.skip += 1,
+            /* Starts skipping the children of void elements */
+            Edge::Enter(Node::Element(element)) => self.skip = usize::from(!self.open(element)),
+            Edge::Exit(Node::Element(_)) if self.skip > 0 => self.skip -= 1,
+            Edge::Exit(Node::Element(element)) => self.close(element),
+            Edge::Enter(node) if self.skip == 0 => {
+                /* The parser drops the first newline after the start tag, so a newline that starts the text needs another one */
+                if newline && node.as_text().is_some_and(|text| text.starts_with('\n')) {
+                    self.output.push('\n');
+                }
+                self.node(node);
+            }
+            _ => {}
+        }
+    }
+
+    /* Writes the start tag, false for void elements which have no content and no end tag */
+    fn open(&mut self, element: &Element) -> bool {
+        self.start_tag(element);
+        if element.tag().is_void() {
+            return false;
+        }
+        let raw = match element.tag().is_element() {
+            true => is_raw(element.tag()),
+            false => self.raw.last().copied().unwrap_or(false),
+        };
+        self.raw.push(raw);
+        self.newline = matches!(element.tag().name(), "listing" | "pre" | "textarea");
+        return true;
+    }
+
+    fn close(&mut self, element: &Element) {
+        self.raw.pop();
+        self.end_tag(element);
+    }
+
+    /* Nodes other than elements */
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Text(text) if self.raw.last().copied().unwrap_or(false) => self.output.push_str(text),
+            Node::Text(text) => escape(&mut self.output, text, None, self.raw_entities),
+            Node::Comment(text) => {
+                self.output.push_str("<!--");
+                self.output.push_str(text);
+                self.output.push_str("-->");
+            }
+            Node::CData(text) => {
+                self.output.push_str("<![CDATA[");
+                self.output.push_str(text);
+                self.output.push_str("]]>");
+            }
+            /* Ended by "?>" like the tokenizer expects, not by ">" */
+            Node::ProcessingInstruction(instruction) => {
+                self.output.push_str("<?");
+                self.output.push_str(&instruction.target);
+                if !instruction.data.is_empty() {
+                    self.output.push(' ');
+                    self.output.push_str(&instruction.data);
+                }
+                self.output.push_str("?>");
+            }
+            Node::XML(text) | Node::PHP(text) => {
+                self.output.push_str("<?");
+                self.output.push_str(text);
+                self.output.push_str("?>");
+            }
+            /* The identifiers are kept, unlike in the specification, so reparsing gives the same quirks mode */
+            Node::Doctype(doctype) => {
+                self.output.push_str("<!DOCTYPE");
+                if let Some(name) = &doctype.name {
+                    self.output.push(' ');
+                    self.output.push_str(name);
+                }
+                match (&doctype.public_id, &doctype.system_id) {
+                    (Some(public_id), system_id) => {
+                        self.output.push_str(&format!(" PUBLIC \"{}\"", public_id));
+                        if let Some(system_id) = system_id {
+                            self.output.push_str(&format!(" \"{}\"", system_id));
+                        }
+                    }
+                    (None, Some(system_id)) => self.output.push_str(&format!(" SYSTEM \"{}\"", system_id)),
+                    (None, None) => {}
+                }
+                self.output.push('>');
+            }
+            Node::Element(_) => {}
+        }
+    }
+
+    /* Nothing is written for the root and the other pseudo tags, only their children */
+    fn start_tag(&mut self, element: &Element) {
+        if !element.tag().is_element() {
+            return;
+        }
+        self.output.push('<');
+        self.output.push_str(element.tag().name());
+        for attribute in element.attributes().into_iter().flatten() {
+            self.output.push(' ');
+            write_attribute(&mut self.output, attribute, self.options.quote, self.raw_entities);
+        }
+        self.output.push('>');
+    }
+
+    fn end_tag(&mut self, element: &Element) {
+        if element.tag().is_element() {
+            self.output.push_str("</");
+            self.output.push_str(element.tag().name());
+            self.output.push('>');
+        }
+    }
+}
+
+pub(super) fn write_attribute(output: &mut String, attribute: &Attribute, quote: Quote, raw_entities: bool) {
+    output.push_str(&attribute.name);
+    let value = attribute.value.as_str();
+    let quote = match quote {
+        Quote::Double => '"',
+        Quote::Single => '\'',
+        Quote::Minimal if value.is_empty() => return,
+        Quote::Minimal if value.contains(|ch: char| ch.is_ascii_whitespace() || "\"'=<>`".contains(ch)) => '"',
+        Quote::Minimal => {
+            output.push('=');
+            escape(output, value, None, raw_entities);
+            return;
+        }
+    };
+    output.push('=');
+    output.push(quote);
+    escape(output, value, Some(quote), raw_entities);
+    output.push(quote);
+}
+
+/* Text escapes "<" and ">", attribute values escape their quote instead and "<" and ">" as well,
+"&" is left alone when the text still has its character references */
+pub(super) fn escape(output: &mut String, text: &str, quote: Option<char>, raw_entities: bool) {
+    for ch in text.chars() {
+        match ch {
+            '&' if !raw_entities => output.push_str("&amp;"),
+            '\u{A0}' => output.push_str("&nbsp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' if quote == Some('"') => output.push_str("&quot;"),
+            '\'' if quote == Some('\'') => output.push_str("&#39;"),
+            ch => output.push(ch),
+        }
+    }
+}
+
+impl Element {
+    /* The element with its start and end tags. An element on its own does not know how it was parsed, so its text
+    is taken as decoded and "&" is escaped. For an element parsed with decode_entities disabled use the
+    _with_options variant with raw_entities set, or the Document methods */
+    pub fn outer_html(&self) -> String {
+        self.outer_html_with_options(&SerializeOptions::default())
+    }
+
+    pub fn outer_html_with_options(&self, options: &SerializeOptions) -> String {
+        let mut serializer = Serializer::new(options, None, options.raw_entities);
+        if serializer.open(self) {
+            for edge in self.traverse() {
+                serializer.edge(edge);
+            }
+            serializer.close(self);
+        }
+        return serializer.output;
+    }
+
+    /* Only the children of the element */
+    pub fn inner_html(&self) -> String {
+        self.inner_html_with_options(&SerializeOptions::default())
+    }
+
+    pub fn inner_html_with_options(&self, options: &SerializeOptions) -> String {
+        if self.tag().is_void() {
+            return String::new();
+        }
+        let mut serializer = Serializer::new(options, Some(self.tag()), options.raw_entities);
+        for edge in self.traverse() {
+            serializer.edge(edge);
+        }
+        return serializer.output;
+    }
+}
+
+impl Document {
+    pub fn outer_html(&self, id: NodeId) -> String {
+        self.outer_html_with_options(id, &SerializeOptions::default())
+    }
+
+    pub fn outer_html_with_options(&self, id: NodeId, options: &SerializeOptions) -> String {
+        let parent = self.parent(id).and_then(|parent| self.element(parent));
+        let mut serializer = Serializer::new(options, parent.map(Element::tag), self.raw_entities || options.raw_entities);
+        for edge in self.traverse(id) {
+            serializer.edge(edge.map(|id| self.node(id)));
+        }
+        return serializer.output;
+    }
+
+    pub fn inner_html(&self, id: NodeId) -> String {
+        self.inner_html_with_options(id, &SerializeOptions::default())
+    }
+
+    pub fn inner_html_with_options(&self, id: NodeId, options: &SerializeOptions) -> String {
+        if self.element(id).is_some_and(|element| element.tag().is_void()) {
+            return String::new();
+        }
+        let mut serializer = Serializer::new(options, self.element(id).map(Element::tag), self.raw_entities || options.raw_entities);
+        for child in self.children(id) {
+            for edge in self.traverse(child) {
+                serializer.edge(edge.map(|id| self.node(id)));
+            }
+        }
+        return serializer.output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::{ParseOptions, DOM};
+
+    fn serialize(html: &str, options: &ParseOptions) -> String {
+        let document = DOM::parse_str_with_options(html, options).unwrap();
+        return document.outer_html(document.root());
+    }
+
+    #[test]
+    fn round_trip() {
+        let html = "<!DOCTYPE html><html><head><title>a &lt; b</title></head><body><p class=\"x\" title='say \"hi\"'>a &amp; b&nbsp;c</p>\
+                    <pre>\n\nx</pre><script>if (a < b) {}</script><br><img src=a.png></body></html>";
+        let once = serialize(html, &ParseOptions::default());
+        assert_eq!(
+            once,
+            "<!DOCTYPE html><html><head><title>a &lt; b</title></head><body><p class=\"x\" title=\"say &quot;hi&quot;\">a &amp; b&nbsp;c</p>\
+             <pre>\n\nx</pre><script>if (a < b) {}</script><br><img src=\"a.png\"></body></html>"
+        );
+        assert_eq!(serialize(&once, &ParseOptions::default()), once);
+    }
+
+    #[test]
+    fn raw_entities_are_not_escaped_again() {
+        let options = ParseOptions { decode_entities: false, ..ParseOptions::default() };
+        let html = "<p title=\"a &amp; b\">a &amp; b &lt; c</p>";
+        let document = DOM::parse_fragment_with_options(html, "body", &options).unwrap();
+        assert_eq!(document.outer_html(document.root()), html);
+        assert_eq!(document.inner_html(document.first_child(document.root()).unwrap()), "a &amp; b &lt; c");
+        let decoded = DOM::parse_fragment(html, "body").unwrap();
+        assert_eq!(decoded.outer_html(decoded.root()), html);
+    }
+
+    #[test]
+    fn elements_take_raw_entities_from_the_options() {
+        let options = ParseOptions { decode_entities: false, ..ParseOptions::default() };
+        let html = "<p title=\"a &amp; b\">a &amp; b</p>";
+        let document = DOM::parse_fragment_with_options(html, "body", &options).unwrap();
+        let node = document.to_node(document.first_child(document.root()).unwrap());
+        let p = node.as_element().unwrap();
+        let raw = SerializeOptions { raw_entities: true, ..SerializeOptions::default() };
+        assert_eq!(p.outer_html_with_options(&raw), html);
+        assert_eq!(p.inner_html_with_options(&raw), "a &amp; b");
+        /* Without the option the references are escaped once more */
+        assert_eq!(p.outer_html(), "<p title=\"a &amp;amp; b\">a &amp;amp; b</p>");
+    }
+
+    #[test]
+    fn quotes() {
+        let document = DOM::parse_fragment("<input value=\"it's\" disabled><a href=x>y</a>", "body").unwrap();
+        let single = SerializeOptions { quote: Quote::Single, ..SerializeOptions::default() };
+        let minimal = SerializeOptions { quote: Quote::Minimal, ..SerializeOptions::default() };
+        let root = document.root();
+        assert_eq!(document.inner_html_with_options(root, &single), "<input value='it&#39;s' disabled=''><a href='x'>y</a>");
+        assert_eq!(document.inner_html_with_options(root, &minimal), "<input value=\"it's\" disabled><a href=x>y</a>");
+    }
+}
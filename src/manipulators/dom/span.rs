@@ -0,0 +1,106 @@
+/* A location in the source, offset is in bytes while line and column start at 1 and count characters */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/* The source range [start, end) a token, element or attribute came from */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start.offset == self.end.offset
+    }
+}
+
+/* The value span excludes the quotes and is None for attributes without a value like "disabled" */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeSpan {
+    pub name: Span,
+    pub value: Option<Span>,
+}
+
+/* Turns byte offsets into line and column numbers */
+pub struct Locator {
+    line_starts: Vec<usize>,
+    /* Offsets are requested close to each other, so columns are counted from the last one */
+    last: Position,
+}
+impl Locator {
+    pub fn new(src: &str) -> Locator {
+        /* A line ends with LF, CR LF or a lone CR */
+        let bytes = src.as_bytes();
+        let mut line_starts = vec![0];
+        for (offset, &byte) in bytes.iter().enumerate() {
+            if byte == b'\n' || (byte == b'\r' && bytes.get(offset + 1) != Some(&b'\n')) {
+                line_starts.push(offset + 1);
+            }
+        }
+        Locator {
+            line_starts,
+            last: Position { offset: 0, line: 1, column: 1 },
+        }
+    }
+
+    pub fn position(&mut self, src: &str, offset: usize) -> Position {
+        let offset = offset.min(src.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = if self.last.line != line {
+            1 + src[self.line_starts[line - 1]..offset].chars().count()
+        } else if self.last.offset <= offset {
+            self.last.column + src[self.last.offset..offset].chars().count()
+        } else {
+            self.last.column - src[offset..self.last.offset].chars().count()
+        };
+        let position = Position { offset, line, column };
+        self.last = position;
+        return position;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    #[test]
+    fn lines_and_columns_count_characters() {
+        let src = "h\u{E9}llo\nw\u{F6}rld\r\n!";
+        let mut locator = Locator::new(src);
+        assert_eq!(locator.position(src, 3), Position { offset: 3, line: 1, column: 3 });
+        assert_eq!(locator.position(src, 8), Position { offset: 8, line: 2, column: 2 });
+        assert_eq!(locator.position(src, 11), Position { offset: 11, line: 2, column: 4 });
+        /* Going back on the same line */
+        assert_eq!(locator.position(src, 7), Position { offset: 7, line: 2, column: 1 });
+        assert_eq!(locator.position(src, 15), Position { offset: 15, line: 3, column: 1 });
+        assert_eq!(locator.position(src, 99).offset, src.len());
+    }
+
+    #[test]
+    fn spans_of_elements_and_attributes() {
+        let html = "<p class=x>h\u{E9}llo</p>\n<br disabled>";
+        let document = DOM::parse_fragment(html, "body").unwrap();
+        let p = document.element(document.query_selector("p").unwrap().unwrap()).unwrap();
+        let span = p.span().unwrap();
+        assert_eq!(&html[span.start.offset..span.end.offset], "<p class=x>h\u{E9}llo</p>");
+        let attribute = p.attributes().unwrap().attribute("class").unwrap().span.unwrap();
+        assert_eq!(&html[attribute.name.start.offset..attribute.name.end.offset], "class");
+        assert_eq!(&html[attribute.value.unwrap().start.offset..attribute.value.unwrap().end.offset], "x");
+        let br = document.element(document.query_selector("br").unwrap().unwrap()).unwrap();
+        assert_eq!((br.span().unwrap().start.line, br.span().unwrap().start.column), (2, 1));
+        assert_eq!(br.attributes().unwrap().attribute("disabled").unwrap().span.unwrap().value, None);
+    }
+}
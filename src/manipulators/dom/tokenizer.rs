@@ -0,0 +1,1554 @@
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+
+use super::doctype::Doctype;
+use super::span::Locator;
+use super::{entities, is_whitespace, Attribute, AttributeSpan, Consumer, ErrorCode, ParseError, ParseOptions, Position, Span, Tag, Token, TokenType, DOM};
+
+/* States of the WHATWG tokenizer, see https://html.spec.whatwg.org/#tokenization */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Data,
+    Rcdata,
+    Rawtext,
+    ScriptData,
+    Plaintext,
+    TagOpen,
+    EndTagOpen,
+    TagName,
+    BeforeAttributeName,
+    AttributeName,
+    AfterAttributeName,
+    BeforeAttributeValue,
+    AttributeValueDoubleQuoted,
+    AttributeValueSingleQuoted,
+    AttributeValueUnquoted,
+    AfterAttributeValueQuoted,
+    SelfClosingStartTag,
+    BogusComment,
+    ProcessingInstruction,
+    MarkupDeclarationOpen,
+    CdataSection,
+    CommentStart,
+    CommentStartDash,
+    Comment,
+    CommentLessThanSign,
+    CommentLessThanSignBang,
+    CommentLessThanSignBangDash,
+    CommentLessThanSignBangDashDash,
+    CommentEndDash,
+    CommentEnd,
+    CommentEndBang,
+    RcdataLessThanSign,
+    RcdataEndTagOpen,
+    RcdataEndTagName,
+    RawtextLessThanSign,
+    RawtextEndTagOpen,
+    RawtextEndTagName,
+    ScriptDataLessThanSign,
+    ScriptDataEndTagOpen,
+    ScriptDataEndTagName,
+    ScriptDataEscapeStart,
+    ScriptDataEscapeStartDash,
+    ScriptDataEscaped,
+    ScriptDataEscapedDash,
+    ScriptDataEscapedDashDash,
+    ScriptDataEscapedLessThanSign,
+    ScriptDataEscapedEndTagOpen,
+    ScriptDataEscapedEndTagName,
+    ScriptDataDoubleEscapeStart,
+    ScriptDataDoubleEscaped,
+    ScriptDataDoubleEscapedDash,
+    ScriptDataDoubleEscapedDashDash,
+    ScriptDataDoubleEscapedLessThanSign,
+    ScriptDataDoubleEscapeEnd,
+    Doctype,
+    BeforeDoctypeName,
+    DoctypeName,
+    AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+}
+
+/* Byte offsets [start, end) in the source */
+type Range = (usize, usize);
+
+/* An attribute of the tag being tokenized, the value range is None for attributes without a value */
+struct PendingAttribute {
+    name: String,
+    value: String,
+    name_range: Range,
+    value_range: Option<Range>,
+}
+
+/* The content of these HTML elements is not markup, see https://html.spec.whatwg.org/#parsing-html-fragments */
+pub fn content_state(tag: &Tag) -> State {
+    match tag.name() {
+        "script" => State::ScriptData,
+        "plaintext" => State::Plaintext,
+        _ if tag.is_raw_text() => State::Rawtext,
+        _ if tag.is_escapable_raw_text() => State::Rcdata,
+        _ => State::Data,
+    }
+}
+
+pub struct Tokenizer {
+    consumer: Consumer,
+    options: ParseOptions,
+    state: State,
+    reconsume: bool,
+    current: Option<char>,
+    offset: usize,
+    /* Where the current character ends, CR LF is one character spanning two bytes */
+    current_end: usize,
+    tokens: VecDeque<Token>,
+    /* Set once the end of the input was tokenized */
+    finished: bool,
+
+    text: String,
+    text_start: usize,
+    text_end: usize,
+
+    token_start: usize,
+    tag_type: TokenType,
+    tag_name: String,
+    last_start_tag: String,
+    self_closing: bool,
+    data: String,
+    buffer: String,
+
+    locator: Locator,
+    attributes: Vec<PendingAttribute>,
+    attribute_start: usize,
+    value_start: usize,
+
+    doctype: Doctype,
+
+    errors: Vec<ParseError>,
+}
+impl Tokenizer {
+    pub fn new(consumer: Consumer, options: &ParseOptions) -> Tokenizer {
+        Tokenizer {
+            locator: Locator::new(consumer.slice(0, consumer.size())),
+            consumer,
+            options: options.clone(),
+            state: State::Data,
+            reconsume: false,
+            current: None,
+            offset: 0,
+            current_end: 0,
+            tokens: VecDeque::new(),
+            finished: false,
+            text: String::new(),
+            text_start: 0,
+            text_end: 0,
+            token_start: 0,
+            tag_type: TokenType::None,
+            tag_name: String::new(),
+            last_start_tag: String::new(),
+            self_closing: false,
+            data: String::new(),
+            buffer: String::new(),
+            attributes: Vec::new(),
+            attribute_start: 0,
+            value_start: 0,
+            doctype: Doctype::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    /* Fragments start inside the context element, whose end tag ends its raw text */
+    pub fn new_fragment(consumer: Consumer, options: &ParseOptions, context: &str) -> Tokenizer {
+        let mut tokenizer = Tokenizer::new(consumer, options);
+        tokenizer.last_start_tag = context.to_string();
+        return tokenizer;
+    }
+
+    /* Without a tree builder every start tag chooses the state of the content after it */
+    pub fn run(mut self) -> (Vec<Token>, Vec<ParseError>) {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token() {
+            if matches!(token.tag_type, TokenType::Open | TokenType::SelfClosing) {
+                self.switch_to(content_state(&token.tag));
+            }
+            tokens.push(token);
+        }
+        return (tokens, self.errors);
+    }
+
+    /* Tokens come one at a time, so the tree builder can switch the state before the next one */
+    pub fn next_token(&mut self) -> Option<Token> {
+        while self.tokens.is_empty() && !self.finished {
+            if !self.step() {
+                self.flush_text(self.text_end);
+                self.finished = true;
+            }
+        }
+        return self.tokens.pop_front();
+    }
+
+    pub fn switch_to(&mut self, state: State) {
+        self.state = state;
+    }
+
+    pub fn errors(self) -> Vec<ParseError> {
+        self.errors
+    }
+
+    /* Reports an error at the current character */
+    fn error(&mut self, code: ErrorCode) {
+        let position = self.position(self.offset);
+        self.errors.push(ParseError::new(code, position));
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        if self.reconsume {
+            self.reconsume = false;
+            return self.current;
+        }
+        if self.consumer.pos() >= self.consumer.size() {
+            self.offset = self.consumer.size();
+            self.current_end = self.consumer.size();
+            self.current = None;
+            return None;
+        }
+        self.offset = self.consumer.pos();
+        self.current = self.consumer.current();
+        self.consumer.eat();
+        /* CR LF and lone CR are read as LF, see https://html.spec.whatwg.org/#preprocessing-the-input-stream.
+        Offsets stay those of the source, so spans still point at the CR */
+        if self.current == Some('\r') {
+            if self.consumer.current() == Some('\n') {
+                self.consumer.eat();
+            }
+            self.current = Some('\n');
+        }
+        self.current_end = self.consumer.pos();
+        match self.current {
+            Some(ch) if ch.is_control() && !is_whitespace(ch) && ch != '\0' => self.error(ErrorCode::ControlCharacterInInputStream),
+            Some(ch) if matches!(ch as u32, 0xFDD0..=0xFDEF) || ch as u32 & 0xFFFE == 0xFFFE => self.error(ErrorCode::NoncharacterInInputStream),
+            _ => {}
+        }
+        return self.current;
+    }
+
+    fn reconsume_in(&mut self, state: State) {
+        self.reconsume = true;
+        self.state = state;
+    }
+
+    fn source(&self, from: usize, to: usize) -> String {
+        self.consumer.slice(from, to).to_string()
+    }
+
+    /* Position right after the last consumed character */
+    fn end(&self) -> usize {
+        self.current_end
+    }
+
+    fn position(&mut self, offset: usize) -> Position {
+        let src = self.consumer.slice(0, self.consumer.size());
+        return self.locator.position(src, offset);
+    }
+
+    fn span(&mut self, start: usize, end: usize) -> Span {
+        Span::new(self.position(start), self.position(end))
+    }
+
+    fn push_text(&mut self, ch: char) {
+        if self.text.is_empty() {
+            self.text_start = self.offset;
+        }
+        self.text.push(ch);
+        self.text_end = self.end();
+    }
+
+    fn push_text_str(&mut self, str: &str, start: usize, end: usize) {
+        if self.text.is_empty() {
+            self.text_start = start;
+        }
+        self.text.push_str(str);
+        self.text_end = end;
+    }
+
+    /* Decodes the character reference after a consumed "&", returns None when there is none */
+    fn consume_character_reference(&mut self, in_attribute: bool) -> Option<String> {
+        if !self.options.decode_entities {
+            return None;
+        }
+        /* References are ASCII, so the byte length equals the number of characters */
+        if let Some(code) = entities::reference_error(self.consumer.rest().as_bytes(), in_attribute) {
+            self.error(code);
+        }
+        let (length, decoded) = entities::match_reference(self.consumer.rest().as_bytes(), in_attribute)?;
+        self.consumer.skip(length);
+        return Some(decoded);
+    }
+
+    fn push_character_reference(&mut self) {
+        let start = self.offset;
+        match self.consume_character_reference(false) {
+            Some(decoded) => self.push_text_str(&decoded, start, self.consumer.pos()),
+            None => self.push_text('&'),
+        }
+    }
+
+    fn end_tag_open(&mut self, text_state: State, name_state: State) {
+        match self.consume() {
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                self.start_tag(TokenType::Close);
+                self.buffer = String::new();
+                self.reconsume_in(name_state);
+            }
+            _ => {
+                self.push_text_str("</", self.token_start, self.token_start + 2);
+                self.reconsume_in(text_state);
+            }
+        }
+    }
+
+    /* Only an end tag matching the last start tag ends raw text, anything else is text */
+    fn end_tag_name(&mut self, text_state: State) {
+        let appropriate = self.tag_name == self.last_start_tag;
+        match self.consume() {
+            Some(ch) if is_whitespace(ch) && appropriate => self.state = State::BeforeAttributeName,
+            Some('/') if appropriate => self.state = State::SelfClosingStartTag,
+            Some('>') if appropriate => self.emit_tag(),
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                self.tag_name.push(ch.to_ascii_lowercase());
+                self.buffer.push(ch);
+            }
+            _ => {
+                let text = format!("</{}", self.buffer);
+                let end = self.token_start + text.len();
+                self.push_text_str(&text, self.token_start, end);
+                self.reconsume_in(text_state);
+            }
+        }
+    }
+
+    fn less_than_sign(&mut self, text_state: State, end_tag_open_state: State) {
+        match self.consume() {
+            Some('/') => {
+                self.buffer = String::new();
+                self.state = end_tag_open_state;
+            }
+            _ => {
+                self.push_text_str("<", self.token_start, self.token_start + 1);
+                self.reconsume_in(text_state);
+            }
+        }
+    }
+
+    fn flush_text(&mut self, end: usize) {
+        if !self.text.is_empty() {
+            let raw = self.source(self.text_start, end);
+            let data = std::mem::take(&mut self.text);
+            let span = Some(self.span(self.text_start, end));
+            self.tokens.push_back(Token { tag: Tag::None, raw, tag_type: TokenType::Content, data, span, attributes: Vec::new(), doctype: None });
+        }
+    }
+
+    fn emit(&mut self, tag: Tag, tag_type: TokenType) {
+        self.flush_text(self.token_start);
+        let end = self.end();
+        let raw = self.source(self.token_start, end);
+        let data = std::mem::take(&mut self.data);
+        let start = self.position(self.token_start);
+        let mut attributes: Vec<Attribute> = Vec::new();
+        for pending in std::mem::take(&mut self.attributes) {
+            let name = self.span(pending.name_range.0, pending.name_range.1);
+            let value = pending.value_range.map(|(value_start, value_end)| self.span(value_start, value_end));
+            /* The first of duplicate attributes wins */
+            if !attributes.iter().any(|attribute| attribute.name == pending.name) {
+                let span = Some(AttributeSpan { name, value });
+                attributes.push(Attribute { name: pending.name, value: pending.value, span });
+            }
+        }
+        let span = Some(Span::new(start, self.position(end)));
+        self.tokens.push_back(Token { tag, raw, tag_type, data, span, attributes, doctype: None });
+    }
+
+    fn emit_tag(&mut self) {
+        if self.tag_type == TokenType::Close {
+            if !self.attributes.is_empty() {
+                self.error(ErrorCode::EndTagWithAttributes);
+            }
+            if self.self_closing {
+                self.error(ErrorCode::EndTagWithTrailingSolidus);
+            }
+        }
+        for index in 1..self.attributes.len() {
+            let attribute = &self.attributes[index];
+            if self.attributes[..index].iter().any(|other| other.name == attribute.name) {
+                let position = self.position(attribute.name_range.0);
+                self.errors.push(ParseError::new(ErrorCode::DuplicateAttribute, position));
+            }
+        }
+        let tag = DOM::parse_tag(&self.tag_name);
+        let tag_type = match self.tag_type {
+            TokenType::Open if self.self_closing => TokenType::SelfClosing,
+            ref tag_type => tag_type.clone(),
+        };
+        self.emit(tag, tag_type.clone());
+        self.state = State::Data;
+        if tag_type != TokenType::Close {
+            self.last_start_tag = std::mem::take(&mut self.tag_name);
+        }
+    }
+
+    fn emit_comment(&mut self) {
+        self.emit(Tag::Comment, TokenType::Comment);
+        self.state = State::Data;
+    }
+
+    fn emit_doctype(&mut self) {
+        let doctype = std::mem::take(&mut self.doctype);
+        self.emit(Tag::Doctype, TokenType::SelfClosing);
+        if let Some(token) = self.tokens.back_mut() {
+            token.doctype = Some(doctype);
+        }
+        self.state = State::Data;
+    }
+
+    /* The current character is the first one of the uppercase keyword, which is matched case-insensitively */
+    fn lookahead_keyword(&mut self, keyword: &str) -> bool {
+        let first = self.current.map(|ch| ch.to_ascii_uppercase());
+        if first != keyword.chars().next() || !self.consumer.lookahead(&keyword[1..], true) {
+            return false;
+        }
+        self.consumer.skip(keyword.len() - 1);
+        return true;
+    }
+
+    fn start_doctype_identifier(&mut self, public: bool, quote: char) {
+        let (identifier, state) = match (public, quote) {
+            (true, '"') => (&mut self.doctype.public_id, State::DoctypePublicIdentifierDoubleQuoted),
+            (true, _) => (&mut self.doctype.public_id, State::DoctypePublicIdentifierSingleQuoted),
+            (false, '"') => (&mut self.doctype.system_id, State::DoctypeSystemIdentifierDoubleQuoted),
+            (false, _) => (&mut self.doctype.system_id, State::DoctypeSystemIdentifierSingleQuoted),
+        };
+        *identifier = Some(String::new());
+        self.state = state;
+    }
+
+    /* Anything but a quote after the PUBLIC or SYSTEM keyword */
+    fn missing_doctype_identifier(&mut self, current: Option<char>, public: bool) {
+        self.doctype.force_quirks = true;
+        match current {
+            Some('>') => {
+                self.error(if public { ErrorCode::MissingDoctypePublicIdentifier } else { ErrorCode::MissingDoctypeSystemIdentifier });
+                self.emit_doctype();
+            }
+            None => {
+                self.error(ErrorCode::EofInDoctype);
+                self.emit_doctype();
+            }
+            Some(_) => {
+                self.error(if public { ErrorCode::MissingQuoteBeforeDoctypePublicIdentifier } else { ErrorCode::MissingQuoteBeforeDoctypeSystemIdentifier });
+                self.reconsume_in(State::BogusDoctype);
+            }
+        }
+    }
+
+    /* The quoted public and system identifier states */
+    fn doctype_identifier(&mut self, public: bool, quote: char) {
+        match self.consume() {
+            Some(ch) if ch == quote => {
+                self.state = if public { State::AfterDoctypePublicIdentifier } else { State::AfterDoctypeSystemIdentifier };
+            }
+            Some('>') => {
+                self.error(if public { ErrorCode::AbruptDoctypePublicIdentifier } else { ErrorCode::AbruptDoctypeSystemIdentifier });
+                self.doctype.force_quirks = true;
+                self.emit_doctype();
+            }
+            None => {
+                self.error(ErrorCode::EofInDoctype);
+                self.doctype.force_quirks = true;
+                self.emit_doctype();
+            }
+            Some(ch) => {
+                let ch = if ch == '\0' {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    '\u{FFFD}'
+                } else {
+                    ch
+                };
+                let identifier = if public { &mut self.doctype.public_id } else { &mut self.doctype.system_id };
+                identifier.get_or_insert_with(String::new).push(ch);
+            }
+        }
+    }
+
+    fn emit_processing_instruction(&mut self) {
+        if self.data.starts_with("php") || self.data.starts_with('=') {
+            self.emit(Tag::PHP, TokenType::PHP);
+        } else if self.data.get(..3).is_some_and(|target| target.eq_ignore_ascii_case("xml"))
+            && !self.data[3..].starts_with(|ch: char| !ch.is_whitespace())
+        {
+            self.emit(Tag::XML, TokenType::SelfClosing);
+        } else {
+            /* The spec turns anything else into a bogus comment, a valid target is kept as a processing instruction */
+            let position = self.position(self.token_start + 1);
+            self.errors.push(ParseError::new(ErrorCode::UnexpectedQuestionMarkInsteadOfTagName, position));
+            let is_target = |ch: char| ch.is_alphanumeric() || matches!(ch, '_' | ':' | '-' | '.');
+            match self.data.chars().next() {
+                Some(ch) if ch.is_alphabetic() || ch == '_' || ch == ':' => {
+                    let length = self.data.find(|ch: char| !is_target(ch)).unwrap_or(self.data.len());
+                    let target = self.data[..length].to_string();
+                    self.data = self.data[length..].trim_start().to_string();
+                    self.emit(Tag::ProcessingInstruction(target), TokenType::Comment);
+                }
+                _ => {
+                    self.data.insert(0, '?');
+                    self.emit(Tag::Comment, TokenType::Comment);
+                }
+            }
+        }
+        self.state = State::Data;
+    }
+
+    fn start_tag(&mut self, tag_type: TokenType) {
+        self.tag_type = tag_type;
+        self.tag_name = String::new();
+        self.self_closing = false;
+        self.attributes = Vec::new();
+    }
+
+    fn start_attribute(&mut self) {
+        self.attribute_start = self.offset;
+        self.attributes.push(PendingAttribute {
+            name: String::new(),
+            value: String::new(),
+            name_range: (self.offset, self.offset),
+            value_range: None,
+        });
+    }
+
+    /* Called with the character right after the name as the current one */
+    fn end_attribute_name(&mut self) {
+        let range = (self.attribute_start, self.offset);
+        if let Some(attribute) = self.attributes.last_mut() {
+            attribute.name_range = range;
+        }
+    }
+
+    fn push_attribute_name(&mut self, ch: char) {
+        if let Some(attribute) = self.attributes.last_mut() {
+            attribute.name.push(if ch == '\0' { '\u{FFFD}' } else { ch.to_ascii_lowercase() });
+        }
+    }
+
+    fn push_attribute_value(&mut self, str: &str) {
+        if let Some(attribute) = self.attributes.last_mut() {
+            attribute.value.push_str(str);
+        }
+    }
+
+    /* Handles a character inside an attribute value that is not the closing quote or whitespace */
+    fn attribute_value_char(&mut self, ch: char) {
+        match ch {
+            '&' => match self.consume_character_reference(true) {
+                Some(decoded) => self.push_attribute_value(&decoded),
+                None => self.push_attribute_value("&"),
+            },
+            '\0' => {
+                self.error(ErrorCode::UnexpectedNullCharacter);
+                self.push_attribute_value("\u{FFFD}");
+            }
+            ch => self.push_attribute_value(ch.encode_utf8(&mut [0; 4])),
+        }
+    }
+
+    fn end_attribute_value(&mut self, end: usize) {
+        let range = (self.value_start, end);
+        if let Some(attribute) = self.attributes.last_mut() {
+            attribute.value_range = Some(range);
+        }
+    }
+
+    /* Runs one step of the state machine, returns false once the input is exhausted */
+    fn step(&mut self) -> bool {
+        match self.state {
+            State::Data => match self.consume() {
+                Some('<') => {
+                    self.token_start = self.offset;
+                    self.state = State::TagOpen;
+                }
+                Some('&') => self.push_character_reference(),
+                Some('\0') => {
+                    /* Kept as is here, the tree builder drops it */
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\0');
+                }
+                Some(ch) => self.push_text(ch),
+                None => return false,
+            },
+
+            State::Rcdata => match self.consume() {
+                Some('<') => {
+                    self.token_start = self.offset;
+                    self.state = State::RcdataLessThanSign;
+                }
+                Some('&') => self.push_character_reference(),
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                }
+                Some(ch) => self.push_text(ch),
+                None => return false,
+            },
+
+            State::Rawtext => match self.consume() {
+                Some('<') => {
+                    self.token_start = self.offset;
+                    self.state = State::RawtextLessThanSign;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                }
+                Some(ch) => self.push_text(ch),
+                None => return false,
+            },
+
+            State::ScriptData => match self.consume() {
+                Some('<') => {
+                    self.token_start = self.offset;
+                    self.state = State::ScriptDataLessThanSign;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                }
+                Some(ch) => self.push_text(ch),
+                None => return false,
+            },
+
+            State::Plaintext => match self.consume() {
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                }
+                Some(ch) => self.push_text(ch),
+                None => return false,
+            },
+
+            State::RcdataLessThanSign => self.less_than_sign(State::Rcdata, State::RcdataEndTagOpen),
+            State::RcdataEndTagOpen => self.end_tag_open(State::Rcdata, State::RcdataEndTagName),
+            State::RcdataEndTagName => self.end_tag_name(State::Rcdata),
+
+            State::RawtextLessThanSign => self.less_than_sign(State::Rawtext, State::RawtextEndTagOpen),
+            State::RawtextEndTagOpen => self.end_tag_open(State::Rawtext, State::RawtextEndTagName),
+            State::RawtextEndTagName => self.end_tag_name(State::Rawtext),
+
+            State::ScriptDataLessThanSign => match self.consume() {
+                Some('/') => {
+                    self.buffer = String::new();
+                    self.state = State::ScriptDataEndTagOpen;
+                }
+                Some('!') => {
+                    self.push_text_str("<!", self.token_start, self.token_start + 2);
+                    self.state = State::ScriptDataEscapeStart;
+                }
+                _ => {
+                    self.push_text_str("<", self.token_start, self.token_start + 1);
+                    self.reconsume_in(State::ScriptData);
+                }
+            },
+            State::ScriptDataEndTagOpen => self.end_tag_open(State::ScriptData, State::ScriptDataEndTagName),
+            State::ScriptDataEndTagName => self.end_tag_name(State::ScriptData),
+
+            /* "<!--" inside a script, where "</script>" may only appear inside a nested "<script>" */
+            State::ScriptDataEscapeStart => match self.consume() {
+                Some('-') => {
+                    self.push_text('-');
+                    self.state = State::ScriptDataEscapeStartDash;
+                }
+                _ => self.reconsume_in(State::ScriptData),
+            },
+
+            State::ScriptDataEscapeStartDash => match self.consume() {
+                Some('-') => {
+                    self.push_text('-');
+                    self.state = State::ScriptDataEscapedDashDash;
+                }
+                _ => self.reconsume_in(State::ScriptData),
+            },
+
+            State::ScriptDataEscaped => match self.consume() {
+                Some('-') => {
+                    self.push_text('-');
+                    self.state = State::ScriptDataEscapedDash;
+                }
+                Some('<') => {
+                    self.token_start = self.offset;
+                    self.state = State::ScriptDataEscapedLessThanSign;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                }
+                Some(ch) => self.push_text(ch),
+                None => {
+                    self.error(ErrorCode::EofInScriptHtmlCommentLikeText);
+                    return false;
+                }
+            },
+
+            State::ScriptDataEscapedDash | State::ScriptDataEscapedDashDash => match self.consume() {
+                Some('-') => {
+                    self.push_text('-');
+                    self.state = State::ScriptDataEscapedDashDash;
+                }
+                Some('<') => {
+                    self.token_start = self.offset;
+                    self.state = State::ScriptDataEscapedLessThanSign;
+                }
+                Some('>') if self.state == State::ScriptDataEscapedDashDash => {
+                    self.push_text('>');
+                    self.state = State::ScriptData;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                    self.state = State::ScriptDataEscaped;
+                }
+                Some(ch) => {
+                    self.push_text(ch);
+                    self.state = State::ScriptDataEscaped;
+                }
+                None => {
+                    self.error(ErrorCode::EofInScriptHtmlCommentLikeText);
+                    return false;
+                }
+            },
+
+            State::ScriptDataEscapedLessThanSign => match self.consume() {
+                Some('/') => {
+                    self.buffer = String::new();
+                    self.state = State::ScriptDataEscapedEndTagOpen;
+                }
+                Some(ch) if ch.is_ascii_alphabetic() => {
+                    self.buffer = String::new();
+                    self.push_text_str("<", self.token_start, self.token_start + 1);
+                    self.reconsume_in(State::ScriptDataDoubleEscapeStart);
+                }
+                _ => {
+                    self.push_text_str("<", self.token_start, self.token_start + 1);
+                    self.reconsume_in(State::ScriptDataEscaped);
+                }
+            },
+            State::ScriptDataEscapedEndTagOpen => self.end_tag_open(State::ScriptDataEscaped, State::ScriptDataEscapedEndTagName),
+            State::ScriptDataEscapedEndTagName => self.end_tag_name(State::ScriptDataEscaped),
+
+            State::ScriptDataDoubleEscapeStart | State::ScriptDataDoubleEscapeEnd => {
+                let double_escape_start = self.state == State::ScriptDataDoubleEscapeStart;
+                match self.consume() {
+                    Some(ch) if is_whitespace(ch) || ch == '/' || ch == '>' => {
+                        let script = self.buffer == "script";
+                        self.state = match (double_escape_start, script) {
+                            (true, true) | (false, false) => State::ScriptDataDoubleEscaped,
+                            (true, false) | (false, true) => State::ScriptDataEscaped,
+                        };
+                        self.push_text(ch);
+                    }
+                    Some(ch) if ch.is_ascii_alphabetic() => {
+                        self.buffer.push(ch.to_ascii_lowercase());
+                        self.push_text(ch);
+                    }
+                    _ if double_escape_start => self.reconsume_in(State::ScriptDataEscaped),
+                    _ => self.reconsume_in(State::ScriptDataDoubleEscaped),
+                }
+            }
+
+            State::ScriptDataDoubleEscaped => match self.consume() {
+                Some('-') => {
+                    self.push_text('-');
+                    self.state = State::ScriptDataDoubleEscapedDash;
+                }
+                Some('<') => {
+                    self.push_text('<');
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                }
+                Some(ch) => self.push_text(ch),
+                None => {
+                    self.error(ErrorCode::EofInScriptHtmlCommentLikeText);
+                    return false;
+                }
+            },
+
+            State::ScriptDataDoubleEscapedDash | State::ScriptDataDoubleEscapedDashDash => match self.consume() {
+                Some('-') => {
+                    self.push_text('-');
+                    self.state = State::ScriptDataDoubleEscapedDashDash;
+                }
+                Some('<') => {
+                    self.push_text('<');
+                    self.state = State::ScriptDataDoubleEscapedLessThanSign;
+                }
+                Some('>') if self.state == State::ScriptDataDoubleEscapedDashDash => {
+                    self.push_text('>');
+                    self.state = State::ScriptData;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.push_text('\u{FFFD}');
+                    self.state = State::ScriptDataDoubleEscaped;
+                }
+                Some(ch) => {
+                    self.push_text(ch);
+                    self.state = State::ScriptDataDoubleEscaped;
+                }
+                None => {
+                    self.error(ErrorCode::EofInScriptHtmlCommentLikeText);
+                    return false;
+                }
+            },
+
+            State::ScriptDataDoubleEscapedLessThanSign => match self.consume() {
+                Some('/') => {
+                    self.buffer = String::new();
+                    self.push_text('/');
+                    self.state = State::ScriptDataDoubleEscapeEnd;
+                }
+                _ => self.reconsume_in(State::ScriptDataDoubleEscaped),
+            },
+
+            State::TagOpen => match self.consume() {
+                Some('!') => self.state = State::MarkupDeclarationOpen,
+                Some('/') => self.state = State::EndTagOpen,
+                Some(ch) if ch.is_ascii_alphabetic() => {
+                    self.start_tag(TokenType::Open);
+                    self.reconsume_in(State::TagName);
+                }
+                Some('?') => {
+                    self.data = String::new();
+                    self.state = State::ProcessingInstruction;
+                }
+                None => {
+                    self.error(ErrorCode::EofBeforeTagName);
+                    self.push_text_str("<", self.token_start, self.token_start + 1);
+                    return false;
+                }
+                Some(_) => {
+                    self.error(ErrorCode::InvalidFirstCharacterOfTagName);
+                    self.push_text_str("<", self.token_start, self.token_start + 1);
+                    self.reconsume_in(State::Data);
+                }
+            },
+
+            State::EndTagOpen => match self.consume() {
+                Some(ch) if ch.is_ascii_alphabetic() => {
+                    self.start_tag(TokenType::Close);
+                    self.reconsume_in(State::TagName);
+                }
+                Some('>') => {
+                    /* "</>" is ignored completely */
+                    self.error(ErrorCode::MissingEndTagName);
+                    self.flush_text(self.token_start);
+                    self.state = State::Data;
+                }
+                None => {
+                    self.error(ErrorCode::EofBeforeTagName);
+                    self.push_text_str("</", self.token_start, self.token_start + 2);
+                    return false;
+                }
+                Some(_) => {
+                    self.error(ErrorCode::InvalidFirstCharacterOfTagName);
+                    self.data = String::new();
+                    self.reconsume_in(State::BogusComment);
+                }
+            },
+
+            State::TagName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::BeforeAttributeName,
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => self.emit_tag(),
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.tag_name.push('\u{FFFD}');
+                }
+                Some(ch) => self.tag_name.push(ch.to_ascii_lowercase()),
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+            },
+
+            State::BeforeAttributeName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('/') | Some('>') | None => self.reconsume_in(State::AfterAttributeName),
+                Some('=') => {
+                    self.error(ErrorCode::UnexpectedEqualsSignBeforeAttributeName);
+                    self.start_attribute();
+                    self.push_attribute_name('=');
+                    self.state = State::AttributeName;
+                }
+                Some(_) => {
+                    self.start_attribute();
+                    self.reconsume_in(State::AttributeName);
+                }
+            },
+
+            State::AttributeName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {
+                    self.end_attribute_name();
+                    self.reconsume_in(State::AfterAttributeName);
+                }
+                Some('/') | Some('>') | None => {
+                    self.end_attribute_name();
+                    self.reconsume_in(State::AfterAttributeName);
+                }
+                Some('=') => {
+                    self.end_attribute_name();
+                    self.state = State::BeforeAttributeValue;
+                }
+                Some(ch) => {
+                    if matches!(ch, '"' | '\'' | '<') {
+                        self.error(ErrorCode::UnexpectedCharacterInAttributeName);
+                    } else if ch == '\0' {
+                        self.error(ErrorCode::UnexpectedNullCharacter);
+                    }
+                    self.push_attribute_name(ch);
+                }
+            },
+
+            State::AfterAttributeName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('=') => self.state = State::BeforeAttributeValue,
+                Some('>') => self.emit_tag(),
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+                Some(_) => {
+                    self.start_attribute();
+                    self.reconsume_in(State::AttributeName);
+                }
+            },
+
+            State::BeforeAttributeValue => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('"') => {
+                    self.value_start = self.end();
+                    self.state = State::AttributeValueDoubleQuoted;
+                }
+                Some('\'') => {
+                    self.value_start = self.end();
+                    self.state = State::AttributeValueSingleQuoted;
+                }
+                Some('>') => {
+                    self.error(ErrorCode::MissingAttributeValue);
+                    self.emit_tag();
+                }
+                _ => {
+                    self.value_start = self.offset;
+                    self.reconsume_in(State::AttributeValueUnquoted);
+                }
+            },
+
+            State::AttributeValueDoubleQuoted => match self.consume() {
+                Some('"') => {
+                    self.end_attribute_value(self.offset);
+                    self.state = State::AfterAttributeValueQuoted;
+                }
+                Some(ch) => self.attribute_value_char(ch),
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+            },
+
+            State::AttributeValueSingleQuoted => match self.consume() {
+                Some('\'') => {
+                    self.end_attribute_value(self.offset);
+                    self.state = State::AfterAttributeValueQuoted;
+                }
+                Some(ch) => self.attribute_value_char(ch),
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+            },
+
+            State::AttributeValueUnquoted => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {
+                    self.end_attribute_value(self.offset);
+                    self.state = State::BeforeAttributeName;
+                }
+                Some('>') => {
+                    self.end_attribute_value(self.offset);
+                    self.emit_tag();
+                }
+                Some(ch) => {
+                    if matches!(ch, '"' | '\'' | '<' | '=' | '`') {
+                        self.error(ErrorCode::UnexpectedCharacterInUnquotedAttributeValue);
+                    }
+                    self.attribute_value_char(ch);
+                }
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+            },
+
+            State::AfterAttributeValueQuoted => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::BeforeAttributeName,
+                Some('/') => self.state = State::SelfClosingStartTag,
+                Some('>') => self.emit_tag(),
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+                Some(_) => {
+                    self.error(ErrorCode::MissingWhitespaceBetweenAttributes);
+                    self.reconsume_in(State::BeforeAttributeName);
+                }
+            },
+
+            State::SelfClosingStartTag => match self.consume() {
+                Some('>') => {
+                    self.self_closing = true;
+                    self.emit_tag();
+                }
+                None => {
+                    self.error(ErrorCode::EofInTag);
+                    return false;
+                }
+                Some(_) => {
+                    self.error(ErrorCode::UnexpectedSolidusInTag);
+                    self.reconsume_in(State::BeforeAttributeName);
+                }
+            },
+
+            State::BogusComment => match self.consume() {
+                Some('>') => self.emit_comment(),
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.data.push('\u{FFFD}');
+                }
+                Some(ch) => self.data.push(ch),
+                None => {
+                    self.emit_comment();
+                    return false;
+                }
+            },
+
+            State::ProcessingInstruction => match self.consume() {
+                /* PHP may contain ">" in its code, so it only ends at "?>" */
+                Some('>') if (self.data.starts_with("php") || self.data.starts_with('=')) && !self.data.ends_with('?') => self.data.push('>'),
+                Some('>') => {
+                    if self.data.ends_with('?') {
+                        self.data.pop();
+                    }
+                    self.emit_processing_instruction();
+                }
+                Some(ch) => self.data.push(ch),
+                None => {
+                    self.emit_processing_instruction();
+                    return false;
+                }
+            },
+
+            State::MarkupDeclarationOpen => {
+                if self.consumer.lookahead("--", false) {
+                    self.consumer.skip(2);
+                    self.data = String::new();
+                    self.state = State::CommentStart;
+                } else if self.consumer.lookahead("DOCTYPE", true) {
+                    self.consumer.skip(7);
+                    self.state = State::Doctype;
+                } else if self.consumer.lookahead("[CDATA[", false) {
+                    /* Whether CDATA is allowed depends on the tree, so the tree builder reports it outside foreign content */
+                    self.consumer.skip(7);
+                    self.data = String::new();
+                    self.state = State::CdataSection;
+                } else {
+                    self.error(ErrorCode::IncorrectlyOpenedComment);
+                    self.data = String::new();
+                    self.state = State::BogusComment;
+                }
+            }
+
+            State::CdataSection => match self.consume() {
+                Some(']') if self.consumer.lookahead("]>", false) => {
+                    self.consumer.skip(2);
+                    self.emit(Tag::CDATA, TokenType::Comment);
+                    self.state = State::Data;
+                }
+                Some(ch) => self.data.push(ch),
+                None => {
+                    self.error(ErrorCode::EofInCdata);
+                    self.emit(Tag::CDATA, TokenType::Comment);
+                    return false;
+                }
+            },
+
+            State::CommentStart => match self.consume() {
+                Some('-') => self.state = State::CommentStartDash,
+                Some('>') => {
+                    self.error(ErrorCode::AbruptClosingOfEmptyComment);
+                    self.emit_comment();
+                }
+                _ => self.reconsume_in(State::Comment),
+            },
+
+            State::CommentStartDash => match self.consume() {
+                Some('-') => self.state = State::CommentEnd,
+                Some('>') => {
+                    self.error(ErrorCode::AbruptClosingOfEmptyComment);
+                    self.emit_comment();
+                }
+                None => {
+                    self.error(ErrorCode::EofInComment);
+                    self.emit_comment();
+                    return false;
+                }
+                Some(_) => {
+                    self.data.push('-');
+                    self.reconsume_in(State::Comment);
+                }
+            },
+
+            State::Comment => match self.consume() {
+                Some('<') => {
+                    self.data.push('<');
+                    self.state = State::CommentLessThanSign;
+                }
+                Some('-') => self.state = State::CommentEndDash,
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.data.push('\u{FFFD}');
+                }
+                Some(ch) => self.data.push(ch),
+                None => {
+                    self.error(ErrorCode::EofInComment);
+                    self.emit_comment();
+                    return false;
+                }
+            },
+
+            State::CommentLessThanSign => match self.consume() {
+                Some('!') => {
+                    self.data.push('!');
+                    self.state = State::CommentLessThanSignBang;
+                }
+                Some('<') => self.data.push('<'),
+                _ => self.reconsume_in(State::Comment),
+            },
+
+            State::CommentLessThanSignBang => match self.consume() {
+                Some('-') => self.state = State::CommentLessThanSignBangDash,
+                _ => self.reconsume_in(State::Comment),
+            },
+
+            State::CommentLessThanSignBangDash => match self.consume() {
+                Some('-') => self.state = State::CommentLessThanSignBangDashDash,
+                _ => self.reconsume_in(State::CommentEndDash),
+            },
+
+            State::CommentLessThanSignBangDashDash => {
+                if !matches!(self.consume(), Some('>') | None) {
+                    self.error(ErrorCode::NestedComment);
+                }
+                self.reconsume_in(State::CommentEnd);
+            }
+
+            State::CommentEndDash => match self.consume() {
+                Some('-') => self.state = State::CommentEnd,
+                None => {
+                    self.error(ErrorCode::EofInComment);
+                    self.emit_comment();
+                    return false;
+                }
+                Some(_) => {
+                    self.data.push('-');
+                    self.reconsume_in(State::Comment);
+                }
+            },
+
+            State::CommentEnd => match self.consume() {
+                Some('>') => self.emit_comment(),
+                Some('!') => self.state = State::CommentEndBang,
+                Some('-') => self.data.push('-'),
+                None => {
+                    self.error(ErrorCode::EofInComment);
+                    self.emit_comment();
+                    return false;
+                }
+                Some(_) => {
+                    self.data.push_str("--");
+                    self.reconsume_in(State::Comment);
+                }
+            },
+
+            State::CommentEndBang => match self.consume() {
+                Some('-') => {
+                    self.data.push_str("--!");
+                    self.state = State::CommentEndDash;
+                }
+                Some('>') => {
+                    self.error(ErrorCode::IncorrectlyClosedComment);
+                    self.emit_comment();
+                }
+                None => {
+                    self.error(ErrorCode::EofInComment);
+                    self.emit_comment();
+                    return false;
+                }
+                Some(_) => {
+                    self.data.push_str("--!");
+                    self.reconsume_in(State::Comment);
+                }
+            },
+
+            State::Doctype => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::BeforeDoctypeName,
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+                Some('>') => self.reconsume_in(State::BeforeDoctypeName),
+                Some(_) => {
+                    self.error(ErrorCode::MissingWhitespaceBeforeDoctypeName);
+                    self.reconsume_in(State::BeforeDoctypeName);
+                }
+            },
+
+            State::BeforeDoctypeName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('>') => {
+                    self.error(ErrorCode::MissingDoctypeName);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                }
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.doctype.name = Some('\u{FFFD}'.to_string());
+                    self.state = State::DoctypeName;
+                }
+                Some(ch) => {
+                    self.doctype.name = Some(ch.to_ascii_lowercase().to_string());
+                    self.state = State::DoctypeName;
+                }
+            },
+
+            State::DoctypeName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::AfterDoctypeName,
+                Some('>') => self.emit_doctype(),
+                Some('\0') => {
+                    self.error(ErrorCode::UnexpectedNullCharacter);
+                    self.doctype.name.get_or_insert_with(String::new).push('\u{FFFD}');
+                }
+                Some(ch) => self.doctype.name.get_or_insert_with(String::new).push(ch.to_ascii_lowercase()),
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+            },
+
+            State::AfterDoctypeName => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('>') => self.emit_doctype(),
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+                Some(_) if self.lookahead_keyword("PUBLIC") => self.state = State::AfterDoctypePublicKeyword,
+                Some(_) if self.lookahead_keyword("SYSTEM") => self.state = State::AfterDoctypeSystemKeyword,
+                Some(_) => {
+                    self.error(ErrorCode::InvalidCharacterSequenceAfterDoctypeName);
+                    self.doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+
+            State::AfterDoctypePublicKeyword => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::BeforeDoctypePublicIdentifier,
+                Some(quote @ ('"' | '\'')) => {
+                    self.error(ErrorCode::MissingWhitespaceAfterDoctypePublicKeyword);
+                    self.start_doctype_identifier(true, quote);
+                }
+                other => self.missing_doctype_identifier(other, true),
+            },
+
+            State::BeforeDoctypePublicIdentifier => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some(quote @ ('"' | '\'')) => self.start_doctype_identifier(true, quote),
+                other => self.missing_doctype_identifier(other, true),
+            },
+
+            State::DoctypePublicIdentifierDoubleQuoted => self.doctype_identifier(true, '"'),
+            State::DoctypePublicIdentifierSingleQuoted => self.doctype_identifier(true, '\''),
+
+            State::AfterDoctypePublicIdentifier => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::BetweenDoctypePublicAndSystemIdentifiers,
+                Some('>') => self.emit_doctype(),
+                Some(quote @ ('"' | '\'')) => {
+                    self.error(ErrorCode::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers);
+                    self.start_doctype_identifier(false, quote);
+                }
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+                Some(_) => {
+                    self.error(ErrorCode::MissingQuoteBeforeDoctypeSystemIdentifier);
+                    self.doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+
+            State::BetweenDoctypePublicAndSystemIdentifiers => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('>') => self.emit_doctype(),
+                Some(quote @ ('"' | '\'')) => self.start_doctype_identifier(false, quote),
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+                Some(_) => {
+                    self.error(ErrorCode::MissingQuoteBeforeDoctypeSystemIdentifier);
+                    self.doctype.force_quirks = true;
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+
+            State::AfterDoctypeSystemKeyword => match self.consume() {
+                Some(ch) if is_whitespace(ch) => self.state = State::BeforeDoctypeSystemIdentifier,
+                Some(quote @ ('"' | '\'')) => {
+                    self.error(ErrorCode::MissingWhitespaceAfterDoctypeSystemKeyword);
+                    self.start_doctype_identifier(false, quote);
+                }
+                other => self.missing_doctype_identifier(other, false),
+            },
+
+            State::BeforeDoctypeSystemIdentifier => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some(quote @ ('"' | '\'')) => self.start_doctype_identifier(false, quote),
+                other => self.missing_doctype_identifier(other, false),
+            },
+
+            State::DoctypeSystemIdentifierDoubleQuoted => self.doctype_identifier(false, '"'),
+            State::DoctypeSystemIdentifierSingleQuoted => self.doctype_identifier(false, '\''),
+
+            State::AfterDoctypeSystemIdentifier => match self.consume() {
+                Some(ch) if is_whitespace(ch) => {}
+                Some('>') => self.emit_doctype(),
+                None => {
+                    self.error(ErrorCode::EofInDoctype);
+                    self.doctype.force_quirks = true;
+                    self.emit_doctype();
+                    return false;
+                }
+                Some(_) => {
+                    /* Unlike the other doctype errors this one does not force quirks mode */
+                    self.error(ErrorCode::UnexpectedCharacterAfterDoctypeSystemIdentifier);
+                    self.reconsume_in(State::BogusDoctype);
+                }
+            },
+
+            State::BogusDoctype => match self.consume() {
+                Some('>') => self.emit_doctype(),
+                Some('\0') => self.error(ErrorCode::UnexpectedNullCharacter),
+                Some(_) => {}
+                None => {
+                    self.emit_doctype();
+                    return false;
+                }
+            },
+        }
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokenize(html: &str) -> (Vec<Token>, Vec<ParseError>) {
+        Tokenizer::new(Consumer::from(html), &ParseOptions::default()).run()
+    }
+
+    fn codes(errors: &[ParseError]) -> Vec<&'static str> {
+        errors.iter().map(|error| error.code.code()).collect()
+    }
+
+    #[test]
+    fn tags_and_attributes() {
+        let (tokens, errors) = tokenize("<DIV Class=a id='b' title=\"c d\" hidden><br/></div>");
+        assert!(errors.is_empty());
+        assert_eq!(tokens.len(), 3);
+        assert_eq!((tokens[0].tag.name(), &tokens[0].tag_type), ("div", &TokenType::Open));
+        let attributes: Vec<(&str, &str)> = tokens[0].attributes.iter().map(|attribute| (attribute.name.as_str(), attribute.value.as_str())).collect();
+        assert_eq!(attributes, [("class", "a"), ("id", "b"), ("title", "c d"), ("hidden", "")]);
+        assert_eq!((tokens[1].tag.name(), &tokens[1].tag_type), ("br", &TokenType::SelfClosing));
+        assert_eq!((tokens[2].tag.name(), &tokens[2].tag_type), ("div", &TokenType::Close));
+    }
+
+    #[test]
+    fn malformed_tags() {
+        let (tokens, errors) = tokenize("<p a=1 a=2 b=\"x\"c>< 1 </>x</p id=1 />");
+        assert_eq!(tokens[0].attributes.len(), 3);
+        assert_eq!(tokens[0].attributes[0].value, "1");
+        assert_eq!((tokens[1].data.as_str(), tokens[2].data.as_str()), ("< 1 ", "x"));
+        assert_eq!(tokens[3].tag_type, TokenType::Close);
+        assert_eq!(
+            codes(&errors),
+            [
+                "missing-whitespace-between-attributes",
+                "duplicate-attribute",
+                "invalid-first-character-of-tag-name",
+                "missing-end-tag-name",
+                "end-tag-with-attributes",
+                "end-tag-with-trailing-solidus",
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let (tokens, errors) = tokenize("<!--a-- b--><!---->x<!-->y<!-- <!-- c --!><!--d");
+        let comments: Vec<&str> = tokens.iter().filter(|token| token.tag_type == TokenType::Comment).map(|token| token.data.as_str()).collect();
+        assert_eq!(comments, ["a-- b", "", "", " <!-- c ", "d"]);
+        assert_eq!(codes(&errors), ["abrupt-closing-of-empty-comment", "nested-comment", "incorrectly-closed-comment", "eof-in-comment"]);
+    }
+
+    #[test]
+    fn doctypes() {
+        let (tokens, errors) = tokenize("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'http://www.w3.org/TR/html4/strict.dtd'>");
+        assert!(errors.is_empty());
+        let doctype = tokens[0].doctype().unwrap();
+        assert_eq!(doctype.name.as_deref(), Some("html"));
+        assert_eq!(doctype.public_id.as_deref(), Some("-//W3C//DTD HTML 4.01//EN"));
+        assert_eq!(doctype.system_id.as_deref(), Some("http://www.w3.org/TR/html4/strict.dtd"));
+        let (tokens, errors) = tokenize("<!doctypehtml SYSTEM>");
+        assert_eq!(tokens[0].doctype().unwrap().name.as_deref(), Some("html"));
+        assert!(tokens[0].doctype().unwrap().force_quirks);
+        assert_eq!(codes(&errors), ["missing-whitespace-before-doctype-name", "missing-doctype-system-identifier"]);
+    }
+
+    #[test]
+    fn cdata_and_eof() {
+        let (tokens, errors) = tokenize("<![CDATA[x]]><div class=");
+        assert!(matches!(tokens[0].tag, Tag::CDATA));
+        assert_eq!(tokens[0].data, "x");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(codes(&errors), ["eof-in-tag"]);
+        let (tokens, errors) = tokenize("a<");
+        assert_eq!(tokens[0].data, "a<");
+        assert_eq!(codes(&errors), ["eof-before-tag-name"]);
+    }
+
+    #[test]
+    fn raw_text_ends_only_at_its_end_tag() {
+        let (tokens, errors) = tokenize("<style>a</b> &amp; </styles></STYLE ><xmp><p></xmp>");
+        assert!(errors.is_empty());
+        assert_eq!(tokens[1].data, "a</b> &amp; </styles>");
+        assert_eq!((tokens[2].tag.name(), &tokens[2].tag_type), ("style", &TokenType::Close));
+        assert_eq!(tokens[4].data, "<p>");
+    }
+
+    #[test]
+    fn rcdata_decodes_references() {
+        let (tokens, _) = tokenize("<title>a &amp; <b></title><textarea></textarea >");
+        assert_eq!(tokens[1].data, "a & <b>");
+        assert_eq!(tokens[4].tag_type, TokenType::Close);
+        assert_eq!(tokens.len(), 5);
+    }
+
+    #[test]
+    fn script_data() {
+        let (tokens, errors) = tokenize("<script>if (a<b) x = '</scr' + 'ipt>';</script>");
+        assert!(errors.is_empty());
+        assert_eq!(tokens[1].data, "if (a<b) x = '</scr' + 'ipt>';");
+        /* </script> inside a double escaped <script> is text */
+        let (tokens, _) = tokenize("<script><!--<script></script>--></script>x");
+        assert_eq!(tokens[1].data, "<!--<script></script>-->");
+        assert_eq!(tokens[3].data, "x");
+        let (_, errors) = tokenize("<script><!--");
+        assert_eq!(codes(&errors), ["eof-in-script-html-comment-like-text"]);
+    }
+
+    #[test]
+    fn plaintext_never_ends() {
+        let (tokens, _) = tokenize("<plaintext></plaintext><p>");
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1].data, "</plaintext><p>");
+    }
+
+    #[test]
+    fn fragment_content_ends_at_the_end_tag_of_its_context() {
+        /* The tree builder switches to the state of the context before the first token */
+        let mut tokenizer = Tokenizer::new_fragment(Consumer::from("<b>x</b></textarea>"), &ParseOptions::default(), "textarea");
+        tokenizer.switch_to(State::Rcdata);
+        let (tokens, _) = tokenizer.run();
+        assert_eq!(tokens[0].data, "<b>x</b>");
+        assert_eq!(tokens[1].tag_type, TokenType::Close);
+    }
+
+    #[test]
+    fn newlines_are_normalized() {
+        let (tokens, errors) = tokenize("a\r\nb\rc<p title=\"x\r\ny\ry\">\r<!--\r\n-->");
+        assert!(errors.is_empty());
+        assert_eq!(tokens[0].data, "a\nb\nc");
+        assert_eq!(tokens[1].attributes[0].value, "x\ny\ny");
+        assert_eq!(tokens[2].data, "\n");
+        assert_eq!(tokens[3].data, "\n");
+    }
+
+    #[test]
+    fn spans_of_normalized_input_point_into_the_source() {
+        let html = "a\r\n<p title=\"x\r\ny\">\rb";
+        let (tokens, _) = tokenize(html);
+        let text = tokens[0].span.unwrap();
+        assert_eq!((text.start.offset, text.end.offset), (0, 3));
+        let tag = tokens[1].span.unwrap();
+        assert_eq!(&html[tag.start.offset..tag.end.offset], "<p title=\"x\r\ny\">");
+        assert_eq!((tag.start.line, tag.start.column), (2, 1));
+        let value = tokens[1].attributes[0].span.unwrap().value.unwrap();
+        assert_eq!(&html[value.start.offset..value.end.offset], "x\r\ny");
+        let last = tokens[2].span.unwrap();
+        assert_eq!(&html[last.start.offset..last.end.offset], "\rb");
+        assert_eq!((last.end.line, last.end.column), (4, 2));
+    }
+
+    #[test]
+    fn leading_crlf_of_pre_is_dropped() {
+        let document = DOM::parse_str("<pre>\r\nx\r\n</pre><textarea>\r\ny</textarea>").unwrap();
+        assert_eq!(document.to_node(document.root()).text(), "x\ny");
+    }
+}
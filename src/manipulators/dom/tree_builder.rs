@@ -0,0 +1,2792 @@
+#![allow(dead_code)]
+
+use super::tokenizer::{content_state, State, Tokenizer};
+use super::{is_whitespace, Attributes, Doctype, Document, Element, ErrorCode, Node, NodeId, ParseError, ParseOptions, ProcessingInstruction, Position, QuirksMode, Span, Tag, Token, TokenType, Whitespace, DOM};
+
+/* Insertion modes of the WHATWG tree construction stage, see https://html.spec.whatwg.org/#tree-construction */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    InHeadNoscript,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InSelectInTable,
+    InTemplate,
+    InFrameset,
+    AfterBody,
+    AfterFrameset,
+    AfterAfterBody,
+    AfterAfterFrameset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Formatting {
+    Marker,
+    Element(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Namespace {
+    Html,
+    MathMl,
+    Svg,
+}
+
+/* State of Whitespace::Trim while walking the text of a block in document order */
+struct Trim {
+    /* The text that ends the content so far, its trailing space goes when the block ends */
+    last: Option<usize>,
+    /* Set at the start of a block and after a space, a space that follows is dropped */
+    space: bool,
+}
+
+/* Elements are kept without children here, the tree is linked by index until it is built */
+#[derive(Debug)]
+struct TreeNode {
+    data: Node,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    namespace: Namespace,
+}
+
+const IMPLIED_END_TAGS: &[&str] = &["dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc"];
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+const HEAD_CONTENT: &[&str] = &["base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title"];
+const TABLE_SECTIONS: &[&str] = &["tbody", "tfoot", "thead"];
+const TABLE_STRUCTURE: &[&str] = &["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"];
+const MAX_DEPTH: usize = 512;
+/* Elements that may be left open at the end of the input without an error */
+const CLOSED_BY_EOF: &[&str] = &[
+    "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody", "td", "tfoot", "th", "thead", "tr",
+    "body", "html",
+];
+/* Start tags that leave SVG and MathML content, see https://html.spec.whatwg.org/#parsing-main-inforeign */
+const BREAKOUT: &[&str] = &[
+    "b", "big", "blockquote", "body", "br", "center", "code", "dd", "div", "dl", "dt", "em", "embed", "h1", "h2", "h3",
+    "h4", "h5", "h6", "head", "hr", "i", "img", "li", "listing", "menu", "meta", "nobr", "ol", "p", "pre", "ruby", "s",
+    "small", "span", "strike", "strong", "sub", "sup", "table", "tt", "u", "ul", "var",
+];
+/* The tokenizer lowercases names, SVG gets the case of these back */
+const SVG_TAG_NAMES: &[(&str, &str)] = &[
+    ("altglyph", "altGlyph"), ("altglyphdef", "altGlyphDef"), ("altglyphitem", "altGlyphItem"),
+    ("animatecolor", "animateColor"), ("animatemotion", "animateMotion"), ("animatetransform", "animateTransform"),
+    ("clippath", "clipPath"), ("feblend", "feBlend"), ("fecolormatrix", "feColorMatrix"),
+    ("fecomponenttransfer", "feComponentTransfer"), ("fecomposite", "feComposite"),
+    ("feconvolvematrix", "feConvolveMatrix"), ("fediffuselighting", "feDiffuseLighting"),
+    ("fedisplacementmap", "feDisplacementMap"), ("fedistantlight", "feDistantLight"), ("fedropshadow", "feDropShadow"),
+    ("feflood", "feFlood"), ("fefunca", "feFuncA"), ("fefuncb", "feFuncB"), ("fefuncg", "feFuncG"), ("fefuncr", "feFuncR"),
+    ("fegaussianblur", "feGaussianBlur"), ("feimage", "feImage"), ("femerge", "feMerge"), ("femergenode", "feMergeNode"),
+    ("femorphology", "feMorphology"), ("feoffset", "feOffset"), ("fepointlight", "fePointLight"),
+    ("fespecularlighting", "feSpecularLighting"), ("fespotlight", "feSpotLight"), ("fetile", "feTile"),
+    ("feturbulence", "feTurbulence"), ("foreignobject", "foreignObject"), ("glyphref", "glyphRef"),
+    ("lineargradient", "linearGradient"), ("radialgradient", "radialGradient"), ("textpath", "textPath"),
+];
+const SVG_ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("attributename", "attributeName"), ("attributetype", "attributeType"), ("basefrequency", "baseFrequency"),
+    ("baseprofile", "baseProfile"), ("calcmode", "calcMode"), ("clippathunits", "clipPathUnits"),
+    ("diffuseconstant", "diffuseConstant"), ("edgemode", "edgeMode"), ("filterunits", "filterUnits"),
+    ("glyphref", "glyphRef"), ("gradienttransform", "gradientTransform"), ("gradientunits", "gradientUnits"),
+    ("kernelmatrix", "kernelMatrix"), ("kernelunitlength", "kernelUnitLength"), ("keypoints", "keyPoints"),
+    ("keysplines", "keySplines"), ("keytimes", "keyTimes"), ("lengthadjust", "lengthAdjust"),
+    ("limitingconeangle", "limitingConeAngle"), ("markerheight", "markerHeight"), ("markerunits", "markerUnits"),
+    ("markerwidth", "markerWidth"), ("maskcontentunits", "maskContentUnits"), ("maskunits", "maskUnits"),
+    ("numoctaves", "numOctaves"), ("pathlength", "pathLength"), ("patterncontentunits", "patternContentUnits"),
+    ("patterntransform", "patternTransform"), ("patternunits", "patternUnits"), ("pointsatx", "pointsAtX"),
+    ("pointsaty", "pointsAtY"), ("pointsatz", "pointsAtZ"), ("preservealpha", "preserveAlpha"),
+    ("preserveaspectratio", "preserveAspectRatio"), ("primitiveunits", "primitiveUnits"), ("refx", "refX"),
+    ("refy", "refY"), ("repeatcount", "repeatCount"), ("repeatdur", "repeatDur"),
+    ("requiredextensions", "requiredExtensions"), ("requiredfeatures", "requiredFeatures"),
+    ("specularconstant", "specularConstant"), ("specularexponent", "specularExponent"), ("spreadmethod", "spreadMethod"),
+    ("startoffset", "startOffset"), ("stddeviation", "stdDeviation"), ("stitchtiles", "stitchTiles"),
+    ("surfacescale", "surfaceScale"), ("systemlanguage", "systemLanguage"), ("tablevalues", "tableValues"),
+    ("targetx", "targetX"), ("targety", "targetY"), ("textlength", "textLength"), ("viewbox", "viewBox"),
+    ("viewtarget", "viewTarget"), ("xchannelselector", "xChannelSelector"), ("ychannelselector", "yChannelSelector"),
+    ("zoomandpan", "zoomAndPan"),
+];
+
+/* Every run of whitespace becomes a single space */
+fn collapse(text: &str) -> String {
+    let mut result = String::new();
+    let mut last_whitespace = false;
+    for ch in text.chars() {
+        if !is_whitespace(ch) {
+            result.push(ch);
+        } else if !last_whitespace {
+            result.push(' ');
+        }
+        last_whitespace = is_whitespace(ch);
+    }
+    return result;
+}
+
+/* Blocks without rules of their own in the body, their start tag closes an open <p> */
+fn is_plain_block(tag: &Tag) -> bool {
+    tag.is_block()
+        && !tag.is_heading()
+        && !tag.is_void()
+        && !TABLE_STRUCTURE.contains(&tag.name())
+        && !matches!(tag.name(),
+            "body" | "dd" | "dt" | "form" | "frameset" | "html" | "legend" | "li" | "listing" | "optgroup" | "option"
+            | "plaintext" | "pre" | "table" | "xmp")
+}
+
+fn is_start(token: &Token) -> bool {
+    match token.tag_type {
+        TokenType::Open => true,
+        TokenType::SelfClosing => token.tag != Tag::Doctype && token.tag != Tag::XML,
+        _ => false,
+    }
+}
+
+fn is_end(token: &Token) -> bool {
+    token.tag_type == TokenType::Close
+}
+
+fn start_tag(token: &Token, names: &[&str]) -> bool {
+    is_start(token) && names.contains(&token.tag.name())
+}
+
+fn end_tag(token: &Token, names: &[&str]) -> bool {
+    is_end(token) && names.contains(&token.tag.name())
+}
+
+fn is_text(token: &Token) -> bool {
+    token.tag_type == TokenType::Content
+}
+
+/* Splits a text token into its leading whitespace and the rest of the token */
+fn split_whitespace(token: &Token) -> (String, Option<Token>) {
+    let rest = token.data.trim_start_matches(is_whitespace);
+    let whitespace = token.data[..token.data.len() - rest.len()].to_string();
+    if rest.is_empty() {
+        return (whitespace, None);
+    }
+    let mut token = token.clone();
+    token.data = rest.to_string();
+    return (whitespace, Some(token));
+}
+
+/* Gives SVG and MathML names the case they are defined with, see https://html.spec.whatwg.org/#creating-and-inserting-nodes */
+fn adjust_foreign(token: &mut Token, namespace: Namespace) {
+    let adjusted = |table: &[(&str, &'static str)], name: &str| table.iter().find(|(lower, _)| *lower == name).map(|(_, name)| *name);
+    if namespace == Namespace::Svg {
+        if let Some(name) = adjusted(SVG_TAG_NAMES, token.tag.name()) {
+            token.tag = Tag::Tag(name.to_string());
+        }
+    }
+    for attribute in &mut token.attributes {
+        let name = match namespace {
+            Namespace::Svg => adjusted(SVG_ATTRIBUTE_NAMES, &attribute.name),
+            Namespace::MathMl if attribute.name == "definitionurl" => Some("definitionURL"),
+            _ => None,
+        };
+        if let Some(name) = name {
+            attribute.name = name.to_string();
+        }
+    }
+}
+
+pub struct TreeBuilder {
+    options: ParseOptions,
+    nodes: Vec<TreeNode>,
+    open: Vec<usize>,
+    formatting: Vec<Formatting>,
+    mode: Mode,
+    original_mode: Mode,
+    /* The mode for the content of each open <template>, see https://html.spec.whatwg.org/#stack-of-template-insertion-modes */
+    template_modes: Vec<Mode>,
+    head: Option<usize>,
+    form: Option<usize>,
+    foster_parenting: bool,
+    /* End of the last token, where elements left open are closed */
+    end: Option<Position>,
+    /* The token being processed, errors are reported at its start */
+    position: Option<Position>,
+    span: Option<Span>,
+    end_tag: String,
+    doctype: Option<Doctype>,
+    quirks_mode: QuirksMode,
+    /* Set after <pre>, <listing> and <textarea>, whose first newline is dropped */
+    skip_newline: bool,
+    /* The element a fragment is parsed for, see https://html.spec.whatwg.org/#html-fragment-parsing-algorithm */
+    context: Option<Tag>,
+    /* A node outside the tree standing in for the context element */
+    context_element: Option<usize>,
+    /* Set when an element whose content is text was inserted, the tokenizer switches before the next token */
+    tokenizer_state: Option<State>,
+    errors: Vec<ParseError>,
+}
+impl TreeBuilder {
+    pub fn new(options: &ParseOptions) -> TreeBuilder {
+        let mut root = Element::default();
+        root.tag = Tag::Root;
+        TreeBuilder {
+            options: options.clone(),
+            nodes: vec![TreeNode { data: Node::Element(root), parent: None, children: Vec::new(), namespace: Namespace::Html }],
+            open: Vec::new(),
+            formatting: Vec::new(),
+            mode: Mode::Initial,
+            original_mode: Mode::Initial,
+            template_modes: Vec::new(),
+            head: None,
+            form: None,
+            foster_parenting: false,
+            end: None,
+            position: None,
+            span: None,
+            end_tag: String::new(),
+            doctype: None,
+            quirks_mode: QuirksMode::NoQuirks,
+            skip_newline: false,
+            context: None,
+            context_element: None,
+            tokenizer_state: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /* The fragment is parsed into an <html> element, as if it were the content of the context element */
+    pub fn new_fragment(options: &ParseOptions, context: Tag) -> TreeBuilder {
+        let mut builder = TreeBuilder::new(options);
+        builder.insert_synthetic("html");
+        if context.name() == "template" {
+            builder.template_modes.push(Mode::InTemplate);
+        }
+        let namespace = match context.name() {
+            "math" => Namespace::MathMl,
+            "svg" => Namespace::Svg,
+            _ => Namespace::Html,
+        };
+        let element = builder.add_node(Node::Element(Element::new(context.clone(), None, Vec::new())));
+        builder.nodes[element].namespace = namespace;
+        builder.context_element = Some(element);
+        builder.tokenizer_state = Some(content_state(&context));
+        builder.context = Some(context);
+        builder.reset_insertion_mode();
+        return builder;
+    }
+
+    pub fn build(mut self, tokenizer: &mut Tokenizer) -> Document {
+        loop {
+            if let Some(state) = self.tokenizer_state.take() {
+                tokenizer.switch_to(state);
+            }
+            match tokenizer.next_token() {
+                Some(token) => self.process(token),
+                None => break,
+            }
+        }
+        self.end_of_file();
+        let preserve = self.context.as_ref().is_some_and(Tag::preserves_whitespace);
+        if self.options.whitespace == Whitespace::Trim && !preserve {
+            self.trim_whitespace(0, &mut Trim { last: None, space: true });
+        }
+        let mut document = Document::new();
+        let root = document.root();
+        match &self.context {
+            /* The fragment is what ended up in the <html> element */
+            Some(context) => {
+                let preserve = context.preserves_whitespace();
+                self.copy_children(&mut document, self.nodes[0].children[0], root, preserve);
+            }
+            None => self.copy_children(&mut document, 0, root, false),
+        }
+        document.errors = self.errors;
+        document.doctype = self.doctype;
+        document.quirks_mode = self.quirks_mode;
+        document.raw_entities = !self.options.decode_entities;
+        return document;
+    }
+
+    /* Empty or truncated input still gets the html, head and body elements a browser would create */
+    fn end_of_file(&mut self) {
+        let end = self.end.unwrap_or(Position { offset: 0, line: 1, column: 1 });
+        self.position = Some(end);
+        loop {
+            match self.mode {
+                Mode::Initial => {
+                    self.error(ErrorCode::MissingDoctype);
+                    self.quirks_mode = QuirksMode::Quirks;
+                    self.mode = Mode::BeforeHtml;
+                }
+                Mode::BeforeHtml => {
+                    self.insert_synthetic("html");
+                    self.mode = Mode::BeforeHead;
+                }
+                Mode::BeforeHead => {
+                    self.head = Some(self.insert_synthetic("head"));
+                    self.mode = Mode::InHead;
+                }
+                Mode::InHead => self.close_head(),
+                Mode::InHeadNoscript => {
+                    self.error(ErrorCode::EofInElement);
+                    self.pop();
+                    self.mode = Mode::InHead;
+                }
+                Mode::AfterHead => {
+                    self.insert_synthetic("body");
+                    self.mode = Mode::InBody;
+                }
+                Mode::Text => {
+                    self.error(ErrorCode::EofInElement);
+                    self.pop();
+                    self.mode = self.original_mode;
+                }
+                /* Open templates are closed, then the input ends in the mode of what contains them */
+                Mode::InTemplate | Mode::InBody | Mode::InTable | Mode::InCaption | Mode::InColumnGroup | Mode::InTableBody
+                | Mode::InRow | Mode::InCell | Mode::InSelect | Mode::InSelectInTable
+                    if self.open.iter().any(|&id| self.name(id) == "template") =>
+                {
+                    self.error(ErrorCode::EofInElement);
+                    self.pop_until(&["template"]);
+                    self.clear_formatting_to_marker();
+                    self.template_modes.pop();
+                    self.reset_insertion_mode();
+                }
+                _ => break,
+            }
+        }
+        /* Elements still open at the end of the input end with it, after </body> that was reported already */
+        let after = matches!(self.mode, Mode::AfterBody | Mode::AfterAfterBody | Mode::AfterFrameset | Mode::AfterAfterFrameset);
+        if !after && self.open.iter().any(|&id| !CLOSED_BY_EOF.contains(&self.name(id))) {
+            self.error(ErrorCode::EofInElement);
+        }
+        for id in std::mem::take(&mut self.open) {
+            self.close_span(id, end);
+        }
+    }
+
+    /* Text that the whitespace options reduce to nothing is left out */
+    fn copy_children(&self, document: &mut Document, id: usize, parent: NodeId, preserve: bool) {
+        for &child in &self.nodes[id].children {
+            let node = match &self.nodes[child].data {
+                Node::Text(text) if !preserve => Node::Text(self.whitespace(text)),
+                other => other.clone(),
+            };
+            if node.as_text() == Some("") {
+                continue;
+            }
+            let preserve = preserve || self.namespace(child) == Namespace::Html && self.tag(child).preserves_whitespace();
+            let copy = document.add(node);
+            document.append(parent, copy);
+            self.copy_children(document, child, copy, preserve);
+        }
+    }
+
+    /* Applies the whitespace options to text outside of elements that preserve it */
+    fn whitespace(&self, text: &str) -> String {
+        if !self.options.keep_whitespace_only_text && text.chars().all(is_whitespace) {
+            return String::new();
+        }
+        if self.options.whitespace == Whitespace::Preserve {
+            return text.to_string();
+        }
+        return collapse(text);
+    }
+
+    /* Collapses the text below id and removes the spaces that do not render: those at the start and end of a
+    block, and those right after another space even when an inline element is in between */
+    fn trim_whitespace(&mut self, id: usize, trim: &mut Trim) {
+        for child in self.nodes[id].children.clone() {
+            let tag = match &self.nodes[child].data {
+                Node::Text(text) if !self.options.keep_whitespace_only_text && text.chars().all(is_whitespace) => continue,
+                Node::Text(text) => {
+                    let mut text = collapse(text);
+                    if trim.space {
+                        text = text.trim_start_matches(' ').to_string();
+                    }
+                    if !text.is_empty() {
+                        trim.space = text.ends_with(' ');
+                        trim.last = Some(child);
+                    }
+                    self.nodes[child].data = Node::Text(text);
+                    continue;
+                }
+                Node::Element(element) => element.tag.clone(),
+                _ => continue,
+            };
+            let preserve = tag.preserves_whitespace();
+            if tag.is_block() || matches!(tag.name(), "br" | "head") {
+                self.trim_block_boundary(trim);
+                if !preserve {
+                    self.trim_whitespace(child, trim);
+                }
+                self.trim_block_boundary(trim);
+            } else if tag.is_metadata() {
+                /* Not rendered along with the text around it, so its own text is trimmed on its own */
+                if !preserve {
+                    let mut own = Trim { last: None, space: true };
+                    self.trim_whitespace(child, &mut own);
+                    self.trim_block_boundary(&mut own);
+                }
+            } else if tag.is_void() || preserve {
+                trim.last = None;
+                trim.space = false;
+            } else {
+                self.trim_whitespace(child, trim);
+            }
+        }
+    }
+
+    fn trim_block_boundary(&mut self, trim: &mut Trim) {
+        if let Some(last) = trim.last.take() {
+            if let Node::Text(text) = &mut self.nodes[last].data {
+                text.truncate(text.trim_end_matches(' ').len());
+            }
+        }
+        trim.space = true;
+    }
+
+    fn process(&mut self, token: Token) {
+        self.span = token.span;
+        self.end_tag = if is_end(&token) { token.tag.name().to_string() } else { String::new() };
+        self.position = token.span.map(|span| span.start);
+        if token.tag == Tag::Doctype && self.mode != Mode::Initial {
+            self.error(ErrorCode::UnexpectedDoctype);
+        }
+
+        let mut token = token;
+        if std::mem::take(&mut self.skip_newline) && is_text(&token) {
+            /* A newline right after <pre> is not part of its content, see https://html.spec.whatwg.org/#parsing-main-inbody */
+            if token.data.starts_with('\n') {
+                token.data.remove(0);
+            }
+        }
+        if !is_text(&token) || !token.data.is_empty() {
+            while let Some(reprocess) = self.tree_construction(token) {
+                token = reprocess;
+            }
+        }
+        if let Some(span) = self.span {
+            self.end = Some(span.end);
+        }
+    }
+
+    /* Elements closed by their own end tag include it, elements closed implicitly end where the token starts */
+    fn closed(&mut self, id: usize) {
+        if let Some(span) = self.span {
+            let end = if self.tag(id).name().eq_ignore_ascii_case(&self.end_tag) { span.end } else { span.start };
+            self.close_span(id, end);
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let id = self.open.pop()?;
+        self.closed(id);
+        return Some(id);
+    }
+
+    fn truncate_open(&mut self, length: usize) {
+        while self.open.len() > length {
+            self.pop();
+        }
+    }
+
+    fn remove_open(&mut self, id: usize) {
+        if let Some(pos) = self.open.iter().position(|&open| open == id) {
+            self.open.remove(pos);
+            self.closed(id);
+        }
+    }
+
+    fn error(&mut self, code: ErrorCode) {
+        if let Some(position) = self.position {
+            self.errors.push(ParseError::new(code, position));
+        }
+    }
+
+    /* Reports text or a tag that is not allowed where it is */
+    fn unexpected(&mut self, token: &Token) {
+        match token.tag_type {
+            TokenType::Content => self.error(ErrorCode::UnexpectedText),
+            TokenType::Close => self.error(ErrorCode::UnexpectedEndTag),
+            TokenType::Open | TokenType::SelfClosing => self.error(ErrorCode::UnexpectedStartTag),
+            _ => {}
+        }
+    }
+
+    fn close_span(&mut self, id: usize, end: Position) {
+        if let Node::Element(element) = &mut self.nodes[id].data {
+            if let Some(span) = &mut element.span {
+                span.end = end.max(span.end);
+            }
+        }
+    }
+
+    /* Tokens in SVG and MathML content have rules of their own, see https://html.spec.whatwg.org/#tree-construction-dispatcher */
+    fn tree_construction(&mut self, token: Token) -> Option<Token> {
+        if self.in_html_content(&token) {
+            return self.dispatch(self.mode, token);
+        }
+        return self.in_foreign_content(token);
+    }
+
+    fn in_html_content(&self, token: &Token) -> bool {
+        let current = match self.adjusted_current() {
+            Some(current) => current,
+            None => return true,
+        };
+        if self.namespace(current) == Namespace::Html {
+            return true;
+        }
+        let start = is_start(token);
+        if self.is_text_integration_point(current) && (start && !start_tag(token, &["mglyph", "malignmark"]) || is_text(token)) {
+            return true;
+        }
+        if self.namespace(current) == Namespace::MathMl && self.tag(current).name() == "annotation-xml" && start_tag(token, &["svg"]) {
+            return true;
+        }
+        return self.is_html_integration_point(current) && (start || is_text(token));
+    }
+
+    /* Handles the token with the rules of the given mode, returns the token if it has to be reprocessed */
+    fn dispatch(&mut self, mode: Mode, token: Token) -> Option<Token> {
+        match mode {
+            Mode::Initial => self.initial(token),
+            Mode::BeforeHtml => self.before_html(token),
+            Mode::BeforeHead => self.before_head(token),
+            Mode::InHead => self.in_head(token),
+            Mode::InHeadNoscript => self.in_head_noscript(token),
+            Mode::AfterHead => self.after_head(token),
+            Mode::InBody => self.in_body(token),
+            Mode::Text => self.text(token),
+            Mode::InTable => self.in_table(token),
+            Mode::InCaption => self.in_caption(token),
+            Mode::InColumnGroup => self.in_column_group(token),
+            Mode::InTableBody => self.in_table_body(token),
+            Mode::InRow => self.in_row(token),
+            Mode::InCell => self.in_cell(token),
+            Mode::InSelect => self.in_select(token),
+            Mode::InSelectInTable => self.in_select_in_table(token),
+            Mode::InTemplate => self.in_template(token),
+            Mode::InFrameset => self.in_frameset(token),
+            Mode::AfterBody => self.after_body(token),
+            Mode::AfterFrameset => self.after_frameset(token),
+            Mode::AfterAfterBody => self.after_after_body(token),
+            Mode::AfterAfterFrameset => self.after_after_frameset(token),
+        }
+    }
+
+    /* ---------- Tree helpers ---------- */
+
+    /* SVG and MathML elements have no name here, so they never match the HTML elements the rules look for */
+    fn name(&self, id: usize) -> &str {
+        match &self.nodes[id].data {
+            Node::Element(element) if self.nodes[id].namespace == Namespace::Html => element.tag.name(),
+            _ => "",
+        }
+    }
+
+    fn namespace(&self, id: usize) -> Namespace {
+        self.nodes[id].namespace
+    }
+
+    /* See https://html.spec.whatwg.org/#special */
+    fn is_special(&self, id: usize) -> bool {
+        match self.namespace(id) {
+            Namespace::Html => self.tag(id).is_special(),
+            Namespace::MathMl => matches!(self.tag(id).name(), "mi" | "mo" | "mn" | "ms" | "mtext" | "annotation-xml"),
+            Namespace::Svg => matches!(self.tag(id).name(), "foreignObject" | "desc" | "title"),
+        }
+    }
+
+    fn is_text_integration_point(&self, id: usize) -> bool {
+        self.namespace(id) == Namespace::MathMl && matches!(self.tag(id).name(), "mi" | "mo" | "mn" | "ms" | "mtext")
+    }
+
+    /* Foreign elements whose content is parsed as HTML again */
+    fn is_html_integration_point(&self, id: usize) -> bool {
+        match self.namespace(id) {
+            Namespace::Html => false,
+            Namespace::MathMl => {
+                let encoding = self.element(id).attributes.as_ref().and_then(|attributes| attributes.get("encoding"));
+                self.tag(id).name() == "annotation-xml"
+                    && encoding.is_some_and(|encoding| {
+                        encoding.eq_ignore_ascii_case("text/html") || encoding.eq_ignore_ascii_case("application/xhtml+xml")
+                    })
+            }
+            Namespace::Svg => matches!(self.tag(id).name(), "foreignObject" | "desc" | "title"),
+        }
+    }
+
+    /* The context element stands in for the <html> element of a fragment, see https://html.spec.whatwg.org/#adjusted-current-node */
+    fn adjusted_current(&self) -> Option<usize> {
+        match self.context_element {
+            Some(context) if self.open.len() == 1 => Some(context),
+            _ => self.open.last().copied(),
+        }
+    }
+
+    fn tag(&self, id: usize) -> &Tag {
+        match &self.nodes[id].data {
+            Node::Element(element) => &element.tag,
+            _ => &Tag::None,
+        }
+    }
+
+    fn element(&self, id: usize) -> &Element {
+        match &self.nodes[id].data {
+            Node::Element(element) => element,
+            _ => unreachable!("only elements are on the stack of open elements"),
+        }
+    }
+
+    fn current(&self) -> usize {
+        *self.open.last().unwrap_or(&0)
+    }
+
+    fn current_is(&self, names: &[&str]) -> bool {
+        !self.open.is_empty() && names.contains(&self.name(self.current()))
+    }
+
+    fn add_node(&mut self, data: Node) -> usize {
+        self.nodes.push(TreeNode { data, parent: None, children: Vec::new(), namespace: Namespace::Html });
+        return self.nodes.len() - 1;
+    }
+
+    fn detach(&mut self, id: usize) {
+        if let Some(parent) = self.nodes[id].parent.take() {
+            self.nodes[parent].children.retain(|&child| child != id);
+        }
+    }
+
+    fn insert_at(&mut self, parent: usize, before: Option<usize>, id: usize) {
+        self.detach(id);
+        self.nodes[id].parent = Some(parent);
+        match before {
+            Some(index) => self.nodes[parent].children.insert(index, id),
+            None => self.nodes[parent].children.push(id),
+        }
+    }
+
+    /* Returns the parent and the child index to insert before, taking foster parenting into account */
+    fn insertion_place(&self, target: Option<usize>) -> (usize, Option<usize>) {
+        /* Like browsers, nesting deeper than MAX_DEPTH adds siblings instead so the tree stays shallow enough to walk */
+        let target = target.unwrap_or(match self.open.get(MAX_DEPTH) {
+            Some(_) => self.open[MAX_DEPTH - 1],
+            None => self.current(),
+        });
+        if self.foster_parenting && ["table", "tbody", "tfoot", "thead", "tr"].contains(&self.name(target)) {
+            let last_table = self.open.iter().rposition(|&id| self.name(id) == "table");
+            return match last_table {
+                None => (self.open[0], None),
+                Some(pos) => {
+                    let table = self.open[pos];
+                    match self.nodes[table].parent {
+                        Some(parent) => {
+                            let index = self.nodes[parent].children.iter().position(|&child| child == table);
+                            (parent, index)
+                        }
+                        None => (self.open[pos - 1], None),
+                    }
+                }
+            };
+        }
+        return (target, None);
+    }
+
+    fn attributes(&self, token: &Token) -> Option<Attributes> {
+        if token.attributes.is_empty() {
+            return None;
+        }
+        let mut attributes = Attributes::new();
+        for attribute in &token.attributes {
+            attributes.push(attribute.clone());
+        }
+        return Some(attributes);
+    }
+
+    fn create_element(&mut self, token: &Token, namespace: Namespace) -> usize {
+        /* "/>" only closes void elements and foreign elements, on other elements it is ignored */
+        if token.tag_type == TokenType::SelfClosing && !token.tag.is_void() && namespace == Namespace::Html {
+            self.error(ErrorCode::NonVoidHtmlElementStartTagWithTrailingSolidus);
+        }
+        let mut element = Element::new(token.tag.clone(), self.attributes(token), Vec::new());
+        element.span = token.span;
+        let id = self.add_node(Node::Element(element));
+        self.nodes[id].namespace = namespace;
+        return id;
+    }
+
+    /* Inserts an SVG or MathML element, "/>" closes it right away */
+    fn insert_foreign(&mut self, token: &Token, namespace: Namespace) {
+        let mut token = token.clone();
+        adjust_foreign(&mut token, namespace);
+        let id = self.create_element(&token, namespace);
+        let (parent, before) = self.insertion_place(None);
+        self.insert_at(parent, before, id);
+        if token.tag_type != TokenType::SelfClosing {
+            self.open.push(id);
+        }
+    }
+
+    fn insert_element(&mut self, token: &Token) -> usize {
+        let id = self.insert_leaf(token);
+        /* The content of script, style, title, textarea and the like is text, SVG and MathML elements of those names never get here */
+        let state = content_state(&token.tag);
+        if state != State::Data {
+            self.tokenizer_state = Some(state);
+        }
+        /* Void elements never have children, so they are closed right away */
+        if !token.tag.is_void() {
+            self.open.push(id);
+        }
+        return id;
+    }
+
+    /* Inserts the element without putting it on the stack of open elements */
+    fn insert_leaf(&mut self, token: &Token) -> usize {
+        let id = self.create_element(token, Namespace::Html);
+        let (parent, before) = self.insertion_place(None);
+        self.insert_at(parent, before, id);
+        return id;
+    }
+
+    fn insert_synthetic(&mut self, name: &str) -> usize {
+        let token = Token {
+            tag: DOM::parse_tag(name),
+            raw: format!("<{}>", name),
+            tag_type: TokenType::Open,
+            data: String::new(),
+            span: None,
+            attributes: Vec::new(),
+            doctype: None,
+        };
+        return self.insert_element(&token);
+    }
+
+    fn insert_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let (parent, before) = self.insertion_place(None);
+        let previous = match before {
+            Some(0) => None,
+            Some(index) => Some(self.nodes[parent].children[index - 1]),
+            None => self.nodes[parent].children.last().copied(),
+        };
+        if let Some(previous) = previous {
+            if let Node::Text(existing) = &mut self.nodes[previous].data {
+                existing.push_str(text);
+                return;
+            }
+        }
+        let id = self.add_node(Node::Text(text.to_string()));
+        self.insert_at(parent, before, id);
+    }
+
+    /* Doctype, XML declarations, PHP blocks, comments, CDATA and processing instructions */
+    fn insert_other(&mut self, token: &Token, parent: Option<usize>) {
+        let data = token.data.clone();
+        let node = match &token.tag {
+            Tag::Doctype => Node::Doctype(token.doctype.clone().unwrap_or_default()),
+            Tag::XML => Node::XML(data),
+            Tag::PHP => Node::PHP(data),
+            Tag::CDATA => Node::CData(data),
+            Tag::ProcessingInstruction(target) => Node::ProcessingInstruction(ProcessingInstruction { target: target.clone(), data }),
+            _ => Node::Comment(data),
+        };
+        let id = self.add_node(node);
+        match parent {
+            Some(parent) => self.insert_at(parent, None, id),
+            None => {
+                let (parent, before) = self.insertion_place(None);
+                self.insert_at(parent, before, id);
+            }
+        }
+    }
+
+    /* CDATA sections only belong in SVG and MathML, elsewhere they are kept but reported */
+    fn insert_comment(&mut self, token: &Token, parent: Option<usize>) {
+        if token.tag == Tag::CDATA && self.adjusted_current().is_none_or(|id| self.namespace(id) == Namespace::Html) {
+            self.error(ErrorCode::CdataInHtmlContent);
+        }
+        self.insert_other(token, parent);
+    }
+
+    fn merge_attributes(&mut self, token: &Token, id: usize) {
+        let attributes = match self.attributes(token) {
+            Some(attributes) => attributes,
+            None => return,
+        };
+        if let Node::Element(element) = &mut self.nodes[id].data {
+            let existing = element.attributes.get_or_insert_with(Attributes::new);
+            for attribute in attributes.iter() {
+                existing.push(attribute.clone());
+            }
+        }
+    }
+
+    /* ---------- Stack of open elements ---------- */
+
+    fn in_scope_where(&self, matches: impl Fn(usize) -> bool, scope: Scope) -> bool {
+        for &id in self.open.iter().rev() {
+            if matches(id) {
+                return true;
+            }
+            let name = self.name(id);
+            let boundary = match scope {
+                Scope::Select => !["optgroup", "option"].contains(&name),
+                Scope::Table => ["html", "table", "template"].contains(&name),
+                _ => {
+                    ["applet", "caption", "html", "table", "td", "th", "marquee", "object", "template"].contains(&name)
+                        || (scope == Scope::ListItem && ["ol", "ul"].contains(&name))
+                        || (scope == Scope::Button && name == "button")
+                }
+            };
+            if boundary {
+                return false;
+            }
+        }
+        return false;
+    }
+
+    fn in_scope(&self, names: &[&str], scope: Scope) -> bool {
+        self.in_scope_where(|id| names.contains(&self.name(id)), scope)
+    }
+
+    fn pop_until(&mut self, names: &[&str]) {
+        while let Some(id) = self.pop() {
+            if names.contains(&self.name(id)) {
+                break;
+            }
+        }
+    }
+
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) {
+        while self.current_is(IMPLIED_END_TAGS) && Some(self.name(self.current())) != except {
+            self.pop();
+        }
+    }
+
+    /* Also closes the table structure, see https://html.spec.whatwg.org/#closing-elements-that-have-implied-end-tags */
+    fn generate_all_implied_end_tags(&mut self) {
+        while self.current_is(IMPLIED_END_TAGS) || self.current_is(&["caption", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"]) {
+            self.pop();
+        }
+    }
+
+    fn close_p_in_button_scope(&mut self) {
+        if self.in_scope(&["p"], Scope::Button) {
+            self.close_p();
+        }
+    }
+
+    fn close_p(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        self.check_current("p");
+        self.pop_until(&["p"]);
+    }
+
+    /* An end tag that closes elements still open inside its element is an error */
+    fn check_current(&mut self, name: &str) {
+        if !self.current_is(&[name]) {
+            self.error(ErrorCode::MisnestedEndTag);
+        }
+    }
+
+    fn clear_stack_to(&mut self, names: &[&str]) {
+        while !self.open.is_empty() && !self.current_is(names) {
+            self.pop();
+        }
+    }
+
+    fn reset_insertion_mode(&mut self) {
+        for (index, &id) in self.open.iter().enumerate().rev() {
+            let last = index == 0;
+            let name = match &self.context {
+                Some(context) if last => context.name(),
+                _ => self.name(id),
+            };
+            self.mode = match name {
+                "select" => {
+                    let in_table = self.open[..index].iter().any(|&ancestor| self.name(ancestor) == "table");
+                    if in_table { Mode::InSelectInTable } else { Mode::InSelect }
+                }
+                "td" | "th" if !last => Mode::InCell,
+                "tr" => Mode::InRow,
+                "tbody" | "thead" | "tfoot" => Mode::InTableBody,
+                "caption" => Mode::InCaption,
+                "colgroup" => Mode::InColumnGroup,
+                "table" => Mode::InTable,
+                "template" => *self.template_modes.last().unwrap_or(&Mode::InBody),
+                "head" if !last => Mode::InHead,
+                "body" => Mode::InBody,
+                "frameset" => Mode::InFrameset,
+                "html" if self.head.is_none() => Mode::BeforeHead,
+                "html" => Mode::AfterHead,
+                _ if last => Mode::InBody,
+                _ => continue,
+            };
+            return;
+        }
+        self.mode = Mode::InBody;
+    }
+
+    /* ---------- List of active formatting elements ---------- */
+
+    fn formatting_position(&self, id: usize) -> Option<usize> {
+        self.formatting.iter().position(|&entry| entry == Formatting::Element(id))
+    }
+
+    fn push_formatting(&mut self, id: usize) {
+        /* Noah's Ark clause, at most three identical elements after the last marker */
+        let start = self.formatting.iter().rposition(|&entry| entry == Formatting::Marker).map(|pos| pos + 1).unwrap_or(0);
+        let element = self.element(id);
+        let identical = (start..self.formatting.len())
+            .filter(|&pos| match self.formatting[pos] {
+                Formatting::Element(other) => {
+                    let other = self.element(other);
+                    other.tag == element.tag && other.attributes == element.attributes
+                }
+                Formatting::Marker => false,
+            })
+            .collect::<Vec<usize>>();
+        if identical.len() >= 3 {
+            self.formatting.remove(identical[0]);
+        }
+        self.formatting.push(Formatting::Element(id));
+    }
+
+    fn clear_formatting_to_marker(&mut self) {
+        while let Some(entry) = self.formatting.pop() {
+            if entry == Formatting::Marker {
+                break;
+            }
+        }
+    }
+
+    fn clone_element(&mut self, id: usize) -> usize {
+        let element = self.element(id);
+        let copy = Element::new(element.tag.clone(), element.attributes.clone(), Vec::new());
+        return self.add_node(Node::Element(copy));
+    }
+
+    fn reconstruct_formatting(&mut self) {
+        let mut index = match self.formatting.last() {
+            None | Some(Formatting::Marker) => return,
+            Some(Formatting::Element(id)) if self.open.contains(id) => return,
+            Some(_) => self.formatting.len() - 1,
+        };
+        while index > 0 {
+            index -= 1;
+            let stop = match self.formatting[index] {
+                Formatting::Marker => true,
+                Formatting::Element(id) => self.open.contains(&id),
+            };
+            if stop {
+                index += 1;
+                break;
+            }
+        }
+        for pos in index..self.formatting.len() {
+            if let Formatting::Element(id) = self.formatting[pos] {
+                let copy = self.clone_element(id);
+                let (parent, before) = self.insertion_place(None);
+                self.insert_at(parent, before, copy);
+                self.open.push(copy);
+                self.formatting[pos] = Formatting::Element(copy);
+            }
+        }
+    }
+
+    /* Returns false when the end tag should be handled like any other end tag */
+    fn adoption_agency(&mut self, subject: &str) -> bool {
+        let current = self.current();
+        if self.name(current) == subject && self.formatting_position(current).is_none() {
+            self.pop();
+            return true;
+        }
+        for iteration in 0..8 {
+            let marker = self.formatting.iter().rposition(|&entry| entry == Formatting::Marker).map(|pos| pos + 1).unwrap_or(0);
+            let found = (marker..self.formatting.len()).rev().find_map(|pos| match self.formatting[pos] {
+                Formatting::Element(id) if self.name(id) == subject => Some((pos, id)),
+                _ => None,
+            });
+            let (formatting_pos, formatting_element) = match found {
+                Some(found) => found,
+                None => return false,
+            };
+            let stack_pos = match self.open.iter().position(|&id| id == formatting_element) {
+                Some(pos) => pos,
+                None => {
+                    self.error(ErrorCode::UnexpectedEndTag);
+                    self.formatting.remove(formatting_pos);
+                    return true;
+                }
+            };
+            if !self.in_scope_where(|id| id == formatting_element, Scope::Default) {
+                self.error(ErrorCode::UnexpectedEndTag);
+                return true;
+            }
+            if iteration == 0 && formatting_element != self.current() {
+                self.error(ErrorCode::MisnestedEndTag);
+            }
+            let furthest_pos = (stack_pos + 1..self.open.len()).find(|&pos| self.is_special(self.open[pos]));
+            let furthest_pos = match furthest_pos {
+                Some(pos) => pos,
+                None => {
+                    self.truncate_open(stack_pos);
+                    self.formatting.remove(formatting_pos);
+                    return true;
+                }
+            };
+            let furthest_block = self.open[furthest_pos];
+            let common_ancestor = self.open[stack_pos - 1];
+            let mut bookmark = formatting_pos;
+            let mut node_pos = furthest_pos;
+            let mut last_node = furthest_block;
+            let mut counter = 0;
+            loop {
+                counter += 1;
+                node_pos -= 1;
+                let node = self.open[node_pos];
+                if node == formatting_element {
+                    break;
+                }
+                let mut position = self.formatting_position(node);
+                if counter > 3 {
+                    if let Some(pos) = position {
+                        self.formatting.remove(pos);
+                        if pos < bookmark {
+                            bookmark -= 1;
+                        }
+                        position = None;
+                    }
+                }
+                let position = match position {
+                    Some(pos) => pos,
+                    None => {
+                        self.remove_open(node);
+                        continue;
+                    }
+                };
+                let copy = self.clone_element(node);
+                self.formatting[position] = Formatting::Element(copy);
+                self.open[node_pos] = copy;
+                if last_node == furthest_block {
+                    bookmark = position + 1;
+                }
+                self.insert_at(copy, None, last_node);
+                last_node = copy;
+            }
+
+            self.detach(last_node);
+            let (parent, before) = self.insertion_place(Some(common_ancestor));
+            self.insert_at(parent, before, last_node);
+
+            let copy = self.clone_element(formatting_element);
+            let children = std::mem::take(&mut self.nodes[furthest_block].children);
+            for &child in &children {
+                self.nodes[child].parent = Some(copy);
+            }
+            self.nodes[copy].children = children;
+            self.insert_at(furthest_block, None, copy);
+
+            if let Some(pos) = self.formatting_position(formatting_element) {
+                self.formatting.remove(pos);
+                if pos < bookmark {
+                    bookmark -= 1;
+                }
+            }
+            self.formatting.insert(bookmark.min(self.formatting.len()), Formatting::Element(copy));
+            self.remove_open(formatting_element);
+            let furthest_pos = self.open.iter().position(|&id| id == furthest_block).unwrap_or(self.open.len() - 1);
+            self.open.insert(furthest_pos + 1, copy);
+        }
+        return true;
+    }
+
+    /* ---------- Insertion modes ---------- */
+
+    fn initial(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (_, rest) = split_whitespace(&token);
+            let rest = rest?;
+            self.error(ErrorCode::MissingDoctype);
+            self.quirks_mode = QuirksMode::Quirks;
+            self.mode = Mode::BeforeHtml;
+            return Some(rest);
+        }
+        if token.tag == Tag::Doctype {
+            let doctype = token.doctype.clone().unwrap_or_default();
+            if !doctype.is_conforming() {
+                self.error(ErrorCode::NonConformingDoctype);
+            }
+            self.quirks_mode = doctype.quirks_mode();
+            self.doctype = Some(doctype);
+            self.insert_other(&token, Some(0));
+            self.mode = Mode::BeforeHtml;
+            return None;
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, Some(0));
+            return None;
+        }
+        self.error(ErrorCode::MissingDoctype);
+        self.quirks_mode = QuirksMode::Quirks;
+        self.mode = Mode::BeforeHtml;
+        return Some(token);
+    }
+
+    fn before_html(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (_, rest) = split_whitespace(&token);
+            let rest = rest?;
+            self.insert_synthetic("html");
+            self.mode = Mode::BeforeHead;
+            return Some(rest);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, Some(0));
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if matches!(token.tag_type, TokenType::PHP) || token.tag == Tag::XML {
+            self.insert_other(&token, Some(0));
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            self.insert_element(&token);
+            self.mode = Mode::BeforeHead;
+            return None;
+        }
+        if is_end(&token) && !["head", "body", "html", "br"].contains(&token.tag.name()) {
+            self.error(ErrorCode::UnexpectedEndTag);
+            return None;
+        }
+        self.insert_synthetic("html");
+        self.mode = Mode::BeforeHead;
+        return Some(token);
+    }
+
+    fn before_head(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (_, rest) = split_whitespace(&token);
+            let rest = rest?;
+            self.head = Some(self.insert_synthetic("head"));
+            self.mode = Mode::InHead;
+            return Some(rest);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if start_tag(&token, &["head"]) {
+            self.head = Some(self.insert_element(&token));
+            self.mode = Mode::InHead;
+            return None;
+        }
+        if is_end(&token) && !["head", "body", "html", "br"].contains(&token.tag.name()) {
+            self.error(ErrorCode::UnexpectedEndTag);
+            return None;
+        }
+        self.head = Some(self.insert_synthetic("head"));
+        self.mode = Mode::InHead;
+        return Some(token);
+    }
+
+    fn in_head(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (whitespace, rest) = split_whitespace(&token);
+            self.insert_text(&whitespace);
+            let rest = rest?;
+            self.close_head();
+            return Some(rest);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if matches!(token.tag_type, TokenType::PHP) || token.tag == Tag::XML {
+            self.insert_other(&token, None);
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if start_tag(&token, &["title", "noframes", "style", "script"]) {
+            self.insert_element(&token);
+            self.original_mode = self.mode;
+            self.mode = Mode::Text;
+            return None;
+        }
+        /* Scripting is disabled, so noscript is parsed as markup */
+        if start_tag(&token, &["noscript"]) {
+            self.insert_element(&token);
+            self.mode = Mode::InHeadNoscript;
+            return None;
+        }
+        if start_tag(&token, &["template"]) {
+            self.insert_element(&token);
+            self.formatting.push(Formatting::Marker);
+            self.mode = Mode::InTemplate;
+            self.template_modes.push(Mode::InTemplate);
+            return None;
+        }
+        if end_tag(&token, &["template"]) {
+            if !self.open.iter().any(|&id| self.name(id) == "template") {
+                self.error(ErrorCode::UnexpectedEndTag);
+                return None;
+            }
+            self.generate_all_implied_end_tags();
+            self.check_current("template");
+            self.pop_until(&["template"]);
+            self.clear_formatting_to_marker();
+            self.template_modes.pop();
+            self.reset_insertion_mode();
+            return None;
+        }
+        if end_tag(&token, &["head"]) {
+            self.close_head();
+            return None;
+        }
+        if start_tag(&token, &["head"]) {
+            self.error(ErrorCode::UnexpectedStartTag);
+            return None;
+        }
+        if is_end(&token) && !["body", "html", "br"].contains(&token.tag.name()) {
+            self.error(ErrorCode::UnexpectedEndTag);
+            return None;
+        }
+        if is_start(&token) && !HEAD_CONTENT.contains(&token.tag.name()) || is_end(&token) {
+            self.close_head();
+            return Some(token);
+        }
+        /* Remaining metadata content stays inside head */
+        self.insert_element(&token);
+        return None;
+    }
+
+    /* Leaves the head, closing whatever is still open inside it */
+    fn close_head(&mut self) {
+        if self.open.iter().any(|&id| self.name(id) == "head") {
+            self.pop_until(&["head"]);
+        }
+        self.mode = Mode::AfterHead;
+    }
+
+    fn in_head_noscript(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (whitespace, rest) = split_whitespace(&token);
+            self.insert_text(&whitespace);
+            let rest = rest?;
+            self.error(ErrorCode::UnexpectedText);
+            self.pop();
+            self.mode = Mode::InHead;
+            return Some(rest);
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if matches!(token.tag_type, TokenType::Comment | TokenType::PHP) || token.tag == Tag::XML {
+            return self.in_head(token);
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if end_tag(&token, &["noscript"]) {
+            self.pop();
+            self.mode = Mode::InHead;
+            return None;
+        }
+        if start_tag(&token, &["basefont", "bgsound", "link", "meta", "noframes", "style"]) {
+            return self.in_head(token);
+        }
+        if start_tag(&token, &["head", "noscript"]) {
+            self.error(ErrorCode::UnexpectedStartTag);
+            return None;
+        }
+        if is_end(&token) && token.tag.name() != "br" {
+            self.error(ErrorCode::UnexpectedEndTag);
+            return None;
+        }
+        self.unexpected(&token);
+        self.pop();
+        self.mode = Mode::InHead;
+        return Some(token);
+    }
+
+    fn after_head(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (whitespace, rest) = split_whitespace(&token);
+            self.insert_text(&whitespace);
+            let rest = rest?;
+            self.insert_synthetic("body");
+            self.mode = Mode::InBody;
+            return Some(rest);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if start_tag(&token, &["body"]) {
+            self.insert_element(&token);
+            self.mode = Mode::InBody;
+            return None;
+        }
+        if start_tag(&token, &["frameset"]) {
+            self.insert_element(&token);
+            self.mode = Mode::InFrameset;
+            return None;
+        }
+        if start_tag(&token, HEAD_CONTENT) {
+            let head = self.head?;
+            self.open.push(head);
+            let reprocess = self.in_head(token);
+            self.remove_open(head);
+            return reprocess;
+        }
+        if end_tag(&token, &["template"]) {
+            return self.in_head(token);
+        }
+        if start_tag(&token, &["head"]) {
+            self.error(ErrorCode::UnexpectedStartTag);
+            return None;
+        }
+        if is_end(&token) && !["body", "html", "br"].contains(&token.tag.name()) {
+            self.error(ErrorCode::UnexpectedEndTag);
+            return None;
+        }
+        self.insert_synthetic("body");
+        self.mode = Mode::InBody;
+        return Some(token);
+    }
+
+    /* See https://html.spec.whatwg.org/#parsing-main-inforeign */
+    fn in_foreign_content(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            if token.data.contains('\0') {
+                self.error(ErrorCode::UnexpectedNullCharacter);
+            }
+            self.insert_text(&token.data.replace('\0', "\u{FFFD}"));
+            return None;
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if matches!(token.tag_type, TokenType::PHP) || token.tag == Tag::XML {
+            self.insert_other(&token, None);
+            return None;
+        }
+        let font = start_tag(&token, &["font"]) && ["color", "face", "size"].iter().any(|name| token.attributes.iter().any(|attribute| attribute.name == *name));
+        if start_tag(&token, BREAKOUT) || font || end_tag(&token, &["br", "p"]) {
+            self.unexpected(&token);
+            loop {
+                let current = self.current();
+                if self.namespace(current) == Namespace::Html || self.is_text_integration_point(current) || self.is_html_integration_point(current) {
+                    break;
+                }
+                self.pop();
+            }
+            return self.dispatch(self.mode, token);
+        }
+        if is_start(&token) {
+            let namespace = self.adjusted_current().map_or(Namespace::Html, |current| self.namespace(current));
+            self.insert_foreign(&token, namespace);
+            return None;
+        }
+
+        /* Foreign end tags close the nearest element of that name, up to the first HTML element */
+        let name = token.tag.name();
+        if !self.tag(self.current()).name().eq_ignore_ascii_case(name) {
+            self.error(ErrorCode::MisnestedEndTag);
+        }
+        for pos in (1..self.open.len()).rev() {
+            let id = self.open[pos];
+            if self.tag(id).name().eq_ignore_ascii_case(name) {
+                self.truncate_open(pos);
+                return None;
+            }
+            if self.namespace(self.open[pos - 1]) == Namespace::Html {
+                return self.dispatch(self.mode, token);
+            }
+        }
+        return None;
+    }
+
+    fn in_body(&mut self, token: Token) -> Option<Token> {
+        let name = token.tag.name().to_string();
+        let name = name.as_str();
+
+        if is_text(&token) {
+            let text = token.data.replace('\0', "");
+            if !text.is_empty() {
+                self.reconstruct_formatting();
+                self.insert_text(&text);
+            }
+            return None;
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if matches!(token.tag_type, TokenType::PHP) || token.tag == Tag::XML {
+            self.insert_other(&token, None);
+            return None;
+        }
+
+        if is_start(&token) {
+            match name {
+                "html" => {
+                    self.error(ErrorCode::UnexpectedStartTag);
+                    if let Some(&html) = self.open.first() {
+                        self.merge_attributes(&token, html);
+                    }
+                }
+                _ if HEAD_CONTENT.contains(&name) => return self.in_head(token),
+                "body" => {
+                    self.error(ErrorCode::UnexpectedStartTag);
+                    if self.open.len() > 1 && self.name(self.open[1]) == "body" {
+                        let body = self.open[1];
+                        self.merge_attributes(&token, body);
+                    }
+                }
+                "frameset" => {}
+                _ if is_plain_block(&token.tag) => {
+                    self.close_p_in_button_scope();
+                    self.insert_element(&token);
+                }
+                _ if HEADINGS.contains(&name) => {
+                    self.close_p_in_button_scope();
+                    if self.current_is(HEADINGS) {
+                        self.pop();
+                    }
+                    self.insert_element(&token);
+                }
+                "pre" | "listing" => {
+                    self.close_p_in_button_scope();
+                    self.insert_element(&token);
+                    self.skip_newline = true;
+                }
+                "form" => {
+                    if self.form.is_some() {
+                        self.error(ErrorCode::UnexpectedStartTag);
+                        return None;
+                    }
+                    self.close_p_in_button_scope();
+                    self.form = Some(self.insert_element(&token));
+                }
+                "li" | "dd" | "dt" => {
+                    let closes: &[&str] = if name == "li" { &["li"] } else { &["dd", "dt"] };
+                    for &id in self.open.clone().iter().rev() {
+                        let node = self.name(id).to_string();
+                        if closes.contains(&node.as_str()) {
+                            self.generate_implied_end_tags(Some(&node));
+                            self.pop_until(&[node.as_str()]);
+                            break;
+                        }
+                        if self.is_special(id) && !["address", "div", "p"].contains(&node.as_str()) {
+                            break;
+                        }
+                    }
+                    self.close_p_in_button_scope();
+                    self.insert_element(&token);
+                }
+                "plaintext" => {
+                    self.close_p_in_button_scope();
+                    self.insert_element(&token);
+                }
+                "button" => {
+                    if self.in_scope(&["button"], Scope::Default) {
+                        self.generate_implied_end_tags(None);
+                        self.pop_until(&["button"]);
+                    }
+                    self.reconstruct_formatting();
+                    self.insert_element(&token);
+                }
+                "a" => {
+                    let marker = self.formatting.iter().rposition(|&entry| entry == Formatting::Marker).map(|pos| pos + 1).unwrap_or(0);
+                    let existing = self.formatting[marker..].iter().rev().find_map(|&entry| match entry {
+                        Formatting::Element(id) if self.name(id) == "a" => Some(id),
+                        _ => None,
+                    });
+                    if let Some(existing) = existing {
+                        self.error(ErrorCode::UnexpectedStartTagImpliesEndTag);
+                        self.adoption_agency("a");
+                        if let Some(pos) = self.formatting_position(existing) {
+                            self.formatting.remove(pos);
+                        }
+                        self.remove_open(existing);
+                    }
+                    self.reconstruct_formatting();
+                    let id = self.insert_element(&token);
+                    self.push_formatting(id);
+                }
+                "nobr" => {
+                    self.reconstruct_formatting();
+                    if self.in_scope(&["nobr"], Scope::Default) {
+                        self.error(ErrorCode::UnexpectedStartTagImpliesEndTag);
+                        self.adoption_agency("nobr");
+                        self.reconstruct_formatting();
+                    }
+                    let id = self.insert_element(&token);
+                    self.push_formatting(id);
+                }
+                _ if token.tag.is_formatting() => {
+                    self.reconstruct_formatting();
+                    let id = self.insert_element(&token);
+                    self.push_formatting(id);
+                }
+                "applet" | "marquee" | "object" => {
+                    self.reconstruct_formatting();
+                    self.insert_element(&token);
+                    self.formatting.push(Formatting::Marker);
+                }
+                "table" => {
+                    /* Quirks mode lets tables nest inside paragraphs */
+                    if self.quirks_mode != QuirksMode::Quirks {
+                        self.close_p_in_button_scope();
+                    }
+                    self.insert_element(&token);
+                    self.mode = Mode::InTable;
+                }
+                "hr" => {
+                    self.close_p_in_button_scope();
+                    self.insert_leaf(&token);
+                }
+                "image" => {
+                    let mut token = token;
+                    token.tag = DOM::parse_tag("img");
+                    return Some(token);
+                }
+                "textarea" | "xmp" | "iframe" | "noembed" => {
+                    if name == "xmp" {
+                        self.close_p_in_button_scope();
+                        self.reconstruct_formatting();
+                    }
+                    self.insert_element(&token);
+                    self.skip_newline = name == "textarea";
+                    self.original_mode = self.mode;
+                    self.mode = Mode::Text;
+                }
+                "select" => {
+                    self.reconstruct_formatting();
+                    self.insert_element(&token);
+                    self.mode = match self.mode {
+                        Mode::InTable | Mode::InCaption | Mode::InTableBody | Mode::InRow | Mode::InCell => Mode::InSelectInTable,
+                        _ => Mode::InSelect,
+                    };
+                }
+                "optgroup" | "option" => {
+                    if self.current_is(&["option"]) {
+                        self.pop();
+                    }
+                    self.reconstruct_formatting();
+                    self.insert_element(&token);
+                }
+                "rb" | "rtc" => {
+                    if self.in_scope(&["ruby"], Scope::Default) {
+                        self.generate_implied_end_tags(None);
+                    }
+                    self.insert_element(&token);
+                }
+                "rp" | "rt" => {
+                    if self.in_scope(&["ruby"], Scope::Default) {
+                        self.generate_implied_end_tags(Some("rtc"));
+                    }
+                    self.insert_element(&token);
+                }
+                _ if TABLE_STRUCTURE.contains(&name) || name == "frame" || name == "head" => self.error(ErrorCode::UnexpectedStartTag),
+                "math" | "svg" => {
+                    self.reconstruct_formatting();
+                    self.insert_foreign(&token, if name == "svg" { Namespace::Svg } else { Namespace::MathMl });
+                }
+                _ => {
+                    self.reconstruct_formatting();
+                    self.insert_element(&token);
+                }
+            }
+            return None;
+        }
+
+        if is_end(&token) {
+            match name {
+                "template" => return self.in_head(token),
+                "br" => {
+                    /* "</br>" is treated as "<br>" */
+                    let mut token = token;
+                    token.tag_type = TokenType::Open;
+                    token.raw = "<br>".to_string();
+                    return Some(token);
+                }
+                "body" | "html" => {
+                    if !self.in_scope(&["body"], Scope::Default) {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                        return None;
+                    }
+                    if self.open.iter().any(|&id| !CLOSED_BY_EOF.contains(&self.name(id))) {
+                        self.error(ErrorCode::MisnestedEndTag);
+                    }
+                    self.mode = Mode::AfterBody;
+                    if name == "html" {
+                        return Some(token);
+                    }
+                }
+                _ if is_plain_block(&token.tag) && name != "p" || ["button", "listing", "pre"].contains(&name) => {
+                    if self.in_scope(&[name], Scope::Default) {
+                        self.generate_implied_end_tags(None);
+                        self.check_current(name);
+                        self.pop_until(&[name]);
+                    } else {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                    }
+                }
+                "form" => {
+                    let form = self.form.take();
+                    match form {
+                        Some(form) if self.in_scope_where(|id| id == form, Scope::Default) => {
+                            self.generate_implied_end_tags(None);
+                            if self.current() != form {
+                                self.error(ErrorCode::MisnestedEndTag);
+                            }
+                            self.remove_open(form);
+                        }
+                        _ => self.error(ErrorCode::UnexpectedEndTag),
+                    }
+                }
+                "p" => {
+                    if !self.in_scope(&["p"], Scope::Button) {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                        self.insert_synthetic("p");
+                    }
+                    self.close_p();
+                }
+                "li" => {
+                    if self.in_scope(&["li"], Scope::ListItem) {
+                        self.generate_implied_end_tags(Some("li"));
+                        self.check_current("li");
+                        self.pop_until(&["li"]);
+                    } else {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                    }
+                }
+                "dd" | "dt" => {
+                    if self.in_scope(&[name], Scope::Default) {
+                        self.generate_implied_end_tags(Some(name));
+                        self.check_current(name);
+                        self.pop_until(&[name]);
+                    } else {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                    }
+                }
+                _ if HEADINGS.contains(&name) => {
+                    if self.in_scope(HEADINGS, Scope::Default) {
+                        self.generate_implied_end_tags(None);
+                        self.check_current(name);
+                        self.pop_until(HEADINGS);
+                    } else {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                    }
+                }
+                _ if token.tag.is_formatting() => {
+                    if !self.adoption_agency(name) {
+                        self.any_other_end_tag(name);
+                    }
+                }
+                "applet" | "marquee" | "object" => {
+                    if self.in_scope(&[name], Scope::Default) {
+                        self.generate_implied_end_tags(None);
+                        self.check_current(name);
+                        self.pop_until(&[name]);
+                        self.clear_formatting_to_marker();
+                    } else {
+                        self.error(ErrorCode::UnexpectedEndTag);
+                    }
+                }
+                _ => self.any_other_end_tag(name),
+            }
+        }
+        return None;
+    }
+
+    fn any_other_end_tag(&mut self, name: &str) {
+        for pos in (0..self.open.len()).rev() {
+            let id = self.open[pos];
+            if self.name(id) == name {
+                self.generate_implied_end_tags(Some(name));
+                if self.current() != id {
+                    self.error(ErrorCode::MisnestedEndTag);
+                }
+                self.truncate_open(pos);
+                return;
+            }
+            if self.is_special(id) {
+                self.error(ErrorCode::UnexpectedEndTag);
+                return;
+            }
+        }
+    }
+
+    fn text(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            self.insert_text(&token.data);
+            return None;
+        }
+        if is_end(&token) {
+            self.pop();
+            self.mode = self.original_mode;
+        }
+        return None;
+    }
+
+    fn in_table(&mut self, token: Token) -> Option<Token> {
+        let name = token.tag.name().to_string();
+        let name = name.as_str();
+
+        if is_text(&token) && self.current_is(&["table", "tbody", "template", "tfoot", "thead", "tr"]) {
+            if token.data.chars().all(is_whitespace) {
+                self.insert_text(&token.data);
+                return None;
+            }
+            return self.foster(token);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if is_start(&token) {
+            match name {
+                "caption" => {
+                    self.clear_stack_to(&["table", "template", "html"]);
+                    self.formatting.push(Formatting::Marker);
+                    self.insert_element(&token);
+                    self.mode = Mode::InCaption;
+                    return None;
+                }
+                "colgroup" => {
+                    self.clear_stack_to(&["table", "template", "html"]);
+                    self.insert_element(&token);
+                    self.mode = Mode::InColumnGroup;
+                    return None;
+                }
+                "col" => {
+                    self.clear_stack_to(&["table", "template", "html"]);
+                    self.insert_synthetic("colgroup");
+                    self.mode = Mode::InColumnGroup;
+                    return Some(token);
+                }
+                "tbody" | "tfoot" | "thead" => {
+                    self.clear_stack_to(&["table", "template", "html"]);
+                    self.insert_element(&token);
+                    self.mode = Mode::InTableBody;
+                    return None;
+                }
+                "td" | "th" | "tr" => {
+                    self.clear_stack_to(&["table", "template", "html"]);
+                    self.insert_synthetic("tbody");
+                    self.mode = Mode::InTableBody;
+                    return Some(token);
+                }
+                "table" => {
+                    self.error(ErrorCode::UnexpectedStartTagImpliesEndTag);
+                    if !self.in_scope(&["table"], Scope::Table) {
+                        return None;
+                    }
+                    self.pop_until(&["table"]);
+                    self.reset_insertion_mode();
+                    return Some(token);
+                }
+                "style" | "script" | "template" => return self.in_head(token),
+                "input" => {
+                    let hidden = self.attributes(&token)
+                        .and_then(|attributes| attributes.get("type").map(|value| value.eq_ignore_ascii_case("hidden")))
+                        .unwrap_or(false);
+                    if !hidden {
+                        return self.foster(token);
+                    }
+                    self.insert_leaf(&token);
+                    return None;
+                }
+                "form" => {
+                    if self.form.is_none() {
+                        self.form = Some(self.insert_leaf(&token));
+                    }
+                    return None;
+                }
+                _ => return self.foster(token),
+            }
+        }
+        if is_end(&token) {
+            match name {
+                "table" => {
+                    if self.in_scope(&["table"], Scope::Table) {
+                        self.pop_until(&["table"]);
+                        self.reset_insertion_mode();
+                    }
+                    return None;
+                }
+                "body" | "caption" | "col" | "colgroup" | "html" | "tbody" | "td" | "tfoot" | "th" | "thead" | "tr" => {
+                    self.unexpected(&token);
+                    return None;
+                }
+                "template" => return self.in_head(token),
+                _ => return self.foster(token),
+            }
+        }
+        return self.foster(token);
+    }
+
+    /* Processes the token with the in body rules, redirecting insertions in front of the table */
+    fn foster(&mut self, token: Token) -> Option<Token> {
+        self.unexpected(&token);
+        self.foster_parenting = true;
+        let reprocess = self.in_body(token);
+        self.foster_parenting = false;
+        return reprocess;
+    }
+
+    fn close_caption(&mut self) -> bool {
+        if !self.in_scope(&["caption"], Scope::Table) {
+            return false;
+        }
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["caption"]);
+        self.clear_formatting_to_marker();
+        self.mode = Mode::InTable;
+        return true;
+    }
+
+    fn in_caption(&mut self, token: Token) -> Option<Token> {
+        if end_tag(&token, &["caption"]) {
+            self.close_caption();
+            return None;
+        }
+        if start_tag(&token, TABLE_STRUCTURE) || end_tag(&token, &["table"]) {
+            return if self.close_caption() { Some(token) } else { None };
+        }
+        if end_tag(&token, &["body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr"]) {
+            return None;
+        }
+        return self.in_body(token);
+    }
+
+    fn in_column_group(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) {
+            let (whitespace, rest) = split_whitespace(&token);
+            self.insert_text(&whitespace);
+            let rest = rest?;
+            if !self.current_is(&["colgroup"]) {
+                return None;
+            }
+            self.pop();
+            self.mode = Mode::InTable;
+            return Some(rest);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if end_tag(&token, &["colgroup"]) {
+            if self.current_is(&["colgroup"]) {
+                self.pop();
+                self.mode = Mode::InTable;
+            }
+            return None;
+        }
+        if start_tag(&token, &["col"]) {
+            self.insert_leaf(&token);
+            return None;
+        }
+        if end_tag(&token, &["col"]) {
+            return None;
+        }
+        if start_tag(&token, &["template"]) || end_tag(&token, &["template"]) {
+            return self.in_head(token);
+        }
+        if !self.current_is(&["colgroup"]) {
+            return None;
+        }
+        self.pop();
+        self.mode = Mode::InTable;
+        return Some(token);
+    }
+
+    fn in_table_body(&mut self, token: Token) -> Option<Token> {
+        if start_tag(&token, &["tr"]) {
+            self.clear_stack_to(&["tbody", "tfoot", "thead", "template", "html"]);
+            self.insert_element(&token);
+            self.mode = Mode::InRow;
+            return None;
+        }
+        if start_tag(&token, &["th", "td"]) {
+            self.clear_stack_to(&["tbody", "tfoot", "thead", "template", "html"]);
+            self.insert_synthetic("tr");
+            self.mode = Mode::InRow;
+            return Some(token);
+        }
+        if end_tag(&token, TABLE_SECTIONS) {
+            if self.in_scope(&[token.tag.name()], Scope::Table) {
+                self.clear_stack_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.pop();
+                self.mode = Mode::InTable;
+            }
+            return None;
+        }
+        if start_tag(&token, &["caption", "col", "colgroup", "tbody", "tfoot", "thead"]) || end_tag(&token, &["table"]) {
+            if !self.in_scope(TABLE_SECTIONS, Scope::Table) {
+                return None;
+            }
+            self.clear_stack_to(&["tbody", "tfoot", "thead", "template", "html"]);
+            self.pop();
+            self.mode = Mode::InTable;
+            return Some(token);
+        }
+        if end_tag(&token, &["body", "caption", "col", "colgroup", "html", "td", "th", "tr"]) {
+            return None;
+        }
+        return self.in_table(token);
+    }
+
+    fn close_row(&mut self) -> bool {
+        if !self.in_scope(&["tr"], Scope::Table) {
+            return false;
+        }
+        self.clear_stack_to(&["tr", "template", "html"]);
+        self.pop();
+        self.mode = Mode::InTableBody;
+        return true;
+    }
+
+    fn in_row(&mut self, token: Token) -> Option<Token> {
+        if start_tag(&token, &["th", "td"]) {
+            self.clear_stack_to(&["tr", "template", "html"]);
+            self.insert_element(&token);
+            self.mode = Mode::InCell;
+            self.formatting.push(Formatting::Marker);
+            return None;
+        }
+        if end_tag(&token, &["tr"]) {
+            self.close_row();
+            return None;
+        }
+        if start_tag(&token, &["caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr"]) || end_tag(&token, &["table"]) {
+            return if self.close_row() { Some(token) } else { None };
+        }
+        if end_tag(&token, TABLE_SECTIONS) {
+            if !self.in_scope(&[token.tag.name()], Scope::Table) || !self.close_row() {
+                return None;
+            }
+            return Some(token);
+        }
+        if end_tag(&token, &["body", "caption", "col", "colgroup", "html", "td", "th"]) {
+            return None;
+        }
+        return self.in_table(token);
+    }
+
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        self.pop_until(&["td", "th"]);
+        self.clear_formatting_to_marker();
+        self.mode = Mode::InRow;
+    }
+
+    fn in_cell(&mut self, token: Token) -> Option<Token> {
+        if end_tag(&token, &["td", "th"]) {
+            if self.in_scope(&[token.tag.name()], Scope::Table) {
+                self.close_cell();
+            }
+            return None;
+        }
+        if start_tag(&token, TABLE_STRUCTURE) {
+            if !self.in_scope(&["td", "th"], Scope::Table) {
+                return None;
+            }
+            self.close_cell();
+            return Some(token);
+        }
+        if end_tag(&token, &["body", "caption", "col", "colgroup", "html"]) {
+            return None;
+        }
+        if end_tag(&token, &["table", "tbody", "tfoot", "thead", "tr"]) {
+            if !self.in_scope(&[token.tag.name()], Scope::Table) {
+                return None;
+            }
+            self.close_cell();
+            return Some(token);
+        }
+        return self.in_body(token);
+    }
+
+    fn close_select(&mut self) -> bool {
+        if !self.in_scope(&["select"], Scope::Select) {
+            return false;
+        }
+        self.pop_until(&["select"]);
+        self.reset_insertion_mode();
+        return true;
+    }
+
+    fn in_select(&mut self, token: Token) -> Option<Token> {
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if is_text(&token) {
+            self.insert_text(&token.data.replace('\0', ""));
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if start_tag(&token, &["option"]) {
+            if self.current_is(&["option"]) {
+                self.pop();
+            }
+            self.insert_element(&token);
+            return None;
+        }
+        if start_tag(&token, &["optgroup"]) {
+            if self.current_is(&["option"]) {
+                self.pop();
+            }
+            if self.current_is(&["optgroup"]) {
+                self.pop();
+            }
+            self.insert_element(&token);
+            return None;
+        }
+        if end_tag(&token, &["optgroup"]) {
+            let len = self.open.len();
+            if self.current_is(&["option"]) && len > 1 && self.name(self.open[len - 2]) == "optgroup" {
+                self.pop();
+            }
+            if self.current_is(&["optgroup"]) {
+                self.pop();
+            }
+            return None;
+        }
+        if end_tag(&token, &["option"]) {
+            if self.current_is(&["option"]) {
+                self.pop();
+            }
+            return None;
+        }
+        if start_tag(&token, &["hr"]) {
+            if self.current_is(&["option"]) {
+                self.pop();
+            }
+            if self.current_is(&["optgroup"]) {
+                self.pop();
+            }
+            self.insert_leaf(&token);
+            return None;
+        }
+        if end_tag(&token, &["select"]) || start_tag(&token, &["select"]) {
+            self.close_select();
+            return None;
+        }
+        if start_tag(&token, &["input", "keygen", "textarea"]) {
+            return if self.close_select() { Some(token) } else { None };
+        }
+        if start_tag(&token, &["script", "template"]) || end_tag(&token, &["template"]) {
+            return self.in_head(token);
+        }
+        return None;
+    }
+
+    /* Table content is allowed directly in a template, the first start tag decides how its content is parsed */
+    fn in_template(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) || matches!(token.tag_type, TokenType::Comment | TokenType::PHP) || token.tag == Tag::Doctype || token.tag == Tag::XML {
+            return self.in_body(token);
+        }
+        if start_tag(&token, HEAD_CONTENT) || end_tag(&token, &["template"]) {
+            return self.in_head(token);
+        }
+        if is_end(&token) {
+            self.error(ErrorCode::UnexpectedEndTag);
+            return None;
+        }
+        let mode = match token.tag.name() {
+            "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => Mode::InTable,
+            "col" => Mode::InColumnGroup,
+            "tr" => Mode::InTableBody,
+            "td" | "th" => Mode::InRow,
+            _ => Mode::InBody,
+        };
+        self.template_modes.pop();
+        self.template_modes.push(mode);
+        self.mode = mode;
+        return Some(token);
+    }
+
+    fn in_select_in_table(&mut self, token: Token) -> Option<Token> {
+        let table = &["caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th"];
+        if start_tag(&token, table) {
+            self.pop_until(&["select"]);
+            self.reset_insertion_mode();
+            return Some(token);
+        }
+        if end_tag(&token, table) {
+            if !self.in_scope(&[token.tag.name()], Scope::Table) {
+                return None;
+            }
+            self.pop_until(&["select"]);
+            self.reset_insertion_mode();
+            return Some(token);
+        }
+        return self.in_select(token);
+    }
+
+    fn in_frameset(&mut self, token: Token) -> Option<Token> {
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if is_text(&token) {
+            let whitespace = token.data.chars().filter(|&ch| is_whitespace(ch)).collect::<String>();
+            self.insert_text(&whitespace);
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if start_tag(&token, &["frameset"]) {
+            self.insert_element(&token);
+            return None;
+        }
+        if end_tag(&token, &["frameset"]) {
+            if self.open.len() > 1 {
+                self.pop();
+                if !self.current_is(&["frameset"]) {
+                    self.mode = Mode::AfterFrameset;
+                }
+            }
+            return None;
+        }
+        if start_tag(&token, &["frame"]) {
+            self.insert_leaf(&token);
+            return None;
+        }
+        if start_tag(&token, &["noframes"]) {
+            return self.in_head(token);
+        }
+        return None;
+    }
+
+    fn after_body(&mut self, token: Token) -> Option<Token> {
+        if is_text(&token) && token.data.chars().all(is_whitespace) {
+            return self.in_body(token);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, Some(self.open[0]));
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if end_tag(&token, &["html"]) {
+            self.mode = Mode::AfterAfterBody;
+            return None;
+        }
+        self.unexpected(&token);
+        self.mode = Mode::InBody;
+        return Some(token);
+    }
+
+    fn after_frameset(&mut self, token: Token) -> Option<Token> {
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, None);
+            return None;
+        }
+        if is_text(&token) {
+            let whitespace = token.data.chars().filter(|&ch| is_whitespace(ch)).collect::<String>();
+            self.insert_text(&whitespace);
+            return None;
+        }
+        if start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if end_tag(&token, &["html"]) {
+            self.mode = Mode::AfterAfterFrameset;
+            return None;
+        }
+        if start_tag(&token, &["noframes"]) {
+            return self.in_head(token);
+        }
+        return None;
+    }
+
+    fn after_after_body(&mut self, token: Token) -> Option<Token> {
+        if (is_text(&token) && token.data.chars().all(is_whitespace)) || start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, Some(0));
+            return None;
+        }
+        if token.tag == Tag::Doctype {
+            return None;
+        }
+        self.unexpected(&token);
+        self.mode = Mode::InBody;
+        return Some(token);
+    }
+
+    fn after_after_frameset(&mut self, token: Token) -> Option<Token> {
+        if token.tag_type == TokenType::Comment {
+            self.insert_comment(&token, Some(0));
+            return None;
+        }
+        if (is_text(&token) && token.data.chars().all(is_whitespace)) || start_tag(&token, &["html"]) {
+            return self.in_body(token);
+        }
+        if start_tag(&token, &["noframes"]) {
+            return self.in_head(token);
+        }
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* The tree in the format of the html5lib tree construction tests, attributes in source order */
+    fn dump(document: &Document) -> String {
+        let mut output = String::new();
+        dump_children(document, document.root(), 0, &mut output);
+        return output;
+    }
+
+    fn dump_children(document: &Document, id: NodeId, depth: usize, output: &mut String) {
+        let indent = "  ".repeat(depth);
+        for child in document.children(id) {
+            match document.node(child) {
+                Node::Element(element) => {
+                    output.push_str(&format!("| {}<{}>\n", indent, element.tag().name()));
+                    for attribute in element.attributes().into_iter().flatten() {
+                        output.push_str(&format!("| {}  {}=\"{}\"\n", indent, attribute.name, attribute.value));
+                    }
+                }
+                Node::Text(text) => output.push_str(&format!("| {}\"{}\"\n", indent, text)),
+                Node::Comment(text) => output.push_str(&format!("| {}<!-- {} -->\n", indent, text)),
+                Node::Doctype(doctype) => output.push_str(&format!("| {}<!DOCTYPE {}>\n", indent, doctype.name.as_deref().unwrap_or(""))),
+                Node::CData(text) => output.push_str(&format!("| {}<![CDATA[{}]]>\n", indent, text)),
+                Node::ProcessingInstruction(instruction) => {
+                    output.push_str(&format!("| {}<?{} {}>\n", indent, instruction.target, instruction.data))
+                }
+                other => output.push_str(&format!("| {}{:?}\n", indent, other)),
+            }
+            dump_children(document, child, depth + 1, output);
+        }
+    }
+
+    fn parse(html: &str) -> String {
+        dump(&DOM::parse_str(html).unwrap())
+    }
+
+    fn tree(lines: &[&str]) -> String {
+        lines.iter().map(|line| format!("| {}\n", line)).collect()
+    }
+
+    fn errors(html: &str) -> Vec<&'static str> {
+        DOM::parse_str(html).unwrap().errors().iter().map(|error| error.code.code()).collect()
+    }
+
+    #[test]
+    fn columns_in_a_table() {
+        assert_eq!(
+            parse("<!DOCTYPE html><table><col><col span=2><tr><td>x"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <table>", "      <colgroup>", "        <col>",
+                "        <col>", "          span=\"2\"", "      <tbody>", "        <tr>", "          <td>", "            \"x\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn implied_end_tags() {
+        assert_eq!(
+            parse("<!DOCTYPE html><p>a<p>b<ul><li>c<li>d</ul><dl><dt>e<dd>f</dl>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <p>", "      \"a\"", "    <p>", "      \"b\"", "    <ul>",
+                "      <li>", "        \"c\"", "      <li>", "        \"d\"", "    <dl>", "      <dt>", "        \"e\"", "      <dd>",
+                "        \"f\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn table_cells_and_rows_are_closed() {
+        assert_eq!(
+            parse("<!DOCTYPE html><table><caption>t<tr><td>a<td>b<tr><th>c</table>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <table>", "      <caption>", "        \"t\"", "      <tbody>",
+                "        <tr>", "          <td>", "            \"a\"", "          <td>", "            \"b\"", "        <tr>",
+                "          <th>", "            \"c\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn content_in_tables_is_foster_parented() {
+        assert_eq!(
+            parse("<!DOCTYPE html><table>a<tr><td>b</td></tr><div>c</div></table>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    \"a\"", "    <div>", "      \"c\"", "    <table>",
+                "      <tbody>", "        <tr>", "          <td>", "            \"b\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn formatting_elements_are_adopted() {
+        assert_eq!(
+            parse("<!DOCTYPE html><b>1<p>2</b>3</p>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <b>", "      \"1\"", "    <p>", "      <b>", "        \"2\"",
+                "      \"3\"",
+            ])
+        );
+        assert_eq!(
+            parse("<!DOCTYPE html><a href=x><div>y</a>z"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <a>", "      href=\"x\"", "    <div>", "      <a>",
+                "        href=\"x\"", "        \"y\"", "      \"z\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn formatting_elements_are_reconstructed() {
+        assert_eq!(
+            parse("<!DOCTYPE html><p><i>a<p>b"),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <p>", "      <i>", "        \"a\"", "    <p>", "      <i>", "        \"b\""])
+        );
+    }
+
+    #[test]
+    fn options_in_a_select() {
+        assert_eq!(
+            parse("<!DOCTYPE html><select><option>a<option>b<p>c</select>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <select>", "      <option>", "        \"a\"", "      <option>",
+                "        \"bc\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn noscript_in_head_keeps_metadata() {
+        assert_eq!(
+            parse("<!DOCTYPE html><head><noscript><link></noscript><title>x</title>"),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "    <noscript>", "      <link>", "    <title>", "      \"x\"", "  <body>"])
+        );
+    }
+
+    #[test]
+    fn noscript_in_head_is_closed_by_body_content() {
+        let html = "<!DOCTYPE html><head><noscript><p>x";
+        assert_eq!(
+            parse(html),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "    <noscript>", "  <body>", "    <p>", "      \"x\""])
+        );
+        assert_eq!(errors(html), ["unexpected-start-tag"]);
+    }
+
+    #[test]
+    fn template_in_head_keeps_its_content() {
+        assert_eq!(
+            parse("<!DOCTYPE html><head><template><p>x</template></head><body>y"),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "    <template>", "      <p>", "        \"x\"", "  <body>", "    \"y\""])
+        );
+    }
+
+    #[test]
+    fn template_left_open_in_head() {
+        let html = "<!DOCTYPE html><head><template><p>x";
+        assert_eq!(
+            parse(html),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "    <template>", "      <p>", "        \"x\"", "  <body>"])
+        );
+        assert_eq!(errors(html), ["eof-in-element"]);
+    }
+
+    #[test]
+    fn template_keeps_table_rows() {
+        assert_eq!(
+            parse("<!DOCTYPE html><template><tr><td>a</td><td>b</td></tr><tr><td>c</template>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "    <template>", "      <tr>", "        <td>", "          \"a\"",
+                "        <td>", "          \"b\"", "      <tr>", "        <td>", "          \"c\"", "  <body>",
+            ])
+        );
+    }
+
+    #[test]
+    fn template_content_mode_is_chosen_by_the_first_tag() {
+        assert_eq!(
+            parse("<!DOCTYPE html><body><template><col><col></template><template><tbody><tr></template>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <template>", "      <col>", "      <col>",
+                "    <template>", "      <tbody>", "        <tr>",
+            ])
+        );
+    }
+
+    #[test]
+    fn nested_templates() {
+        assert_eq!(
+            parse("<!DOCTYPE html><template><template><td>x</template><b>y</template>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "    <template>", "      <template>", "        <td>", "          \"x\"",
+                "      <b>", "        \"y\"", "  <body>",
+            ])
+        );
+    }
+
+    #[test]
+    fn stray_template_end_tag() {
+        assert_eq!(errors("<!DOCTYPE html></template>"), ["unexpected-end-tag"]);
+    }
+
+    #[test]
+    fn fragment_in_template_context() {
+        let document = DOM::parse_fragment("<tr><td>x</td></tr>", "template").unwrap();
+        assert_eq!(dump(&document), tree(&["<tr>", "  <td>", "    \"x\""]));
+    }
+
+    #[test]
+    fn self_closing_html_elements_are_left_open() {
+        let html = "<!DOCTYPE html><span/>x<div/>y</div></span>";
+        assert_eq!(
+            parse(html),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <span>", "      \"x\"", "      <div>", "        \"y\""])
+        );
+        assert_eq!(errors(html), ["non-void-html-element-start-tag-with-trailing-solidus"; 2]);
+    }
+
+    #[test]
+    fn self_closing_foreign_and_void_elements_are_closed() {
+        let html = "<!DOCTYPE html><br/><svg><path/><g/></svg><meta/>x";
+        assert_eq!(
+            parse(html),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <br>", "    <svg>", "      <path>", "      <g>", "    <meta>", "    \"x\""])
+        );
+        assert!(errors(html).is_empty());
+    }
+
+    #[test]
+    fn svg_names_get_their_case_back() {
+        assert_eq!(
+            parse("<!DOCTYPE html><svg viewbox='0 0 1 1'><clippath><lineargradient gradientunits='x'/></clippath></svg>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <svg>", "      viewBox=\"0 0 1 1\"", "      <clipPath>",
+                "        <linearGradient>", "          gradientUnits=\"x\"",
+            ])
+        );
+        assert_eq!(
+            parse("<!DOCTYPE html><math definitionurl=a><mi definitionurl=b></math>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <math>", "      definitionURL=\"a\"", "      <mi>",
+                "        definitionURL=\"b\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn html_start_tags_break_out_of_foreign_content() {
+        let html = "<!DOCTYPE html><svg><g><p>a</p><font color=red>b</font>";
+        assert_eq!(
+            parse(html),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <svg>", "      <g>", "    <p>", "      \"a\"", "    <font>",
+                "      color=\"red\"", "      \"b\"",
+            ])
+        );
+        assert_eq!(errors(html), vec!["unexpected-start-tag"]);
+        assert_eq!(
+            parse("<!DOCTYPE html><svg><font>a</font></svg>"),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <svg>", "      <font>", "        \"a\""])
+        );
+    }
+
+    #[test]
+    fn integration_points_contain_html() {
+        assert_eq!(
+            parse("<!DOCTYPE html><svg><foreignobject><div><svg><desc><b>a</b></desc></svg></div></foreignobject><rect/></svg>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <svg>", "      <foreignObject>", "        <div>",
+                "          <svg>", "            <desc>", "              <b>", "                \"a\"", "      <rect>",
+            ])
+        );
+        assert_eq!(
+            parse("<!DOCTYPE html><math><mi><b>a</b><mglyph></mi><annotation-xml encoding=text/html><div>b</div></annotation-xml></math>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <math>", "      <mi>", "        <b>", "          \"a\"",
+                "        <mglyph>", "      <annotation-xml>", "        encoding=\"text/html\"", "        <div>", "          \"b\"",
+            ])
+        );
+        /* Without the encoding the content of annotation-xml stays MathML, apart from the tags that break out */
+        assert_eq!(
+            parse("<!DOCTYPE html><math><annotation-xml><svg><desc>a</desc></svg><p>b"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <math>", "      <annotation-xml>", "        <svg>",
+                "          <desc>", "            \"a\"", "    <p>", "      \"b\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn foreign_elements_named_like_raw_text_elements_contain_markup() {
+        assert_eq!(
+            parse("<!DOCTYPE html><svg><title><b>a</b></title><style>b<c/></style></svg><math><mi><style>c<d></style>"),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <svg>", "      <title>", "        <b>", "          \"a\"",
+                "      <style>", "        \"b\"", "        <c>", "    <math>", "      <mi>", "        <style>", "          \"c<d>\"",
+            ])
+        );
+    }
+
+    #[test]
+    fn foreign_end_tags_close_foreign_elements() {
+        let html = "<!DOCTYPE html><div><svg><g><a>x</G></svg></div>";
+        assert_eq!(
+            parse(html),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <div>", "      <svg>", "        <g>", "          <a>", "            \"x\""])
+        );
+        assert_eq!(errors(html), vec!["misnested-end-tag"]);
+        /* An end tag for an HTML element is handled by the HTML rules */
+        assert_eq!(
+            parse("<!DOCTYPE html><div><svg><g></div>x"),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <div>", "      <svg>", "        <g>", "    \"x\""])
+        );
+    }
+
+    #[test]
+    fn fragment_in_foreign_context() {
+        let document = DOM::fragment("<clippath/><p>a", "svg", &ParseOptions::default());
+        assert_eq!(dump(&document), tree(&["<clipPath>", "<p>", "  \"a\""]));
+    }
+
+    #[test]
+    fn comments_stay_where_they_are() {
+        let html = "<!-- a --><!DOCTYPE html><!--b--><html><!--c--><head></head><!--d--><body><p>x<!-- build:js -->y</p></body><!--e--></html><!--f-->";
+        assert_eq!(
+            parse(html),
+            tree(&[
+                "<!--  a  -->", "<!DOCTYPE html>", "<!-- b -->", "<html>", "  <!-- c -->", "  <head>", "  <!-- d -->", "  <body>",
+                "    <p>", "      \"x\"", "      <!--  build:js  -->", "      \"y\"", "  <!-- e -->", "<!-- f -->",
+            ])
+        );
+        assert!(errors(html).is_empty());
+    }
+
+    #[test]
+    fn cdata_sections_in_foreign_and_html_content() {
+        let html = "<!DOCTYPE html><svg><![CDATA[a<b]]></svg><math><mi><![CDATA[c]]></mi></math><p><![CDATA[d]]>";
+        assert_eq!(
+            parse(html),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <svg>", "      <![CDATA[a<b]]>", "    <math>", "      <mi>",
+                "        <![CDATA[c]]>", "    <p>", "      <![CDATA[d]]>",
+            ])
+        );
+        /* Only the section in HTML content is reported, the one in <mi> is still inside MathML */
+        assert_eq!(errors(html), vec!["cdata-in-html-content"]);
+    }
+
+    #[test]
+    fn processing_instructions() {
+        assert_eq!(
+            parse("<!DOCTYPE html><?xml-stylesheet href=a.css?><p>a<?php echo 1; ?>b"),
+            tree(&["<!DOCTYPE html>", "<?xml-stylesheet href=a.css>", "<html>", "  <head>", "  <body>", "    <p>", "      \"a\"", "      PHP(\"php echo 1; \")", "      \"b\""])
+        );
+    }
+
+    #[test]
+    fn misnested_formatting_end_tags_are_reported() {
+        let html = "<!DOCTYPE html><p><b><i>x</b>y</i></p>";
+        assert_eq!(
+            parse(html),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <p>", "      <b>", "        <i>", "          \"x\"",
+                "      <i>", "        \"y\"",
+            ])
+        );
+        assert_eq!(errors(html), ["misnested-end-tag"]);
+        assert_eq!(errors("<!DOCTYPE html><p>x</b></p>"), ["unexpected-end-tag"]);
+    }
+
+    #[test]
+    fn nested_links_are_reported() {
+        let html = "<!DOCTYPE html><a href=1>x<a href=2>y</a>";
+        assert_eq!(
+            parse(html),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <a>", "      href=\"1\"", "      \"x\"", "    <a>",
+                "      href=\"2\"", "      \"y\"",
+            ])
+        );
+        assert_eq!(errors(html), ["unexpected-start-tag-implies-end-tag"]);
+        assert_eq!(errors("<!DOCTYPE html><nobr>a<nobr>b</nobr>"), ["unexpected-start-tag-implies-end-tag"]);
+    }
+
+    #[test]
+    fn text_after_body_is_reported() {
+        let html = "<!DOCTYPE html><p>x</p></body>y";
+        assert_eq!(parse(html), tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <p>", "      \"x\"", "    \"y\""]));
+        assert_eq!(errors(html), ["unexpected-text"]);
+        assert_eq!(errors("<!DOCTYPE html></html><p>"), ["unexpected-start-tag"]);
+        assert!(errors("<!DOCTYPE html></body> \n<!--x--></html> ").is_empty());
+    }
+
+    #[test]
+    fn content_in_tables_is_reported() {
+        assert_eq!(
+            errors("<!DOCTYPE html><table>a<tr><td>b</td></tr><div>c</div></table>"),
+            ["unexpected-text", "unexpected-start-tag", "unexpected-text", "unexpected-end-tag"]
+        );
+        assert_eq!(errors("<!DOCTYPE html><table> <tr> </tr> </table>"), Vec::<&str>::new());
+        assert_eq!(errors("<!DOCTYPE html><table></tr><table></table>"), ["unexpected-end-tag", "unexpected-start-tag-implies-end-tag"]);
+    }
+
+    #[test]
+    fn end_tags_closing_other_elements_are_reported() {
+        assert_eq!(errors("<!DOCTYPE html><div><span>x</div>"), ["misnested-end-tag"]);
+        assert_eq!(errors("<!DOCTYPE html><h1>x</h2>"), ["misnested-end-tag"]);
+        assert_eq!(errors("<!DOCTYPE html><div>x</body>"), ["misnested-end-tag"]);
+        assert!(errors("<!DOCTYPE html><ul><li>a<li>b</ul><p>c</body>").is_empty());
+    }
+
+    #[test]
+    fn strict_mode_rejects_recovered_errors() {
+        let mut options = ParseOptions::default();
+        options.strict = true;
+        for html in ["<a>x<a>y</a>", "<b><i>x</b></i>", "<p>x</p></body>y"] {
+            let html = format!("<!DOCTYPE html>{}", html);
+            assert!(DOM::parse_str_with_options(&html, &options).is_err(), "{}", html);
+        }
+        assert!(DOM::parse_str_with_options("<!DOCTYPE html><p>x</p>", &options).is_ok());
+    }
+
+    fn parse_trimmed(html: &str, keep_whitespace_only_text: bool) -> String {
+        let mut options = ParseOptions::default();
+        options.whitespace = Whitespace::Trim;
+        options.keep_whitespace_only_text = keep_whitespace_only_text;
+        let document = DOM::parse_str_with_options(html, &options).unwrap();
+        let body = document.descendants(document.root()).find(|&id| document.node(id).as_element().is_some_and(|element| element.tag().name() == "body"));
+        return document.to_node(body.unwrap()).text();
+    }
+
+    #[test]
+    fn trim_keeps_spaces_between_inline_elements() {
+        assert_eq!(parse_trimmed("<p>Hello <b>big</b> world</p>", true), "Hello big world");
+        assert_eq!(parse_trimmed("<p>a <b> b </b> c</p>", true), "a b c");
+        assert_eq!(parse_trimmed("<p><span>x</span> <span>y</span></p>", true), "x y");
+        assert_eq!(parse_trimmed("<p><span>x</span> <span>y</span></p>", false), "xy");
+    }
+
+    #[test]
+    fn trim_removes_whitespace_at_block_boundaries() {
+        assert_eq!(parse_trimmed("<div>\n  <p> a </p>\n  <p>\tb\n</p>\n</div>", true), "ab");
+        assert_eq!(parse_trimmed("<ul>\n<li> one\n<li>two <i>three </i></ul>", true), "onetwo three");
+        assert_eq!(parse_trimmed("<p>a <br> b <img> c </p>", true), "ab  c");
+    }
+
+    #[test]
+    fn trim_leaves_preserved_elements_alone() {
+        assert_eq!(parse_trimmed("<p> a </p><pre>\n  b  </pre><textarea> c </textarea>", true), "a  b   c ");
+    }
+
+    fn parse_collapsed(html: &str, keep_whitespace_only_text: bool) -> String {
+        let mut options = ParseOptions::default();
+        options.whitespace = Whitespace::Collapse;
+        options.keep_whitespace_only_text = keep_whitespace_only_text;
+        return dump(&DOM::parse_str_with_options(html, &options).unwrap());
+    }
+
+    #[test]
+    fn collapse_turns_runs_of_whitespace_into_one_space() {
+        assert_eq!(
+            parse_collapsed("<!DOCTYPE html><p>\n  a \t b\n</p> <p>c<b>  d  </b></p>", true),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <p>", "      \" a b \"", "    \" \"", "    <p>", "      \"c\"",
+                "      <b>", "        \" d \"",
+            ])
+        );
+        assert_eq!(
+            parse_collapsed("<!DOCTYPE html><p>\n  a \t b\n</p> <p>c<b>  d  </b></p>", false),
+            tree(&["<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <p>", "      \" a b \"", "    <p>", "      \"c\"", "      <b>", "        \" d \""])
+        );
+    }
+
+    #[test]
+    fn collapse_keeps_whitespace_in_pre_and_textarea() {
+        assert_eq!(
+            parse_collapsed("<!DOCTYPE html><pre>\n\n a  <b> b\t</b>\n</pre><textarea>  c\n\n d </textarea><p>  e  </p>", false),
+            tree(&[
+                "<!DOCTYPE html>", "<html>", "  <head>", "  <body>", "    <pre>", "      \"\n a  \"", "      <b>", "        \" b\t\"",
+                "      \"\n\"", "    <textarea>", "      \"  c\n\n d \"", "    <p>", "      \" e \"",
+            ])
+        );
+    }
+
+    #[test]
+    fn collapsed_fragment_in_a_pre_context() {
+        let mut options = ParseOptions::default();
+        options.whitespace = Whitespace::Collapse;
+        let document = DOM::parse_fragment_with_options("a  <b>  b </b>", "pre", &options).unwrap();
+        assert_eq!(dump(&document), tree(&["\"a  \"", "<b>", "  \"  b \""]));
+        let document = DOM::parse_fragment_with_options("a  <b>  b </b>", "div", &options).unwrap();
+        assert_eq!(dump(&document), tree(&["\"a \"", "<b>", "  \" b \""]));
+    }
+}
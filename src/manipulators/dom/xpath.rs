@@ -0,0 +1,1331 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{is_whitespace, Document, Node, NodeId};
+
+/* A node of the XPath data model, attributes are not nodes of the document so they are addressed by their element */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum XPathNode {
+    Node(NodeId),
+    Attribute(NodeId, String),
+}
+
+/* The result of an expression */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /* In document order, without duplicates */
+    NodeSet(Vec<XPathNode>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+/* A parsed expression, see https://www.w3.org/TR/1999/REC-xpath-19991116/ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPath {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    Arithmetic(Arithmetic, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Path(PathStart, Vec<Step>),
+    /* A primary expression followed by predicates */
+    Filter(Box<Expr>, Vec<Expr>),
+    Literal(String),
+    Number(f64),
+    /* The offset is kept for the errors that can only be found once the value is known */
+    Variable(String, usize),
+    Function(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathStart {
+    Root,
+    Context,
+    Expr(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Expr>,
+}
+
+/* SelfAxis because self is a keyword */
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    /* Always empty, the tree has no namespace nodes */
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    SelfAxis,
+}
+const AXES: &[(&str, Axis)] = &[
+    ("ancestor", Axis::Ancestor),
+    ("ancestor-or-self", Axis::AncestorOrSelf),
+    ("attribute", Axis::Attribute),
+    ("child", Axis::Child),
+    ("descendant", Axis::Descendant),
+    ("descendant-or-self", Axis::DescendantOrSelf),
+    ("following", Axis::Following),
+    ("following-sibling", Axis::FollowingSibling),
+    ("namespace", Axis::Namespace),
+    ("parent", Axis::Parent),
+    ("preceding", Axis::Preceding),
+    ("preceding-sibling", Axis::PrecedingSibling),
+    ("self", Axis::SelfAxis),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum NodeTest {
+    /* * */
+    Any,
+    /* Names are matched ASCII case-insensitively like HTML names */
+    Name(String),
+    /* prefix:* */
+    Prefix(String),
+    Node,
+    Text,
+    Comment,
+    ProcessingInstruction(Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+/* The core function library */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Last,
+    Position,
+    Count,
+    Id,
+    LocalName,
+    NamespaceUri,
+    Name,
+    String,
+    Concat,
+    StartsWith,
+    Contains,
+    SubstringBefore,
+    SubstringAfter,
+    Substring,
+    StringLength,
+    NormalizeSpace,
+    Translate,
+    Boolean,
+    Not,
+    True,
+    False,
+    Lang,
+    Number,
+    Sum,
+    Floor,
+    Ceiling,
+    Round,
+}
+/* Name, function, minimum and maximum number of arguments */
+const FUNCTIONS: &[(&str, Function, usize, usize)] = &[
+    ("last", Function::Last, 0, 0),
+    ("position", Function::Position, 0, 0),
+    ("count", Function::Count, 1, 1),
+    ("id", Function::Id, 1, 1),
+    ("local-name", Function::LocalName, 0, 1),
+    ("namespace-uri", Function::NamespaceUri, 0, 1),
+    ("name", Function::Name, 0, 1),
+    ("string", Function::String, 0, 1),
+    ("concat", Function::Concat, 2, usize::MAX),
+    ("starts-with", Function::StartsWith, 2, 2),
+    ("contains", Function::Contains, 2, 2),
+    ("substring-before", Function::SubstringBefore, 2, 2),
+    ("substring-after", Function::SubstringAfter, 2, 2),
+    ("substring", Function::Substring, 2, 3),
+    ("string-length", Function::StringLength, 0, 1),
+    ("normalize-space", Function::NormalizeSpace, 0, 1),
+    ("translate", Function::Translate, 3, 3),
+    ("boolean", Function::Boolean, 1, 1),
+    ("not", Function::Not, 1, 1),
+    ("true", Function::True, 0, 0),
+    ("false", Function::False, 0, 0),
+    ("lang", Function::Lang, 1, 1),
+    ("number", Function::Number, 0, 1),
+    ("sum", Function::Sum, 1, 1),
+    ("floor", Function::Floor, 1, 1),
+    ("ceiling", Function::Ceiling, 1, 1),
+    ("round", Function::Round, 1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XPathErrorKind {
+    UnexpectedEnd,
+    UnexpectedCharacter(char),
+    UnexpectedToken(String),
+    UnknownAxis(String),
+    UnknownFunction(String),
+    WrongArgumentCount(String),
+    NotANodeSet,
+    UnknownVariable(String),
+}
+
+/* The offset is in bytes into the expression */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XPathError {
+    pub kind: XPathErrorKind,
+    pub offset: usize,
+}
+impl fmt::Display for XPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            XPathErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression")?,
+            XPathErrorKind::UnexpectedCharacter(ch) => write!(f, "unexpected character {:?}", ch)?,
+            XPathErrorKind::UnexpectedToken(token) => write!(f, "unexpected {:?}", token)?,
+            XPathErrorKind::UnknownAxis(name) => write!(f, "unknown axis {}", name)?,
+            XPathErrorKind::UnknownFunction(name) => write!(f, "unknown function {}()", name)?,
+            XPathErrorKind::WrongArgumentCount(name) => write!(f, "wrong number of arguments for {}()", name)?,
+            XPathErrorKind::NotANodeSet => write!(f, "expected a node-set")?,
+            XPathErrorKind::UnknownVariable(name) => write!(f, "unknown variable ${}", name)?,
+        }
+        write!(f, " at offset {}", self.offset)
+    }
+}
+impl std::error::Error for XPathError {}
+
+fn is_name_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_' || !ch.is_ascii()
+}
+
+fn is_name(ch: char) -> bool {
+    is_name_start(ch) || ch.is_ascii_digit() || ch == '-' || ch == '.'
+}
+
+/* ---------- Tokenizer ---------- */
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DotDot,
+    At,
+    Comma,
+    ColonColon,
+    Slash,
+    DoubleSlash,
+    Pipe,
+    Plus,
+    Minus,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    /* A name test or the multiply operator, the parser knows which */
+    Star,
+    /* NCName, QName or prefix:*, operator names like "and" are told apart by the parser as well */
+    Name(String),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+}
+
+/* Tokens with their start and end offsets */
+fn tokenize(input: &str) -> Result<Vec<(Token, usize, usize)>, XPathError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let token = match ch {
+            ch if is_whitespace(ch) => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            '@' => Token::At,
+            ',' => Token::Comma,
+            '|' => Token::Pipe,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '=' => Token::Equal,
+            '*' => Token::Star,
+            ':' if next == Some(':') => {
+                chars.next();
+                Token::ColonColon
+            }
+            '!' if next == Some('=') => {
+                chars.next();
+                Token::NotEqual
+            }
+            '<' | '>' | '/' if next == Some(if ch == '/' { '/' } else { '=' }) => {
+                chars.next();
+                match ch {
+                    '<' => Token::LessOrEqual,
+                    '>' => Token::GreaterOrEqual,
+                    _ => Token::DoubleSlash,
+                }
+            }
+            '<' => Token::Less,
+            '>' => Token::Greater,
+            '/' => Token::Slash,
+            '.' if next == Some('.') => {
+                chars.next();
+                Token::DotDot
+            }
+            '.' | '0'..='9' if ch != '.' || next.is_some_and(|next| next.is_ascii_digit()) => {
+                let mut end = start + 1;
+                let mut dot = ch == '.';
+                while let Some(&(offset, next)) = chars.peek() {
+                    if !(next.is_ascii_digit() || (next == '.' && !dot)) {
+                        break;
+                    }
+                    dot |= next == '.';
+                    end = offset + 1;
+                    chars.next();
+                }
+                Token::Number(input[start..end].parse().unwrap_or(f64::NAN))
+            }
+            '.' => Token::Dot,
+            '"' | '\'' => match input[start + 1..].find(ch) {
+                Some(length) => {
+                    while chars.next_if(|&(offset, _)| offset <= start + length).is_some() {}
+                    chars.next();
+                    Token::Literal(input[start + 1..start + 1 + length].to_string())
+                }
+                None => return Err(XPathError { kind: XPathErrorKind::UnexpectedEnd, offset: input.len() }),
+            },
+            '$' => {
+                let end = name_end(input, start + 1);
+                if end == start + 1 {
+                    return Err(XPathError { kind: XPathErrorKind::UnexpectedCharacter('$'), offset: start });
+                }
+                while chars.next_if(|&(offset, _)| offset < end).is_some() {}
+                Token::Variable(input[start + 1..end].to_string())
+            }
+            ch if is_name_start(ch) => {
+                let end = name_end(input, start);
+                while chars.next_if(|&(offset, _)| offset < end).is_some() {}
+                Token::Name(input[start..end].to_string())
+            }
+            ch => return Err(XPathError { kind: XPathErrorKind::UnexpectedCharacter(ch), offset: start }),
+        };
+        let end = chars.peek().map_or(input.len(), |&(offset, _)| offset);
+        tokens.push((token, start, end));
+    }
+    return Ok(tokens);
+}
+
+/* The end of the NCName, QName or prefix:* that starts at the offset */
+fn name_end(input: &str, start: usize) -> usize {
+    let ncname_end = |start: usize| match input[start..].chars().next() {
+        Some(ch) if is_name_start(ch) => input[start..].find(|ch| !is_name(ch)).map_or(input.len(), |length| start + length),
+        _ => start,
+    };
+    let end = ncname_end(start);
+    if end == start || !input[end..].starts_with(':') || input[end..].starts_with("::") {
+        return end;
+    }
+    if input[end + 1..].starts_with('*') {
+        return end + 2;
+    }
+    let local_end = ncname_end(end + 1);
+    return if local_end > end + 1 { local_end } else { end };
+}
+
+/* ---------- Parsing ---------- */
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, usize, usize)>,
+    index: usize,
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.index + n).map(|(token, _, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let (token, _, _) = self.tokens.get(self.index)?;
+        self.index += 1;
+        return Some(token.clone());
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        if self.peek() == Some(expected) {
+            self.index += 1;
+            return true;
+        }
+        return false;
+    }
+
+    /* Operator names are only operators where an operator is expected */
+    fn eat_name(&mut self, name: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Name(found)) if found == name) {
+            self.index += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.index).map_or(self.input.len(), |&(_, start, _)| start)
+    }
+
+    fn error(&self, kind: XPathErrorKind) -> XPathError {
+        XPathError { kind, offset: self.offset() }
+    }
+
+    fn unexpected(&self) -> XPathError {
+        match self.tokens.get(self.index) {
+            Some(&(_, start, end)) => self.error(XPathErrorKind::UnexpectedToken(self.input[start..end].to_string())),
+            None => self.error(XPathErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), XPathError> {
+        if !self.eat(expected) {
+            return Err(self.unexpected());
+        }
+        return Ok(());
+    }
+
+    fn expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.and_expr()?;
+        while self.eat_name("or") {
+            left = Expr::Or(Box::new(left), Box::new(self.and_expr()?));
+        }
+        return Ok(left);
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.equality_expr()?;
+        while self.eat_name("and") {
+            left = Expr::And(Box::new(left), Box::new(self.equality_expr()?));
+        }
+        return Ok(left);
+    }
+
+    fn equality_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.relational_expr()?;
+        loop {
+            let comparison = match self.peek() {
+                Some(Token::Equal) => Comparison::Equal,
+                Some(Token::NotEqual) => Comparison::NotEqual,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = Expr::Compare(comparison, Box::new(left), Box::new(self.relational_expr()?));
+        }
+    }
+
+    fn relational_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.additive_expr()?;
+        loop {
+            let comparison = match self.peek() {
+                Some(Token::Less) => Comparison::Less,
+                Some(Token::LessOrEqual) => Comparison::LessOrEqual,
+                Some(Token::Greater) => Comparison::Greater,
+                Some(Token::GreaterOrEqual) => Comparison::GreaterOrEqual,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = Expr::Compare(comparison, Box::new(left), Box::new(self.additive_expr()?));
+        }
+    }
+
+    fn additive_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.multiplicative_expr()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Plus) => Arithmetic::Add,
+                Some(Token::Minus) => Arithmetic::Subtract,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = Expr::Arithmetic(operator, Box::new(left), Box::new(self.multiplicative_expr()?));
+        }
+    }
+
+    fn multiplicative_expr(&mut self) -> Result<Expr, XPathError> {
+        let mut left = self.unary_expr()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Star) => Arithmetic::Multiply,
+                Some(Token::Name(name)) if name == "div" => Arithmetic::Divide,
+                Some(Token::Name(name)) if name == "mod" => Arithmetic::Modulo,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = Expr::Arithmetic(operator, Box::new(left), Box::new(self.unary_expr()?));
+        }
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr, XPathError> {
+        if self.eat(&Token::Minus) {
+            return Ok(Expr::Negate(Box::new(self.unary_expr()?)));
+        }
+        return self.union_expr();
+    }
+
+    fn union_expr(&mut self) -> Result<Expr, XPathError> {
+        let offset = self.offset();
+        let mut left = self.path_expr()?;
+        if self.peek() == Some(&Token::Pipe) && !left.may_be_node_set() {
+            return Err(XPathError { kind: XPathErrorKind::NotANodeSet, offset });
+        }
+        while self.eat(&Token::Pipe) {
+            left = Expr::Union(Box::new(left), Box::new(self.node_set_operand(Parser::path_expr, true)?));
+        }
+        return Ok(left);
+    }
+
+    /* Parses an operand that has to be a node-set when it is required */
+    fn node_set_operand(&mut self, parse: fn(&mut Parser<'a>) -> Result<Expr, XPathError>, required: bool) -> Result<Expr, XPathError> {
+        let offset = self.offset();
+        let expr = parse(self)?;
+        if required && !expr.may_be_node_set() {
+            return Err(XPathError { kind: XPathErrorKind::NotANodeSet, offset });
+        }
+        return Ok(expr);
+    }
+
+    fn path_expr(&mut self) -> Result<Expr, XPathError> {
+        let filter = match (self.peek(), self.peek_at(1)) {
+            (Some(Token::Variable(_) | Token::LeftParen | Token::Literal(_) | Token::Number(_)), _) => true,
+            (Some(Token::Name(name)), Some(Token::LeftParen)) => !is_node_type(name),
+            _ => false,
+        };
+        if !filter {
+            return self.location_path();
+        }
+        let offset = self.offset();
+        let primary = self.primary_expr()?;
+        let predicates = self.predicates()?;
+        let more = matches!(self.peek(), Some(Token::Slash | Token::DoubleSlash));
+        if (!predicates.is_empty() || more) && !primary.may_be_node_set() {
+            return Err(XPathError { kind: XPathErrorKind::NotANodeSet, offset });
+        }
+        let filter = match predicates.is_empty() {
+            true => primary,
+            false => Expr::Filter(Box::new(primary), predicates),
+        };
+        if !more {
+            return Ok(filter);
+        }
+        let mut steps = Vec::new();
+        self.relative_location_path(&mut steps)?;
+        return Ok(Expr::Path(PathStart::Expr(Box::new(filter)), steps));
+    }
+
+    fn location_path(&mut self) -> Result<Expr, XPathError> {
+        let mut steps = Vec::new();
+        if self.eat(&Token::Slash) {
+            if matches!(self.peek(), Some(Token::Dot | Token::DotDot | Token::At | Token::Star | Token::Name(_))) {
+                self.steps(&mut steps)?;
+            }
+            return Ok(Expr::Path(PathStart::Root, steps));
+        }
+        if matches!(self.peek(), Some(Token::DoubleSlash)) {
+            self.relative_location_path(&mut steps)?;
+            return Ok(Expr::Path(PathStart::Root, steps));
+        }
+        self.steps(&mut steps)?;
+        return Ok(Expr::Path(PathStart::Context, steps));
+    }
+
+    /* "/" or "//" followed by steps */
+    fn relative_location_path(&mut self, steps: &mut Vec<Step>) -> Result<(), XPathError> {
+        if self.eat(&Token::DoubleSlash) {
+            steps.push(Step { axis: Axis::DescendantOrSelf, test: NodeTest::Node, predicates: Vec::new() });
+        } else {
+            self.expect(&Token::Slash)?;
+        }
+        return self.steps(steps);
+    }
+
+    fn steps(&mut self, steps: &mut Vec<Step>) -> Result<(), XPathError> {
+        steps.push(self.step()?);
+        while matches!(self.peek(), Some(Token::Slash | Token::DoubleSlash)) {
+            self.relative_location_path(steps)?;
+        }
+        return Ok(());
+    }
+
+    fn step(&mut self) -> Result<Step, XPathError> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step { axis: Axis::SelfAxis, test: NodeTest::Node, predicates: Vec::new() });
+        }
+        if self.eat(&Token::DotDot) {
+            return Ok(Step { axis: Axis::Parent, test: NodeTest::Node, predicates: Vec::new() });
+        }
+        let axis = match (self.peek(), self.peek_at(1)) {
+            (Some(Token::At), _) => {
+                self.next();
+                Axis::Attribute
+            }
+            (Some(Token::Name(name)), Some(Token::ColonColon)) => {
+                let axis = match AXES.iter().find(|(axis, _)| axis == name) {
+                    Some(&(_, axis)) => axis,
+                    None => return Err(self.error(XPathErrorKind::UnknownAxis(name.clone()))),
+                };
+                self.index += 2;
+                axis
+            }
+            _ => Axis::Child,
+        };
+        let test = self.node_test()?;
+        let predicates = self.predicates()?;
+        return Ok(Step { axis, test, predicates });
+    }
+
+    fn node_test(&mut self) -> Result<NodeTest, XPathError> {
+        let name = match self.peek() {
+            Some(Token::Star) => {
+                self.next();
+                return Ok(NodeTest::Any);
+            }
+            Some(Token::Name(name)) => name.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        if self.peek_at(1) != Some(&Token::LeftParen) {
+            self.next();
+            return Ok(match name.strip_suffix(":*") {
+                Some(prefix) => NodeTest::Prefix(prefix.to_string()),
+                None => NodeTest::Name(name),
+            });
+        }
+        if !is_node_type(&name) {
+            return Err(self.unexpected());
+        }
+        self.index += 2;
+        let test = match name.as_str() {
+            "node" => NodeTest::Node,
+            "text" => NodeTest::Text,
+            "comment" => NodeTest::Comment,
+            _ => match self.peek() {
+                Some(Token::Literal(target)) => {
+                    let target = target.clone();
+                    self.next();
+                    NodeTest::ProcessingInstruction(Some(target))
+                }
+                _ => NodeTest::ProcessingInstruction(None),
+            },
+        };
+        self.expect(&Token::RightParen)?;
+        return Ok(test);
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>, XPathError> {
+        let mut predicates = Vec::new();
+        while self.eat(&Token::LeftBracket) {
+            predicates.push(self.expr()?);
+            self.expect(&Token::RightBracket)?;
+        }
+        return Ok(predicates);
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr, XPathError> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Variable(name)) => return Ok(Expr::Variable(name, offset)),
+            Some(Token::Literal(literal)) => return Ok(Expr::Literal(literal)),
+            Some(Token::Number(number)) => return Ok(Expr::Number(number)),
+            Some(Token::LeftParen) => {
+                let expr = self.expr()?;
+                self.expect(&Token::RightParen)?;
+                return Ok(expr);
+            }
+            Some(Token::Name(name)) => return self.function_call(name, offset),
+            _ => {
+                self.index -= 1;
+                return Err(self.unexpected());
+            }
+        }
+    }
+
+    /* After the name */
+    fn function_call(&mut self, name: String, offset: usize) -> Result<Expr, XPathError> {
+        let (function, min, max) = match FUNCTIONS.iter().find(|(function, ..)| *function == name) {
+            Some(&(_, function, min, max)) => (function, min, max),
+            None => return Err(XPathError { kind: XPathErrorKind::UnknownFunction(name), offset }),
+        };
+        self.expect(&Token::LeftParen)?;
+        let mut arguments = Vec::new();
+        if !self.eat(&Token::RightParen) {
+            loop {
+                let required = matches!(function, Function::Count | Function::Sum | Function::LocalName | Function::NamespaceUri | Function::Name);
+                arguments.push(self.node_set_operand(Parser::expr, required)?);
+                if self.eat(&Token::RightParen) {
+                    break;
+                }
+                self.expect(&Token::Comma)?;
+            }
+        }
+        if arguments.len() < min || arguments.len() > max {
+            return Err(XPathError { kind: XPathErrorKind::WrongArgumentCount(name), offset });
+        }
+        return Ok(Expr::Function(function, arguments));
+    }
+}
+
+fn is_node_type(name: &str) -> bool {
+    matches!(name, "comment" | "text" | "processing-instruction" | "node")
+}
+
+impl Expr {
+    /* False when the expression can never be a node-set, variables are only known when evaluating */
+    fn may_be_node_set(&self) -> bool {
+        match self {
+            Expr::Path(..) | Expr::Union(..) | Expr::Variable(..) | Expr::Function(Function::Id, _) => true,
+            Expr::Filter(expr, _) => expr.may_be_node_set(),
+            _ => false,
+        }
+    }
+
+    fn offset(&self) -> usize {
+        match self {
+            Expr::Variable(_, offset) => *offset,
+            Expr::Filter(expr, _) => expr.offset(),
+            _ => 0,
+        }
+    }
+}
+
+impl XPath {
+    pub fn parse(expression: &str) -> Result<XPath, XPathError> {
+        let mut parser = Parser { input: expression, tokens: tokenize(expression)?, index: 0 };
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return Err(parser.unexpected());
+        }
+        return Ok(XPath { expr });
+    }
+
+    pub fn evaluate(&self, document: &Document, context: NodeId) -> Result<Value, XPathError> {
+        self.evaluate_with_variables(document, context, &HashMap::new())
+    }
+
+    pub fn evaluate_with_variables(&self, document: &Document, context: NodeId, variables: &HashMap<String, Value>) -> Result<Value, XPathError> {
+        let evaluator = Evaluator::new(document, context, variables);
+        return evaluator.eval(&self.expr, &Context { node: XPathNode::Node(context), position: 1, size: 1 });
+    }
+}
+
+impl Document {
+    /* Evaluates the expression with the root as the context node */
+    pub fn evaluate(&self, expression: &str) -> Result<Value, XPathError> {
+        XPath::parse(expression)?.evaluate(self, self.root())
+    }
+}
+
+/* ---------- Evaluation ---------- */
+
+struct Context {
+    node: XPathNode,
+    /* Starts at 1 */
+    position: usize,
+    size: usize,
+}
+
+struct Evaluator<'a> {
+    document: &'a Document,
+    variables: &'a HashMap<String, Value>,
+    /* The root of the tree that contains the context node, "/" starts there */
+    root: NodeId,
+    /* Position of every node of the tree in document order */
+    order: HashMap<NodeId, usize>,
+}
+impl<'a> Evaluator<'a> {
+    fn new(document: &'a Document, context: NodeId, variables: &'a HashMap<String, Value>) -> Evaluator<'a> {
+        let root = document.ancestors(context).last().unwrap_or(context);
+        let order = std::iter::once(root).chain(document.descendants(root)).enumerate().map(|(index, id)| (id, index)).collect();
+        Evaluator { document, variables, root, order }
+    }
+
+    fn eval(&self, expr: &Expr, context: &Context) -> Result<Value, XPathError> {
+        let value = match expr {
+            Expr::Or(left, right) => Value::Boolean(self.eval(left, context)?.as_boolean() || self.eval(right, context)?.as_boolean()),
+            Expr::And(left, right) => Value::Boolean(self.eval(left, context)?.as_boolean() && self.eval(right, context)?.as_boolean()),
+            Expr::Compare(comparison, left, right) => {
+                let (left, right) = (self.eval(left, context)?, self.eval(right, context)?);
+                Value::Boolean(self.compare(*comparison, &left, &right))
+            }
+            Expr::Arithmetic(operator, left, right) => {
+                let left = self.eval(left, context)?.as_number(self.document);
+                let right = self.eval(right, context)?.as_number(self.document);
+                Value::Number(match operator {
+                    Arithmetic::Add => left + right,
+                    Arithmetic::Subtract => left - right,
+                    Arithmetic::Multiply => left * right,
+                    Arithmetic::Divide => left / right,
+                    Arithmetic::Modulo => left % right,
+                })
+            }
+            Expr::Negate(expr) => Value::Number(-self.eval(expr, context)?.as_number(self.document)),
+            Expr::Union(left, right) => {
+                let mut nodes = self.node_set(left, context)?;
+                nodes.extend(self.node_set(right, context)?);
+                self.sort(&mut nodes);
+                Value::NodeSet(nodes)
+            }
+            Expr::Path(start, steps) => {
+                let mut nodes = match start {
+                    PathStart::Root => vec![XPathNode::Node(self.root)],
+                    PathStart::Context => vec![context.node.clone()],
+                    PathStart::Expr(expr) => self.node_set(expr, context)?,
+                };
+                for step in steps {
+                    let mut next = Vec::new();
+                    for node in &nodes {
+                        next.extend(self.step(step, node)?);
+                    }
+                    self.sort(&mut next);
+                    nodes = next;
+                }
+                Value::NodeSet(nodes)
+            }
+            Expr::Filter(expr, predicates) => {
+                let mut nodes = self.node_set(expr, context)?;
+                for predicate in predicates {
+                    nodes = self.filter(nodes, predicate)?;
+                }
+                Value::NodeSet(nodes)
+            }
+            Expr::Literal(literal) => Value::String(literal.clone()),
+            Expr::Number(number) => Value::Number(*number),
+            Expr::Variable(name, offset) => match self.variables.get(name) {
+                Some(value) => value.clone(),
+                None => return Err(XPathError { kind: XPathErrorKind::UnknownVariable(name.clone()), offset: *offset }),
+            },
+            Expr::Function(function, arguments) => self.call(*function, arguments, context)?,
+        };
+        return Ok(value);
+    }
+
+    fn node_set(&self, expr: &Expr, context: &Context) -> Result<Vec<XPathNode>, XPathError> {
+        match self.eval(expr, context)? {
+            Value::NodeSet(nodes) => Ok(nodes),
+            _ => Err(XPathError { kind: XPathErrorKind::NotANodeSet, offset: expr.offset() }),
+        }
+    }
+
+    fn string(&self, value: Value) -> String {
+        value.as_string(self.document)
+    }
+
+    fn number(&self, value: Value) -> f64 {
+        value.as_number(self.document)
+    }
+
+    /* Sorts into document order and removes duplicates, attributes come after their element and before its children */
+    fn sort(&self, nodes: &mut Vec<XPathNode>) {
+        let key = |node: &XPathNode| match node {
+            XPathNode::Node(id) => (self.order.get(id).copied().unwrap_or(usize::MAX), 0),
+            XPathNode::Attribute(id, name) => {
+                let attributes = self.document.element(*id).and_then(|element| element.attributes());
+                let index = attributes.and_then(|attributes| attributes.iter().position(|attribute| attribute.name.eq_ignore_ascii_case(name)));
+                (self.order.get(id).copied().unwrap_or(usize::MAX), 1 + index.unwrap_or(0))
+            }
+        };
+        nodes.sort_by_key(key);
+        nodes.dedup();
+    }
+
+    fn step(&self, step: &Step, node: &XPathNode) -> Result<Vec<XPathNode>, XPathError> {
+        let mut nodes: Vec<XPathNode> = self.axis(step.axis, node).into_iter().filter(|node| self.test(&step.test, node)).collect();
+        for predicate in &step.predicates {
+            nodes = self.filter(nodes, predicate)?;
+        }
+        return Ok(nodes);
+    }
+
+    /* Positions follow the order of the nodes, which is reversed for the reverse axes */
+    fn filter(&self, nodes: Vec<XPathNode>, predicate: &Expr) -> Result<Vec<XPathNode>, XPathError> {
+        let size = nodes.len();
+        let mut filtered = Vec::new();
+        for (index, node) in nodes.into_iter().enumerate() {
+            let context = Context { node, position: index + 1, size };
+            let keep = match self.eval(predicate, &context)? {
+                Value::Number(number) => number == context.position as f64,
+                value => value.as_boolean(),
+            };
+            if keep {
+                filtered.push(context.node);
+            }
+        }
+        return Ok(filtered);
+    }
+
+    /* The nodes of the axis in axis order, nearest first for the reverse axes */
+    fn axis(&self, axis: Axis, node: &XPathNode) -> Vec<XPathNode> {
+        let document = self.document;
+        let nodes = |ids: &mut dyn Iterator<Item = NodeId>| ids.map(XPathNode::Node).collect::<Vec<XPathNode>>();
+        let id = match node {
+            XPathNode::Node(id) => *id,
+            /* The parent of an attribute is its element, but it is not a child of the element */
+            XPathNode::Attribute(element, _) => {
+                return match axis {
+                    Axis::Parent => vec![XPathNode::Node(*element)],
+                    Axis::Ancestor => nodes(&mut std::iter::once(*element).chain(document.ancestors(*element))),
+                    Axis::AncestorOrSelf => {
+                        let mut ancestors = vec![node.clone()];
+                        ancestors.extend(nodes(&mut std::iter::once(*element).chain(document.ancestors(*element))));
+                        ancestors
+                    }
+                    Axis::SelfAxis | Axis::DescendantOrSelf => vec![node.clone()],
+                    Axis::Following => nodes(&mut document.descendants(*element).chain(self.following(*element))),
+                    Axis::Preceding => nodes(&mut self.preceding(*element).into_iter()),
+                    _ => Vec::new(),
+                };
+            }
+        };
+        match axis {
+            Axis::Child => nodes(&mut document.children(id)),
+            Axis::Descendant => nodes(&mut document.descendants(id)),
+            Axis::DescendantOrSelf => nodes(&mut std::iter::once(id).chain(document.descendants(id))),
+            Axis::Parent => nodes(&mut document.parent(id).into_iter()),
+            Axis::Ancestor => nodes(&mut document.ancestors(id)),
+            Axis::AncestorOrSelf => nodes(&mut std::iter::once(id).chain(document.ancestors(id))),
+            Axis::FollowingSibling => nodes(&mut document.following_siblings(id)),
+            Axis::PrecedingSibling => nodes(&mut document.preceding_siblings(id)),
+            Axis::Following => nodes(&mut self.following(id)),
+            Axis::Preceding => nodes(&mut self.preceding(id).into_iter()),
+            Axis::SelfAxis => vec![node.clone()],
+            Axis::Namespace => Vec::new(),
+            Axis::Attribute => match document.element(id).filter(|element| element.tag().is_element()).and_then(|element| element.attributes()) {
+                Some(attributes) => attributes.iter().map(|attribute| XPathNode::Attribute(id, attribute.name.clone())).collect(),
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /* After the node in document order, without its descendants */
+    fn following(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let document = self.document;
+        std::iter::once(id)
+            .chain(document.ancestors(id))
+            .flat_map(move |current| document.following_siblings(current))
+            .flat_map(move |sibling| std::iter::once(sibling).chain(document.descendants(sibling)))
+    }
+
+    /* Before the node in reverse document order, without its ancestors */
+    fn preceding(&self, id: NodeId) -> Vec<NodeId> {
+        let document = self.document;
+        let mut preceding = Vec::new();
+        for current in std::iter::once(id).chain(document.ancestors(id)) {
+            for sibling in document.preceding_siblings(current) {
+                let mut subtree: Vec<NodeId> = std::iter::once(sibling).chain(document.descendants(sibling)).collect();
+                subtree.reverse();
+                preceding.extend(subtree);
+            }
+        }
+        return preceding;
+    }
+
+    fn test(&self, test: &NodeTest, node: &XPathNode) -> bool {
+        let has_prefix = |name: &str, prefix: &str| name.len() > prefix.len() && name[..prefix.len()].eq_ignore_ascii_case(prefix) && name[prefix.len()..].starts_with(':');
+        let id = match node {
+            XPathNode::Attribute(_, name) => {
+                return match test {
+                    NodeTest::Any | NodeTest::Node => true,
+                    NodeTest::Name(expected) => name.eq_ignore_ascii_case(expected),
+                    NodeTest::Prefix(prefix) => has_prefix(name, prefix),
+                    _ => false,
+                };
+            }
+            XPathNode::Node(id) => *id,
+        };
+        match (test, self.document.node(id)) {
+            (NodeTest::Node, _) => true,
+            (NodeTest::Text, Node::Text(_) | Node::CData(_)) => true,
+            (NodeTest::Comment, Node::Comment(_)) => true,
+            (NodeTest::ProcessingInstruction(target), Node::ProcessingInstruction(instruction)) => {
+                target.as_ref().is_none_or(|target| *target == instruction.target)
+            }
+            (NodeTest::Any, Node::Element(element)) => element.tag().is_element(),
+            (NodeTest::Name(name), Node::Element(element)) => element.tag().is_element() && element.tag().name().eq_ignore_ascii_case(name),
+            (NodeTest::Prefix(prefix), Node::Element(element)) => element.tag().is_element() && has_prefix(element.tag().name(), prefix),
+            _ => false,
+        }
+    }
+
+    /* See https://www.w3.org/TR/1999/REC-xpath-19991116/#booleans, a node-set compares true when any of its nodes does */
+    fn compare(&self, comparison: Comparison, left: &Value, right: &Value) -> bool {
+        let atoms = |value: &Value, other: &Value| match (value, other) {
+            (Value::NodeSet(nodes), Value::Boolean(_)) => vec![Value::Boolean(!nodes.is_empty())],
+            (Value::NodeSet(nodes), _) => nodes.iter().map(|node| Value::String(node.string_value(self.document))).collect(),
+            (value, _) => vec![value.clone()],
+        };
+        let (left, right) = (atoms(left, right), atoms(right, left));
+        return left.iter().any(|left| right.iter().any(|right| self.compare_atoms(comparison, left, right)));
+    }
+
+    fn compare_atoms(&self, comparison: Comparison, left: &Value, right: &Value) -> bool {
+        let equal = match (left, right) {
+            _ if !matches!(comparison, Comparison::Equal | Comparison::NotEqual) => None,
+            (Value::Boolean(_), _) | (_, Value::Boolean(_)) => Some(left.as_boolean() == right.as_boolean()),
+            (Value::Number(_), _) | (_, Value::Number(_)) => Some(self.number(left.clone()) == self.number(right.clone())),
+            _ => Some(self.string(left.clone()) == self.string(right.clone())),
+        };
+        let (left, right) = (self.number(left.clone()), self.number(right.clone()));
+        match comparison {
+            Comparison::Equal => equal.unwrap_or(false),
+            Comparison::NotEqual => !equal.unwrap_or(true),
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+
+    /* ---------- Functions ---------- */
+
+    fn call(&self, function: Function, arguments: &[Expr], context: &Context) -> Result<Value, XPathError> {
+        let mut values = Vec::new();
+        for argument in arguments {
+            values.push(self.eval(argument, context)?);
+        }
+        /* Functions that take an optional argument default to the context node */
+        let argument = |index: usize| values.get(index).cloned().unwrap_or_else(|| Value::NodeSet(vec![context.node.clone()]));
+        let string = |index: usize| self.string(argument(index));
+        let number = |index: usize| self.number(argument(index));
+        let value = match function {
+            Function::Last => Value::Number(context.size as f64),
+            Function::Position => Value::Number(context.position as f64),
+            Function::Count => Value::Number(argument(0).nodes().map_or(0, |nodes| nodes.len()) as f64),
+            Function::Id => {
+                let ids: Vec<String> = match argument(0) {
+                    Value::NodeSet(nodes) => nodes.iter().map(|node| node.string_value(self.document)).collect(),
+                    value => vec![self.string(value)],
+                };
+                let ids: Vec<&str> = ids.iter().flat_map(|ids| ids.split(is_whitespace)).filter(|id| !id.is_empty()).collect();
+                let nodes = self.document.descendants(self.root).filter(|&id| {
+                    self.document.element(id).and_then(|element| element.attribute("id")).is_some_and(|value| ids.contains(&value))
+                });
+                Value::NodeSet(nodes.map(XPathNode::Node).collect())
+            }
+            Function::LocalName | Function::Name | Function::NamespaceUri => {
+                let name = match argument(0).nodes().and_then(|nodes| nodes.first()) {
+                    Some(node) if function != Function::NamespaceUri => node.name(self.document),
+                    _ => String::new(),
+                };
+                Value::String(match function {
+                    Function::LocalName => name.rsplit(':').next().unwrap_or_default().to_string(),
+                    _ => name,
+                })
+            }
+            Function::String => Value::String(string(0)),
+            Function::Concat => Value::String((0..values.len()).map(string).collect()),
+            Function::StartsWith => Value::Boolean(string(0).starts_with(&string(1))),
+            Function::Contains => Value::Boolean(string(0).contains(&string(1))),
+            Function::SubstringBefore => {
+                let (value, separator) = (string(0), string(1));
+                Value::String(value.find(&separator).map_or(String::new(), |index| value[..index].to_string()))
+            }
+            Function::SubstringAfter => {
+                let (value, separator) = (string(0), string(1));
+                Value::String(value.find(&separator).map_or(String::new(), |index| value[index + separator.len()..].to_string()))
+            }
+            /* Characters at the positions p where round(start) <= p < round(start) + round(length), with NaN comparing false */
+            Function::Substring => {
+                let start = round(number(1));
+                let end = match values.len() {
+                    3 => start + round(number(2)),
+                    _ => f64::INFINITY,
+                };
+                let value = string(0);
+                let chars = value.chars().enumerate().filter(|&(index, _)| {
+                    let position = (index + 1) as f64;
+                    position >= start && position < end
+                });
+                Value::String(chars.map(|(_, ch)| ch).collect())
+            }
+            Function::StringLength => Value::Number(string(0).chars().count() as f64),
+            Function::NormalizeSpace => Value::String(string(0).split(is_whitespace).filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ")),
+            Function::Translate => {
+                let (from, to): (Vec<char>, Vec<char>) = (string(1).chars().collect(), string(2).chars().collect());
+                let value = string(0);
+                let translated = value.chars().filter_map(|ch| match from.iter().position(|&from| from == ch) {
+                    Some(index) => to.get(index).copied(),
+                    None => Some(ch),
+                });
+                Value::String(translated.collect())
+            }
+            Function::Boolean => Value::Boolean(argument(0).as_boolean()),
+            Function::Not => Value::Boolean(!argument(0).as_boolean()),
+            Function::True => Value::Boolean(true),
+            Function::False => Value::Boolean(false),
+            Function::Lang => Value::Boolean(self.lang(&context.node, &string(0))),
+            Function::Number => Value::Number(number(0)),
+            Function::Sum => {
+                let nodes = argument(0).nodes().map(<[XPathNode]>::to_vec).unwrap_or_default();
+                Value::Number(nodes.iter().map(|node| parse_number(&node.string_value(self.document))).sum())
+            }
+            Function::Floor => Value::Number(number(0).floor()),
+            Function::Ceiling => Value::Number(number(0).ceil()),
+            Function::Round => Value::Number(round(number(0))),
+        };
+        return Ok(value);
+    }
+
+    /* The nearest xml:lang or lang attribute is the language, "en" matches "en" and "en-US" */
+    fn lang(&self, node: &XPathNode, lang: &str) -> bool {
+        let id = match node {
+            XPathNode::Node(id) | XPathNode::Attribute(id, _) => *id,
+        };
+        for id in std::iter::once(id).chain(self.document.ancestors(id)) {
+            let element = match self.document.element(id) {
+                Some(element) => element,
+                None => continue,
+            };
+            if let Some(value) = element.attribute("xml:lang").or_else(|| element.attribute("lang")) {
+                let value = value.to_ascii_lowercase();
+                let lang = lang.to_ascii_lowercase();
+                return value == lang || value.starts_with(&format!("{}-", lang));
+            }
+        }
+        return false;
+    }
+}
+
+/* Rounds halves up, keeping NaN, infinities and negative zero */
+fn round(number: f64) -> f64 {
+    if !number.is_finite() || number == 0.0 {
+        return number;
+    }
+    if (-0.5..0.0).contains(&number) {
+        return -0.0;
+    }
+    return (number + 0.5).floor();
+}
+
+/* Only an optional minus sign and decimal digits with whitespace around, anything else is NaN */
+fn parse_number(value: &str) -> f64 {
+    let value = value.trim_matches(is_whitespace);
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let valid = !digits.is_empty()
+        && digits != "."
+        && digits.chars().all(|ch| ch.is_ascii_digit() || ch == '.')
+        && digits.matches('.').count() <= 1;
+    return match valid {
+        true => value.parse().unwrap_or(f64::NAN),
+        false => f64::NAN,
+    };
+}
+
+/* Integers without a decimal point, never in exponent notation */
+fn format_number(number: f64) -> String {
+    if number.is_nan() {
+        return "NaN".to_string();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if number == 0.0 {
+        return "0".to_string();
+    }
+    return number.to_string();
+}
+
+impl XPathNode {
+    /* See https://www.w3.org/TR/1999/REC-xpath-19991116/#data-model */
+    pub fn string_value(&self, document: &Document) -> String {
+        let id = match self {
+            XPathNode::Attribute(id, name) => {
+                return document.element(*id).and_then(|element| element.attribute(name)).unwrap_or_default().to_string();
+            }
+            XPathNode::Node(id) => *id,
+        };
+        match document.node(id) {
+            Node::Element(_) => std::iter::once(id)
+                .chain(document.descendants(id))
+                .filter_map(|id| match document.node(id) {
+                    Node::Text(text) | Node::CData(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            Node::Text(text) | Node::CData(text) | Node::Comment(text) => text.clone(),
+            Node::ProcessingInstruction(instruction) => instruction.data.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /* The qualified name of elements and attributes and the target of processing instructions */
+    pub fn name(&self, document: &Document) -> String {
+        match self {
+            XPathNode::Attribute(_, name) => name.clone(),
+            XPathNode::Node(id) => match document.node(*id) {
+                Node::Element(element) if element.tag().is_element() => element.tag().name().to_string(),
+                Node::ProcessingInstruction(instruction) => instruction.target.clone(),
+                _ => String::new(),
+            },
+        }
+    }
+}
+
+impl Value {
+    pub fn nodes(&self) -> Option<&[XPathNode]> {
+        match self {
+            Value::NodeSet(nodes) => Some(nodes),
+            _ => None,
+        }
+    }
+
+    pub fn as_boolean(&self) -> bool {
+        match self {
+            Value::NodeSet(nodes) => !nodes.is_empty(),
+            Value::String(string) => !string.is_empty(),
+            Value::Number(number) => *number != 0.0 && !number.is_nan(),
+            Value::Boolean(boolean) => *boolean,
+        }
+    }
+
+    /* A node-set is the string-value of its first node */
+    pub fn as_string(&self, document: &Document) -> String {
+        match self {
+            Value::NodeSet(nodes) => nodes.first().map(|node| node.string_value(document)).unwrap_or_default(),
+            Value::String(string) => string.clone(),
+            Value::Number(number) => format_number(*number),
+            Value::Boolean(boolean) => boolean.to_string(),
+        }
+    }
+
+    pub fn as_number(&self, document: &Document) -> f64 {
+        match self {
+            Value::Number(number) => *number,
+            Value::Boolean(boolean) => *boolean as u8 as f64,
+            value => parse_number(&value.as_string(document)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manipulators::dom::DOM;
+
+    const HTML: &str = "<!DOCTYPE html><html><head><title>T</title></head><body>\
+        <ul id=list><li class=a>1</li><li>2</li><li class=a lang=en-GB>3</li><li>4</li></ul>\
+        <p>x <b>y</b> z</p><!--c--></body></html>";
+
+    fn strings(document: &Document, expression: &str) -> Vec<String> {
+        let value = document.evaluate(expression).unwrap();
+        return value.nodes().unwrap().iter().map(|node| node.string_value(document)).collect();
+    }
+
+    fn string(document: &Document, expression: &str) -> String {
+        return document.evaluate(expression).unwrap().as_string(document);
+    }
+
+    #[test]
+    fn axes() {
+        let document = DOM::parse_str(HTML).unwrap();
+        assert_eq!(strings(&document, "//li[2]/following-sibling::li"), ["3", "4"]);
+        assert_eq!(strings(&document, "//li[3]/preceding-sibling::li"), ["1", "2"]);
+        assert_eq!(strings(&document, "//li[3]/preceding-sibling::li[1]"), ["2"]);
+        assert_eq!(strings(&document, "//b/ancestor::*/@id | //b/parent::p"), ["x y z"]);
+        assert_eq!(strings(&document, "//li[4]/ancestor-or-self::*[@id]"), ["1234"]);
+        assert_eq!(strings(&document, "//ul/following::text()"), ["x ", "y", " z"]);
+        assert_eq!(strings(&document, "//b/preceding::li[1]"), ["4"]);
+        assert_eq!(strings(&document, "//p/descendant-or-self::node()[self::b or self::p]"), ["x y z", "y"]);
+        assert_eq!(strings(&document, "//li[@class='a']/attribute::lang"), ["en-GB"]);
+        assert_eq!(strings(&document, "/descendant::comment()"), ["c"]);
+        assert_eq!(strings(&document, "//li/namespace::*"), Vec::<String>::new());
+        assert_eq!(strings(&document, "//ul/li[last()]/.."), ["1234"]);
+    }
+
+    #[test]
+    fn predicates_and_positions() {
+        let document = DOM::parse_str(HTML).unwrap();
+        assert_eq!(strings(&document, "//li[position() > 1][2]"), ["3"]);
+        assert_eq!(strings(&document, "(//li)[last() - 1]"), ["3"]);
+        assert_eq!(strings(&document, "//li[@class][not(@lang)]"), ["1"]);
+        assert_eq!(strings(&document, "//li[. = 2 or . = '4']"), ["2", "4"]);
+        assert_eq!(strings(&document, "id('list')/li[lang('en')]"), ["3"]);
+    }
+
+    #[test]
+    fn functions() {
+        let document = DOM::parse_str(HTML).unwrap();
+        assert_eq!(string(&document, "count(//li)"), "4");
+        assert_eq!(string(&document, "sum(//li) div 4"), "2.5");
+        assert_eq!(string(&document, "concat(name(//ul), '-', string(//ul/@id))"), "ul-list");
+        assert_eq!(string(&document, "normalize-space('  a \n b ')"), "a b");
+        assert_eq!(string(&document, "substring('12345', 1.5, 2.6)"), "234");
+        assert_eq!(string(&document, "substring-before('a/b/c', '/')"), "a");
+        assert_eq!(string(&document, "substring-after('a/b/c', '/')"), "b/c");
+        assert_eq!(string(&document, "translate('bar', 'abc', 'ABC')"), "BAr");
+        assert_eq!(string(&document, "string-length(//title)"), "1");
+        assert_eq!(string(&document, "round(2.5) + floor(-1.5) + ceiling(0.2)"), "2");
+        assert_eq!(string(&document, "7 mod 3 * -1"), "-1");
+        assert_eq!(string(&document, "1 div 0"), "Infinity");
+        assert_eq!(string(&document, "number('x')"), "NaN");
+        assert_eq!(string(&document, "starts-with(//p, 'x') and contains(//p, 'y')"), "true");
+        assert_eq!(string(&document, "//li = 3"), "true");
+        assert_eq!(string(&document, "//li != 3"), "true");
+        assert_eq!(string(&document, "boolean(//table)"), "false");
+    }
+
+    #[test]
+    fn variables_and_context() {
+        let document = DOM::parse_str(HTML).unwrap();
+        let list = document.query_selector("ul").unwrap().unwrap();
+        let xpath = XPath::parse("li[. > $min]").unwrap();
+        let variables = HashMap::from([("min".to_string(), Value::Number(2.0))]);
+        let value = xpath.evaluate_with_variables(&document, list, &variables).unwrap();
+        assert_eq!(value.nodes().unwrap().len(), 2);
+        assert_eq!(XPath::parse("count(/html/body/*)").unwrap().evaluate(&document, list).unwrap(), Value::Number(2.0));
+        assert_eq!(xpath.evaluate(&document, list).unwrap_err().kind, XPathErrorKind::UnknownVariable("min".to_string()));
+    }
+
+    #[test]
+    fn errors() {
+        let kind = |expression: &str| XPath::parse(expression).unwrap_err().kind;
+        assert_eq!(kind("//li["), XPathErrorKind::UnexpectedEnd);
+        assert_eq!(kind("sideways::li"), XPathErrorKind::UnknownAxis("sideways".to_string()));
+        assert_eq!(kind("frobnicate()"), XPathErrorKind::UnknownFunction("frobnicate".to_string()));
+        assert_eq!(kind("count()"), XPathErrorKind::WrongArgumentCount("count".to_string()));
+        let document = DOM::parse_str(HTML).unwrap();
+        assert_eq!(document.evaluate("1/li").unwrap_err().kind, XPathErrorKind::NotANodeSet);
+    }
+}
@@ -3,6 +3,8 @@
 use std::collections::{HashMap};
 use crate::utils::clean_string;
 
+pub use super::consumer::Consumer;
+
 #[derive(Debug)]
 pub enum Value {
     Int(i32),
@@ -34,50 +36,14 @@ pub enum TokenType {
     CloseBrack,
 }
 
-#[derive(Debug)]
-pub struct Consumer {
-    pub pos: usize,
-    pub ch: char,
-    pub size: usize,
-    vec: Vec<u8>,
-}
-impl Consumer {
-    pub fn new(src: &str) -> Consumer {
-        return Consumer {
-            vec: src.bytes().collect::<Vec<u8>>(),
-            pos: 0,
-            ch: src.bytes().collect::<Vec<u8>>()[0] as char,
-            size: src.bytes().len(),
-        };
-    }
-
-    pub fn eat(&mut self) -> char {
-        if self.pos + 1 < self.size {
-            self.pos += 1;
-            self.ch = self.vec[self.pos] as char;
-        } else {
-            self.pos = self.size;
-        }
-        return self.ch;
-    }
-
-    pub fn peek(&self) -> Result<char, String> {
-        if self.pos + 1 < self.size {
-            return Ok(self.vec[self.pos + 1] as char);
-        } else {
-            return Err("EOF / EOL".to_string());
-        }
-    }
-}
-
 pub struct Tokenizer {}
 impl Tokenizer {
     pub fn tokenize(string: &str) -> Vec<TokenType> {
         let mut token_types: Vec<TokenType> = Vec::new();
         let mut value: String = String::new();
         let mut consumer: Consumer = Consumer::new(string);
-        while consumer.pos < consumer.size {
-            match consumer.ch {
+        while let Some(ch) = consumer.current() {
+            match ch {
                 ',' => {
                     if !value.is_empty() {
                         token_types.push(TokenType::Ident(value.trim().to_string()));
@@ -125,7 +91,7 @@ impl Json {
     pub fn parse(string: &str) -> Vec<VariableTypedValue> {
         let mut variables: Vec<VariableTypedValue> = Vec::new();
         let mut tokens: Vec<TokenType> = Tokenizer::tokenize(string);
-        while tokens.len() > 0 {
+        while !tokens.is_empty() {
             let token = &tokens[0];
             match token {
                 TokenType::OpenCurly => variables.push(Self::parse_obj(&mut tokens)),
@@ -138,7 +104,7 @@ impl Json {
 
                 _ => {} //println!("Token \"{:?}\" has not been matched yet", token)
             }
-            if tokens.len() > 0 {
+            if !tokens.is_empty() {
                 tokens = tokens[1..].to_vec();
             }
         }
@@ -150,7 +116,7 @@ impl Json {
         //consume the "[" to prevent infinite loop
         *tokens = tokens[1..].to_vec();
 
-        while tokens.len() > 0 && tokens[0] != TokenType::CloseBrack {
+        while !tokens.is_empty() && tokens[0] != TokenType::CloseBrack {
             let token: &TokenType = &tokens[0];
             match token {
                 TokenType::OpenBrack => values.push(Self::parse_arr(tokens)),
@@ -160,12 +126,12 @@ impl Json {
                 )),
                 _ => {}
             }
-            if tokens.len() > 0 {
+            if !tokens.is_empty() {
                 *tokens = tokens[1..].to_vec();
             }
         }
         //consume the "]" to prevent adding it as a variable
-        if tokens.len() > 0 {
+        if !tokens.is_empty() {
             *tokens = tokens[1..].to_vec();
         }
         return VariableTypedValue::Array(values);
@@ -177,7 +143,7 @@ impl Json {
         //consume "{" to prevent infinate loop
         *tokens = tokens[1..].to_vec();
        
-        while tokens.len() > 0 && tokens[0] != TokenType::CloseCurly {
+        while !tokens.is_empty() && tokens[0] != TokenType::CloseCurly {
             let token = &tokens[0];
 
             match token {
@@ -187,12 +153,9 @@ impl Json {
                 }
                 token => {
                     if key.is_empty() {
-                        match token {
-                            TokenType::Ident(val) => {
-                                key = clean_string(val);
-                            }
-                            _ => {}
-                        };
+                        if let TokenType::Ident(val) = token {
+                            key = clean_string(val);
+                        }
                     } else {
                         match token {
                             TokenType::OpenBrack => {
@@ -208,12 +171,12 @@ impl Json {
                     }
                 }
             }
-            if tokens.len() > 0 {
+            if !tokens.is_empty() {
                 *tokens = tokens[1..].to_vec();
             }
         }
         //consume the "}" to prevent adding it as a variable
-        if tokens.len() > 0 {
+        if !tokens.is_empty() {
             *tokens = tokens[1..].to_vec();
         }
         VariableTypedValue::Object(obj)
@@ -237,7 +200,6 @@ impl Json {
                 }
                 str if str.contains(".") => Ok(Value::Float(str.parse::<f32>().unwrap())),
                 str => Ok(Value::Int(str.parse::<i32>().unwrap())),
-                str => Err(format!("Could not parse value: \"{}\"", str)),
             },
             t => Err(format!("Token value cannot be parsed, {:?}", t)),
         }
@@ -1,2 +1,3 @@
+pub mod consumer;
 pub mod dom;
 pub mod json;
\ No newline at end of file
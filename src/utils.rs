@@ -1,3 +1,4 @@
+#[allow(dead_code)]
 pub trait Shift<T> {
     fn shift(&mut self) -> () where T: Clone;
 }